@@ -1,47 +1,110 @@
-use anchor_spl::token::{transfer, Transfer};
+use anchor_lang::solana_program::{hash, keccak, program_option::COption};
+use anchor_lang::system_program::{allocate, assign, Allocate, Assign};
+use anchor_lang::Discriminator;
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token::spl_token::instruction::AuthorityType;
+use anchor_spl::token::{
+    approve, burn, close_account, mint_to, revoke, set_authority, transfer, Approve, Burn,
+    CloseAccount, MintTo, Revoke, SetAuthority, Transfer,
+};
+use bytemuck::Zeroable;
 use switchboard_solana::prelude::*;
 
 declare_id!("Hxoo6xf3yChvNbyyWAShypeQPEEmEUXWuWWCQu5BmTfi");
 
+/// The Pyth receiver program, owner of posted price updates.
+pub mod pyth_receiver {
+    use super::*;
+
+    declare_id!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+}
+
 pub const GAME_STATE_SEED: &[u8] = b"GAME_STATE";
 pub const GAME_CONFIG_SEED: &[u8] = b"GAME_CONFIG";
 pub const BET_SEED: &[u8] = b"BET";
+pub const SUBSCRIPTION_SEED: &[u8] = b"SUBSCRIPTION";
+pub const POSITION_MINT_SEED: &[u8] = b"POSITION_MINT";
+pub const VOUCHER_SEED: &[u8] = b"VOUCHER";
+pub const USER_PROFILE_SEED: &[u8] = b"USER_PROFILE";
+pub const REFERRER_SEED: &[u8] = b"REFERRER";
+pub const TOURNAMENT_SEED: &[u8] = b"TOURNAMENT";
+pub const TOURNAMENT_ENTRY_SEED: &[u8] = b"TOURNAMENT_ENTRY";
+pub const SESSION_SEED: &[u8] = b"SESSION";
+pub const PAIR_SEED: &[u8] = b"PAIR";
+pub const CONFIG_CHANGE_SEED: &[u8] = b"CONFIG_CHANGE";
+pub const SETTLEMENT_BATCH_SEED: &[u8] = b"SETTLEMENT_BATCH";
 pub const MIN_BET: u64 = 5_000_000;
 pub const MAX_BET: u64 = 50_000_000;
 pub const MAX_UTILIZED_LIQUIDITY: u64 = 255_000_000;
 pub const CANCEL_BUFFER: u64 = 1 * 24 * 60 * 60;
 pub const MAX_INTERVAL: u32 = 1 * 24 * 60 * 60;
 pub const MIN_INTERVAL: u32 = 2 * 60;
+pub const MIN_ENTRY_DELAY: u32 = 5;
+pub const MAX_ENTRY_DELAY: u32 = 1 * 60 * 60;
+pub const ENTRY_DELAY_LIMIT: u32 = 1 * 24 * 60 * 60;
+pub const BET_COOLDOWN: u32 = 5;
+pub const MAX_OPEN_BETS: u32 = 10;
 pub const LEVERAGE: u16 = 1700;
+pub const CONFIG_CHANGE_DELAY: u32 = 2 * 24 * 60 * 60;
+pub const MAX_DISPUTE_WINDOW: u32 = 1 * 24 * 60 * 60;
+pub const MAX_PUBLISH_DELAY: u32 = 10;
+pub const PUBLISH_DELAY_LIMIT: u32 = i16::MAX as u32;
+pub const SETTLEMENT_BUCKET: u64 = 5 * 60;
+pub const MAX_BATCH_BETS: usize = 3;
+pub const REFERRAL_SHARE_BPS: u16 = 2000;
+pub const MAX_BPS: u16 = 10_000;
 pub const MAX_PAIRS: usize = 10;
+pub const MAX_TOURNAMENT_WINNERS: usize = 10;
+pub const GAME_CONFIG_VERSION: u8 = 5;
+pub const GAME_STATE_VERSION: u8 = 2;
+pub const BET_VERSION: u8 = 3;
 
 #[program]
 pub mod game {
     use super::*;
 
-    pub fn initialize(ctx: Context<Initialize>) -> anchor_lang::Result<()> {
+    /// A default `switchboard_routine` leaves bets to be settled by request until
+    /// a routine is set through a config change.
+    pub fn initialize(
+        ctx: Context<Initialize>,
+        game_id: u64,
+        switchboard_routine: Pubkey,
+    ) -> anchor_lang::Result<()> {
         let mut config = ctx.accounts.game_config.load_init()?;
 
         config.bump = ctx.bumps.game_config;
+        config.version = GAME_CONFIG_VERSION;
+        config.game_id = game_id;
         config.authority = ctx.accounts.authority.key();
+        config.pauser = ctx.accounts.authority.key();
+        config.risk_manager = ctx.accounts.authority.key();
+        config.treasurer = ctx.accounts.authority.key();
         config.min_bet = MIN_BET;
         config.max_bet = MAX_BET;
         config.max_utilized_liquidity = MAX_UTILIZED_LIQUIDITY;
         config.cancel_buffer = CANCEL_BUFFER;
         config.max_interval = MAX_INTERVAL;
         config.min_interval = MIN_INTERVAL;
+        config.min_entry_delay = MIN_ENTRY_DELAY;
+        config.max_entry_delay = MAX_ENTRY_DELAY;
+        config.bet_cooldown = BET_COOLDOWN;
+        config.max_open_bets = MAX_OPEN_BETS;
+        config.config_change_delay = CONFIG_CHANGE_DELAY;
+        config.max_publish_delay = MAX_PUBLISH_DELAY;
         config.leverage = LEVERAGE;
+        config.referral_share_bps = REFERRAL_SHARE_BPS;
         config.switchboard_function = ctx.accounts.switchboard_function.key();
+        config.switchboard_routine = switchboard_routine;
         config.token_mint = ctx.accounts.token_mint.key();
         config.game_escrow = ctx.accounts.game_escrow.key();
-        config.num_pairs = 2;
-        config.accepted_pairs[0] = *b"BTCUSDXX";
-        config.accepted_pairs[1] = *b"ETHUSDXX";
 
         let mut state = ctx.accounts.game_state.load_init()?;
         state.bump = ctx.bumps.game_state;
+        state.version = GAME_STATE_VERSION;
+        state.game_id = game_id;
         state.next_bet_id = 0;
         state.locked_liquidity = 0;
+        state.unclaimed_referral_rewards = 0;
 
         Ok(())
     }
@@ -52,93 +115,271 @@ pub mod game {
         pair: [u8; 8],
         interval: u32,
         is_long: bool,
+        start_time: u64,
+        allowlist_proof: Vec<[u8; 32]>,
     ) -> anchor_lang::prelude::Result<()> {
         let config = &ctx.accounts.game_config.load()?;
+        if config.paused {
+            return Err(error!(GameError::GamePaused));
+        }
+        if ctx.accounts.accepted_pair.load()?.disabled {
+            return Err(error!(GameError::PairDisabled));
+        }
+        config.validate_bet(amount, interval)?;
+        config.validate_allowlist(&ctx.accounts.user.key(), &allowlist_proof)?;
 
-        if amount < config.min_bet || amount > config.max_bet {
-            return Err(error!(GameError::InvalidAmount));
+        // The open price must be sampled strictly after the bet lands, otherwise
+        // the bettor could pick a price that is already known. A zero start time
+        // opens the bet as early as the config allows.
+        let clock = Clock::get()?;
+        let current_timestamp = clock.unix_timestamp as u64;
+        let earliest_start_time = current_timestamp
+            .checked_add(config.min_entry_delay as u64)
+            .ok_or(error!(GameError::MathOverflow))?;
+        let latest_start_time = current_timestamp
+            .checked_add(config.max_entry_delay as u64)
+            .ok_or(error!(GameError::MathOverflow))?;
+        let start_time = if start_time == 0 {
+            earliest_start_time
+        } else {
+            start_time
+        };
+
+        if start_time < earliest_start_time || start_time > latest_start_time {
+            return Err(error!(GameError::InvalidStartTime));
         }
 
-        if interval < config.min_interval || interval > config.max_interval {
-            return Err(error!(GameError::InvalidInterval));
+        // A session key bets on behalf of its user within the limits the user
+        // approved, so the main wallet doesn't have to sign every bet.
+        if let Some(session) = &ctx.accounts.session {
+            let mut session = session.load_mut()?;
+
+            if session.user != ctx.accounts.user.key()
+                || session.session_key != ctx.accounts.payer.key()
+                || session.expires_at <= current_timestamp
+            {
+                return Err(error!(GameError::InvalidSession));
+            }
+
+            if session.num_pairs > 0
+                && !session
+                    .allowed_pairs
+                    .get(0..session.num_pairs as usize)
+                    .unwrap_or(&[])
+                    .contains(&pair)
+            {
+                return Err(error!(GameError::InvalidPair));
+            }
+
+            if ctx.accounts.voucher.is_none() {
+                let spent = session
+                    .spent
+                    .checked_add(amount)
+                    .ok_or(error!(GameError::MathOverflow))?;
+                if spent > session.spending_cap {
+                    return Err(error!(GameError::SessionCapExceeded));
+                }
+                session.spent = spent;
+            }
+        } else if ctx.accounts.user.key() != ctx.accounts.payer.key() {
+            return Err(error!(GameError::Unauthorized));
         }
 
-        if !config
-            .accepted_pairs
-            .get(0..config.num_pairs as usize)
-            .unwrap_or(&[])
-            .contains(&pair)
-        {
-            return Err(error!(GameError::InvalidPair));
+        if let Some(tournament_entry) = &ctx.accounts.tournament_entry {
+            let mut tournament_entry = tournament_entry.load_mut()?;
+            let tournament = match &ctx.accounts.tournament {
+                Some(tournament) if tournament.key() == tournament_entry.tournament => {
+                    tournament.load()?
+                }
+                _ => return Err(error!(GameError::InvalidTournament)),
+            };
+
+            if tournament_entry.user != ctx.accounts.user.key()
+                || ctx.accounts.voucher.is_some()
+                || start_time < tournament.start_time
+                || start_time.saturating_add(interval as u64) > tournament.end_time
+            {
+                return Err(error!(GameError::InvalidTournament));
+            }
+
+            if tournament.num_pairs > 0
+                && !tournament
+                    .allowed_pairs
+                    .get(0..tournament.num_pairs as usize)
+                    .unwrap_or(&[])
+                    .contains(&pair)
+            {
+                return Err(error!(GameError::InvalidPair));
+            }
+
+            tournament_entry.open_bets = tournament_entry
+                .open_bets
+                .checked_add(1)
+                .ok_or(error!(GameError::MathOverflow))?;
+            tournament_entry.num_bets = tournament_entry
+                .num_bets
+                .checked_add(1)
+                .ok_or(error!(GameError::MathOverflow))?;
+        }
+
+        // A voucher funds the stake from the house, so a win only pays the profit.
+        if let Some(voucher) = &ctx.accounts.voucher {
+            let mut voucher = voucher.load_mut()?;
+
+            if voucher.user != ctx.accounts.user.key()
+                || voucher.uses >= voucher.max_uses
+                || voucher.expires_at <= current_timestamp
+            {
+                return Err(error!(GameError::InvalidVoucher));
+            }
+
+            if voucher.amount != amount {
+                return Err(error!(GameError::InvalidAmount));
+            }
+
+            if voucher.num_pairs > 0
+                && !voucher
+                    .allowed_pairs
+                    .get(0..voucher.num_pairs as usize)
+                    .unwrap_or(&[])
+                    .contains(&pair)
+            {
+                return Err(error!(GameError::InvalidPair));
+            }
+
+            voucher.uses = voucher.uses.checked_add(1).ok_or(error!(GameError::MathOverflow))?;
         }
 
         let mut state = ctx.accounts.game_state.load_mut()?;
         let bet_id = state.next_bet_id;
-        let available_liquidity = config.max_utilized_liquidity - state.locked_liquidity;
-        let payout = (amount * config.leverage as u64) / 1000;
+        let available_liquidity = config
+            .max_utilized_liquidity
+            .checked_sub(state.locked_liquidity)
+            .ok_or(error!(GameError::InsufficientLiquidity))?;
+        let payout = amount
+            .checked_mul(config.leverage as u64)
+            .ok_or(error!(GameError::MathOverflow))?
+            / 1000;
+        let payout = match ctx.accounts.voucher {
+            Some(_) => payout.checked_sub(amount).ok_or(error!(GameError::MathUnderflow))?,
+            None => payout,
+        };
 
         if payout > available_liquidity {
             return Err(error!(GameError::InsufficientLiquidity));
         }
 
-        // Transfer token
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.user_token_account.to_account_info().clone(),
-            to: ctx.accounts.game_escrow.to_account_info().clone(),
-            authority: ctx.accounts.payer.to_account_info().clone(),
+        // The bettor's first referrer sticks to their profile and earns a share of
+        // the house edge on every later bet the bettor loses.
+        let mut user_profile = match ctx.accounts.user_profile.load_mut() {
+            Ok(user_profile) => user_profile,
+            Err(_) => {
+                let mut user_profile = ctx.accounts.user_profile.load_init()?;
+                user_profile.bump = ctx.bumps.user_profile;
+                user_profile.user = ctx.accounts.user.key();
+                user_profile
+            }
+        };
+
+        user_profile.open_bet(config, current_timestamp)?;
+
+        if let Some(referrer) = &ctx.accounts.referrer {
+            if user_profile.referrer == Pubkey::default() {
+                if referrer.load()?.wallet == ctx.accounts.user.key() {
+                    return Err(error!(GameError::InvalidReferrer));
+                }
+                user_profile.referrer = referrer.key();
+            }
+        }
+
+        if user_profile.referrer != Pubkey::default() {
+            let referrer = match &ctx.accounts.referrer {
+                Some(referrer) if referrer.key() == user_profile.referrer => referrer,
+                _ => return Err(error!(GameError::InvalidReferrer)),
+            };
+
+            // Voucher stakes are house money and carry no edge to share. The reward
+            // itself accrues on settlement, as refunded bets earn nothing.
+            if ctx.accounts.voucher.is_none() {
+                let mut referrer = referrer.load_mut()?;
+                referrer.referred_volume = referrer
+                    .referred_volume
+                    .checked_add(amount)
+                    .ok_or(error!(GameError::MathOverflow))?;
+            }
+        }
+
+        let game_id_bytes = config.game_id.to_le_bytes();
+        let seeds = &[GAME_CONFIG_SEED, game_id_bytes.as_ref(), &[config.bump]];
+        let binding = &[seeds.as_slice()];
+
+        if ctx.accounts.voucher.is_none() {
+            // Transfer token. Session bets pull from the user's account through the
+            // delegation approved when the session was created.
+            match &ctx.accounts.session {
+                Some(_) => {
+                    let cpi_accounts = Transfer {
+                        from: ctx.accounts.user_token_account.to_account_info().clone(),
+                        to: ctx.accounts.game_escrow.to_account_info().clone(),
+                        authority: ctx.accounts.game_config.to_account_info().clone(),
+                    };
+                    let cpi_program = ctx.accounts.token_program.to_account_info().clone();
+                    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, binding);
+                    transfer(cpi_ctx, amount)?;
+                }
+                None => {
+                    let cpi_accounts = Transfer {
+                        from: ctx.accounts.user_token_account.to_account_info().clone(),
+                        to: ctx.accounts.game_escrow.to_account_info().clone(),
+                        authority: ctx.accounts.payer.to_account_info().clone(),
+                    };
+                    let cpi_program = ctx.accounts.token_program.to_account_info().clone();
+                    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+                    transfer(cpi_ctx, amount)?;
+                }
+            }
+        }
+
+        // Mint the 1-of-1 position token and drop the mint authority so the supply
+        // stays fixed. Whoever holds it is paid on settlement.
+        let cpi_accounts = MintTo {
+            mint: ctx.accounts.position_mint.to_account_info().clone(),
+            to: ctx.accounts.position_token_account.to_account_info().clone(),
+            authority: ctx.accounts.game_config.to_account_info().clone(),
         };
         let cpi_program = ctx.accounts.token_program.to_account_info().clone();
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        transfer(cpi_ctx, amount)?;
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, binding);
+        mint_to(cpi_ctx, 1)?;
 
-        // Create the Switchboard request account.
-        let clock = Clock::get()?;
-        let start_time = clock.unix_timestamp as u64;
-        let end_time = start_time + interval as u64;
-        let request_params = format!(
-            "PID={},BET_ID={},PAIR={},START_TIME={},END_TIME={},BET={},USER_TOKEN={},ESCROW={}",
-            id(),
-            bet_id,
-            std::str::from_utf8(&pair).unwrap(),
-            start_time,
-            end_time,
-            ctx.accounts.bet.key(),
-            ctx.accounts.user_token_account.key(),
-            ctx.accounts.game_escrow.key(),
-        );
-        let container_params = request_params.into_bytes();
-        let request_init_ctx = FunctionRequestInit {
-            request: ctx.accounts.switchboard_request.clone(),
-            authority: ctx.accounts.bet.to_account_info(),
-            function: ctx.accounts.switchboard_function.to_account_info(),
-            function_authority: None, // only needed if switchboard_function.requests_require_authorization is enabled
-            escrow: ctx.accounts.switchboard_request_escrow.clone(),
-            mint: ctx.accounts.switchboard_mint.to_account_info(),
-            state: ctx.accounts.switchboard_state.to_account_info(),
-            attestation_queue: ctx.accounts.switchboard_attestation_queue.to_account_info(),
-            payer: ctx.accounts.payer.to_account_info(),
-            system_program: ctx.accounts.system_program.to_account_info(),
-            token_program: ctx.accounts.token_program.to_account_info(),
-            associated_token_program: ctx.accounts.associated_token_program.to_account_info(),
+        let cpi_accounts = SetAuthority {
+            account_or_mint: ctx.accounts.position_mint.to_account_info().clone(),
+            current_authority: ctx.accounts.game_config.to_account_info().clone(),
         };
-        request_init_ctx.invoke(
-            ctx.accounts.switchboard.clone(),
-            // max_container_params_len - the length of the vec containing the container params
-            // default: 256 bytes
-            Some(container_params.len() as u32),
-            // container_params - the container params
-            // default: empty vec
-            Some(container_params),
-            // garbage_collection_slot - the slot when the request can be closed by anyone and is considered dead
-            // default: None, only authority can close the request
-            None,
-        )?;
+        let cpi_program = ctx.accounts.token_program.to_account_info().clone();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, binding);
+        set_authority(cpi_ctx, AuthorityType::MintTokens, None)?;
+
+        // The settlement routine picks the bet up once it has ended.
+        let end_time = start_time
+            .checked_add(interval as u64)
+            .ok_or(error!(GameError::MathOverflow))?;
+
+        state.next_bet_id = state
+            .next_bet_id
+            .checked_add(1)
+            .ok_or(error!(GameError::MathOverflow))?;
+        state.locked_liquidity = state
+            .locked_liquidity
+            .checked_add(payout)
+            .ok_or(error!(GameError::MathOverflow))?;
 
-        state.next_bet_id += 1;
-        state.locked_liquidity += payout;
+        // The escrow has to cover this bet's payout on top of everything owed.
+        ctx.accounts.game_escrow.reload()?;
+        state.check_solvency(ctx.accounts.game_escrow.amount)?;
 
         let mut bet = ctx.accounts.bet.load_init()?;
         bet.bump = ctx.bumps.bet;
+        bet.version = BET_VERSION;
         bet.bet_id = bet_id;
         bet.amount = amount;
         bet.payout = payout;
@@ -146,26 +387,253 @@ pub mod game {
         bet.end_time = end_time;
         bet.open_price = 0;
         bet.close_price = 0;
-        bet.user = ctx.accounts.payer.key();
+        bet.user = ctx.accounts.user.key();
         bet.user_token_account = ctx.accounts.user_token_account.key();
         bet.pair = pair.clone();
         bet.is_long = is_long;
         bet.active = true;
-        bet.switchboard_request = ctx.accounts.switchboard_request.key();
+        bet.switchboard_request = Pubkey::default();
+        bet.subscription = Pubkey::default();
+        bet.position_mint = ctx.accounts.position_mint.key();
+        bet.claimable = 0;
+        bet.voucher = match &ctx.accounts.voucher {
+            Some(voucher) => voucher.key(),
+            None => Pubkey::default(),
+        };
+        bet.tournament_entry = match &ctx.accounts.tournament_entry {
+            Some(tournament_entry) => tournament_entry.key(),
+            None => Pubkey::default(),
+        };
+        bet.user_profile = ctx.accounts.user_profile.key();
 
-        emit!(BetPlaced {
+        emit_cpi!(BetPlaced {
             bet_id,
-            user: ctx.accounts.payer.key(),
+            user: ctx.accounts.user.key(),
             amount,
             pair,
             interval,
             is_long,
+            placed_time: current_timestamp,
             start_time,
         });
 
         Ok(())
     }
 
+    pub fn create_subscription(
+        ctx: Context<CreateSubscription>,
+        subscription_id: u64,
+        deposit: u64,
+        stake: u64,
+        pair: [u8; 8],
+        interval: u32,
+        is_long: bool,
+        max_rounds: u32,
+        compound: bool,
+        allowlist_proof: Vec<[u8; 32]>,
+    ) -> anchor_lang::prelude::Result<()> {
+        let config = ctx.accounts.game_config.load()?;
+        if config.paused {
+            return Err(error!(GameError::GamePaused));
+        }
+        if ctx.accounts.accepted_pair.load()?.disabled {
+            return Err(error!(GameError::PairDisabled));
+        }
+        config.validate_bet(stake, interval)?;
+        config.validate_allowlist(&ctx.accounts.payer.key(), &allowlist_proof)?;
+
+        if max_rounds == 0 {
+            return Err(error!(GameError::InvalidRounds));
+        }
+
+        if deposit < stake {
+            return Err(error!(GameError::InsufficientSubscriptionBalance));
+        }
+
+        // Transfer token
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_token_account.to_account_info().clone(),
+            to: ctx.accounts.subscription_escrow.to_account_info().clone(),
+            authority: ctx.accounts.payer.to_account_info().clone(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info().clone();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        transfer(cpi_ctx, deposit)?;
+
+        // Rounds after the first are opened by the settlement of the previous one,
+        // which creates their bet from rent prefunded here. Whatever is left over
+        // is refunded when the subscription is cancelled.
+        let round_rent = Rent::get()?
+            .minimum_balance(8 + std::mem::size_of::<Bet>())
+            .checked_mul(max_rounds as u64 - 1)
+            .ok_or(error!(GameError::MathOverflow))?;
+        let cpi_accounts = anchor_lang::system_program::Transfer {
+            from: ctx.accounts.payer.to_account_info().clone(),
+            to: ctx.accounts.subscription.to_account_info().clone(),
+        };
+        let cpi_program = ctx.accounts.system_program.to_account_info().clone();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        anchor_lang::system_program::transfer(cpi_ctx, round_rent)?;
+
+        let mut subscription = ctx.accounts.subscription.load_init()?;
+        subscription.bump = ctx.bumps.subscription;
+        subscription.game_id = config.game_id;
+        subscription.subscription_id = subscription_id;
+        subscription.user = ctx.accounts.payer.key();
+        subscription.user_token_account = ctx.accounts.user_token_account.key();
+        subscription.subscription_escrow = ctx.accounts.subscription_escrow.key();
+        subscription.stake = stake;
+        subscription.next_stake = stake;
+        subscription.pair = pair;
+        subscription.interval = interval;
+        subscription.is_long = is_long;
+        subscription.compound = compound;
+        subscription.max_rounds = max_rounds;
+        subscription.rounds_played = 0;
+        subscription.active_bet_id = 0;
+        subscription.has_active_bet = false;
+
+        emit_cpi!(SubscriptionCreated {
+            subscription: ctx.accounts.subscription.key(),
+            user: ctx.accounts.payer.key(),
+            deposit,
+            stake,
+            pair,
+            interval,
+            is_long,
+            max_rounds,
+            compound,
+        });
+
+        Ok(())
+    }
+
+    /// Opens the next round of a subscription from its escrow balance. Rounds are
+    /// opened by the settlement of the previous one when it can, e.g. the rent
+    /// for them was prefunded; otherwise anyone can call this once the previous
+    /// round is settled, so no user signature is needed.
+    pub fn open_subscription_round(
+        ctx: Context<OpenSubscriptionRound>,
+    ) -> anchor_lang::prelude::Result<()> {
+        let config = &ctx.accounts.game_config.load()?;
+
+        if config.paused {
+            return Err(error!(GameError::GamePaused));
+        }
+
+        if ctx.accounts.accepted_pair.load()?.disabled {
+            return Err(error!(GameError::PairDisabled));
+        }
+
+        let clock = Clock::get()?;
+        let current_timestamp = clock.unix_timestamp as u64;
+        let mut state = ctx.accounts.game_state.load_mut()?;
+
+        // Rounds count against the subscriber's limits like any other bet and
+        // free their slot once settled or cancelled.
+        let mut user_profile = match ctx.accounts.user_profile.load_mut() {
+            Ok(user_profile) => user_profile,
+            Err(_) => {
+                let mut user_profile = ctx.accounts.user_profile.load_init()?;
+                user_profile.bump = ctx.bumps.user_profile;
+                user_profile.user = ctx.accounts.subscription.load()?.user;
+                user_profile
+            }
+        };
+
+        let (round, event) = ctx.accounts.subscription.load_mut()?.open_round(
+            config,
+            &mut state,
+            &mut user_profile,
+            ctx.accounts.subscription_escrow.amount,
+            current_timestamp,
+        )?;
+
+        // Transfer token
+        let subscription = ctx.accounts.subscription.load()?;
+        let game_id_bytes = subscription.game_id.to_le_bytes();
+        let subscription_id_bytes = subscription.subscription_id.to_le_bytes();
+        let seeds = &[
+            SUBSCRIPTION_SEED,
+            game_id_bytes.as_ref(),
+            subscription.user.as_ref(),
+            subscription_id_bytes.as_ref(),
+            &[subscription.bump],
+        ];
+        let binding = &[seeds.as_slice()];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.subscription_escrow.to_account_info().clone(),
+            to: ctx.accounts.game_escrow.to_account_info().clone(),
+            authority: ctx.accounts.subscription.to_account_info().clone(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info().clone();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, binding);
+        transfer(cpi_ctx, round.amount)?;
+
+        // The escrow has to cover this bet's payout on top of everything owed.
+        ctx.accounts.game_escrow.reload()?;
+        state.check_solvency(ctx.accounts.game_escrow.amount)?;
+
+        // The settlement routine picks the bet up once it has ended.
+        let mut bet = ctx.accounts.bet.load_init()?;
+        *bet = round;
+        bet.bump = ctx.bumps.bet;
+        bet.subscription = ctx.accounts.subscription.key();
+        bet.user_profile = ctx.accounts.user_profile.key();
+
+        emit_cpi!(event);
+
+        Ok(())
+    }
+
+    pub fn cancel_subscription(ctx: Context<CancelSubscription>) -> anchor_lang::prelude::Result<()> {
+        let subscription = ctx.accounts.subscription.load()?;
+
+        if subscription.has_active_bet {
+            return Err(error!(GameError::ActiveSubscriptionRound));
+        }
+
+        let game_id_bytes = subscription.game_id.to_le_bytes();
+        let subscription_id_bytes = subscription.subscription_id.to_le_bytes();
+        let seeds = &[
+            SUBSCRIPTION_SEED,
+            game_id_bytes.as_ref(),
+            subscription.user.as_ref(),
+            subscription_id_bytes.as_ref(),
+            &[subscription.bump],
+        ];
+        let binding = &[seeds.as_slice()];
+
+        // Transfer token
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.subscription_escrow.to_account_info().clone(),
+            to: ctx.accounts.user_token_account.to_account_info().clone(),
+            authority: ctx.accounts.subscription.to_account_info().clone(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info().clone();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, binding);
+        transfer(cpi_ctx, ctx.accounts.subscription_escrow.amount)?;
+
+        let cpi_accounts = CloseAccount {
+            account: ctx.accounts.subscription_escrow.to_account_info().clone(),
+            destination: ctx.accounts.payer.to_account_info().clone(),
+            authority: ctx.accounts.subscription.to_account_info().clone(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info().clone();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, binding);
+        close_account(cpi_ctx)?;
+
+        emit_cpi!(SubscriptionCancelled {
+            subscription: ctx.accounts.subscription.key(),
+            user: ctx.accounts.payer.key(),
+            rounds_played: subscription.rounds_played,
+        });
+
+        Ok(())
+    }
+
+    /// Triggers the request of a bet placed before settlement moved to the
+    /// routine. Newer bets have no request of their own.
     pub fn request_bet_execution(
         ctx: Context<RequestBetExecution>,
         bet_id: u64,
@@ -195,8 +663,14 @@ pub mod game {
             token_program: ctx.accounts.token_program.to_account_info(),
             system_program: ctx.accounts.system_program.to_account_info(),
         };
+        let game_id_bytes = ctx.accounts.game_config.load()?.game_id.to_le_bytes();
         let bet_id_bytes = bet_id.to_le_bytes();
-        let seeds = &[BET_SEED, bet_id_bytes.as_ref(), &[bet.bump]];
+        let seeds = &[
+            BET_SEED,
+            game_id_bytes.as_ref(),
+            bet_id_bytes.as_ref(),
+            &[bet.bump],
+        ];
         trigger_ctx.invoke_signed(
             ctx.accounts.switchboard.clone(),
             // bounty - the amount of SOL to pay the Switchboard Function for executing the request
@@ -212,84 +686,435 @@ pub mod game {
         Ok(())
     }
 
-    pub fn settle_bet(
-        ctx: Context<SettleBet>,
-        bet_id: u64,
-        open_price: u64,
-        close_price: u64,
+    /// Requests one Switchboard run settling up to `MAX_BATCH_BETS` bets that
+    /// ended in the same `SETTLEMENT_BUCKET`, so the function fetches each price
+    /// once and the bets share the request cost. Anyone can act as the keeper,
+    /// and a bucket with more bets is covered by several batches. The remaining
+    /// accounts are each bet followed by its accepted pair.
+    pub fn request_batch_settlement<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RequestBatchSettlement<'info>>,
+        bucket: u64,
     ) -> anchor_lang::Result<()> {
-        let mut bet = ctx.accounts.bet.load_mut()?;
+        let num_bets = ctx.remaining_accounts.len() / 2;
+        if num_bets == 0 || num_bets > MAX_BATCH_BETS || ctx.remaining_accounts.len() % 2 != 0 {
+            return Err(error!(GameError::InvalidBatch));
+        }
 
-        if !bet.active {
-            return Err(error!(GameError::InactiveBet));
+        let clock = Clock::get()?;
+        let current_timestamp = clock.unix_timestamp as u64;
+        let bucket_end = bucket
+            .checked_add(1)
+            .ok_or(error!(GameError::MathOverflow))?
+            .checked_mul(SETTLEMENT_BUCKET)
+            .ok_or(error!(GameError::MathOverflow))?;
+        if bucket_end > current_timestamp {
+            return Err(error!(GameError::InvalidTimestamp));
         }
 
-        bet.active = false;
-        bet.open_price = open_price;
-        bet.close_price = close_price;
+        let config = ctx.accounts.game_config.load()?;
+        let game_id_bytes = config.game_id.to_le_bytes();
+        let mut bet_ids = Vec::with_capacity(num_bets);
+        let mut bet_params = Vec::with_capacity(num_bets);
+        for accounts in ctx.remaining_accounts.chunks(2) {
+            let bet_loader = AccountLoader::<Bet>::try_from(&accounts[0])?;
+            let bet = bet_loader.load()?;
+            let bet_key = Pubkey::create_program_address(
+                &[BET_SEED, game_id_bytes.as_ref(), bet.bet_id.to_le_bytes().as_ref(), &[bet.bump]],
+                &id(),
+            )
+            .map_err(|_| error!(GameError::InvalidBatch))?;
+            if bet_key != bet_loader.key()
+                || !bet.active
+                || bet.end_time / SETTLEMENT_BUCKET != bucket
+                || bet_ids.contains(&{ bet.bet_id })
+            {
+                return Err(error!(GameError::InvalidBatch));
+            }
 
-        let mut state = ctx.accounts.game_state.load_mut()?;
-        let payout = bet.payout;
-        state.locked_liquidity -= payout;
+            let accepted_pair_loader = AccountLoader::<AcceptedPair>::try_from(&accounts[1])?;
+            let accepted_pair = accepted_pair_loader.load()?;
+            let accepted_pair_key = Pubkey::create_program_address(
+                &[PAIR_SEED, game_id_bytes.as_ref(), bet.pair.as_ref(), &[accepted_pair.bump]],
+                &id(),
+            )
+            .map_err(|_| error!(GameError::InvalidBatch))?;
+            if accepted_pair_key != accepted_pair_loader.key() {
+                return Err(error!(GameError::InvalidBatch));
+            }
+            if accepted_pair.pull_oracle {
+                return Err(error!(GameError::WrongOracle));
+            }
 
-        if (bet.is_long && close_price >= open_price) || (!bet.is_long && close_price <= open_price)
-        {
-            // Transfer token
-            let config = ctx.accounts.game_config.load()?;
-            let seeds = &[GAME_CONFIG_SEED, &[config.bump]];
-            let binding = &[seeds.as_slice()];
-            let cpi_accounts = Transfer {
-                from: ctx.accounts.game_escrow.to_account_info().clone(),
-                to: ctx.accounts.user_token_account.to_account_info().clone(),
-                authority: ctx.accounts.game_config.to_account_info().clone(),
+            // Same fields the bet's own request carries, bar the shared ones.
+            let mut params = format!(
+                "BET_ID={},PAIR={},FEED_ID={},START_TIME={},END_TIME={},BET={},USER_TOKEN={}",
+                { bet.bet_id },
+                std::str::from_utf8(&bet.pair).unwrap(),
+                accepted_pair.feed_id_hex(),
+                { bet.start_time },
+                { bet.end_time },
+                bet_key,
+                { bet.user_token_account },
+            );
+            let position_token_account = if bet.position_mint == Pubkey::default() {
+                Pubkey::default()
+            } else {
+                get_associated_token_address(&{ bet.user }, &{ bet.position_mint })
             };
-            let cpi_program = ctx.accounts.token_program.to_account_info().clone();
-            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, binding);
-            transfer(cpi_ctx, payout)?;
-
-            emit!(BetExecuted {
-                bet_id,
-                user: bet.user,
-                won: true,
-                payout
-            });
-        } else {
-            bet.payout = 0;
-
-            emit!(BetExecuted {
-                bet_id,
-                user: bet.user,
-                won: false,
-                payout: 0
-            });
+            for (name, key) in [
+                ("POSITION", position_token_account),
+                ("SUBSCRIPTION", bet.subscription),
+                ("TOURNAMENT_ENTRY", bet.tournament_entry),
+                ("USER_PROFILE", bet.user_profile),
+            ] {
+                if key != Pubkey::default() {
+                    params = format!("{},{}={}", params, name, key);
+                }
+            }
+            bet_ids.push(bet.bet_id);
+            bet_params.push(params);
         }
 
-        // TODO: close account
-
-        Ok(())
-    }
-
-    pub fn cancel_bet(ctx: Context<CancelBet>, bet_id: u64) -> anchor_lang::prelude::Result<()> {
-        let mut bet = ctx.accounts.bet.load_mut()?;
+        let mut batch = ctx.accounts.settlement_batch.load_init()?;
+        batch.bump = ctx.bumps.settlement_batch;
+        batch.game_id = config.game_id;
+        batch.bucket = bucket;
+        batch.switchboard_request = ctx.accounts.switchboard_request.key();
+        batch.keeper = ctx.accounts.payer.key();
+        batch.num_bets = num_bets as u8;
+        let mut batch_bet_ids = [0u64; MAX_BATCH_BETS];
+        batch_bet_ids[..num_bets].copy_from_slice(&bet_ids);
+        batch.bet_ids = batch_bet_ids;
 
-        if bet.active == false {
-            return Err(error!(GameError::InactiveBet));
-        }
+        // Bets are separated by ';' after the shared params.
+        let container_params = format!(
+            "PID={},GAME_ID={},ESCROW={},BATCH={},KEEPER={};{}",
+            id(),
+            { config.game_id },
+            { config.game_escrow },
+            ctx.accounts.settlement_batch.key(),
+            ctx.accounts.payer.key(),
+            bet_params.join(";")
+        )
+        .into_bytes();
+        let request_init_ctx = FunctionRequestInit {
+            request: ctx.accounts.switchboard_request.clone(),
+            authority: ctx.accounts.settlement_batch.to_account_info(),
+            function: ctx.accounts.switchboard_function.to_account_info(),
+            function_authority: None,
+            escrow: ctx.accounts.switchboard_request_escrow.clone(),
+            mint: ctx.accounts.switchboard_mint.to_account_info(),
+            state: ctx.accounts.switchboard_state.to_account_info(),
+            attestation_queue: ctx.accounts.switchboard_attestation_queue.to_account_info(),
+            payer: ctx.accounts.payer.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+            associated_token_program: ctx.accounts.associated_token_program.to_account_info(),
+        };
+        request_init_ctx.invoke(
+            ctx.accounts.switchboard.clone(),
+            Some(container_params.len() as u32),
+            Some(container_params),
+            None,
+        )?;
+
+        let trigger_ctx = FunctionRequestTrigger {
+            request: ctx.accounts.switchboard_request.clone(),
+            authority: ctx.accounts.settlement_batch.to_account_info(),
+            escrow: ctx.accounts.switchboard_request_escrow.clone(),
+            function: ctx.accounts.switchboard_function.to_account_info(),
+            state: ctx.accounts.switchboard_state.to_account_info(),
+            attestation_queue: ctx.accounts.switchboard_attestation_queue.to_account_info(),
+            payer: ctx.accounts.payer.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        };
+        let request_key = ctx.accounts.switchboard_request.key();
+        let seeds = &[
+            SETTLEMENT_BATCH_SEED,
+            game_id_bytes.as_ref(),
+            request_key.as_ref(),
+            &[batch.bump],
+        ];
+        trigger_ctx.invoke_signed(ctx.accounts.switchboard.clone(), None, None, None, &[seeds])?;
+
+        emit_cpi!(BatchSettlementRequested {
+            settlement_batch: ctx.accounts.settlement_batch.key(),
+            bucket,
+            bet_ids,
+        });
+
+        Ok(())
+    }
+
+    /// Closes a batch once every bet it covers is settled, refunding its rent to
+    /// the keeper. Anyone can close it, e.g. the function after its settlements.
+    /// The remaining accounts are the covered bets in the order they were batched.
+    pub fn close_settlement_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CloseSettlementBatch<'info>>,
+    ) -> anchor_lang::Result<()> {
+        let batch = ctx.accounts.settlement_batch.load()?;
+        let num_bets = batch.num_bets as usize;
+        if ctx.remaining_accounts.len() != num_bets {
+            return Err(error!(GameError::InvalidBatch));
+        }
+
+        let game_id_bytes = batch.game_id.to_le_bytes();
+        let bet_ids = batch.bet_ids;
+        for (bet_id, bet_info) in bet_ids[..num_bets].iter().zip(ctx.remaining_accounts) {
+            let bet_loader = AccountLoader::<Bet>::try_from(bet_info)?;
+            let bet = bet_loader.load()?;
+            let bet_key = Pubkey::create_program_address(
+                &[BET_SEED, game_id_bytes.as_ref(), bet_id.to_le_bytes().as_ref(), &[bet.bump]],
+                &id(),
+            )
+            .map_err(|_| error!(GameError::InvalidBatch))?;
+            if bet_key != bet_loader.key() {
+                return Err(error!(GameError::InvalidBatch));
+            }
+            if bet.active {
+                return Err(error!(GameError::BatchNotSettled));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn settle_bet(
+        ctx: Context<SettleBet>,
+        bet_id: u64,
+        open: OraclePrice,
+        close: OraclePrice,
+        price_expo: i32,
+    ) -> anchor_lang::Result<()> {
+        if ctx.accounts.settlement.accepted_pair.load()?.pull_oracle {
+            return Err(error!(GameError::WrongOracle));
+        }
+        ctx.accounts.validate_request()?;
+
+        let (event, next_round) = ctx
+            .accounts
+            .settlement
+            .settle(bet_id, open, close, price_expo)?;
+        match event {
+            SettlementEvent::Executed(event) => emit_cpi!(event),
+            SettlementEvent::Refunded(event) => emit_cpi!(event),
+        }
+        if let Some(event) = next_round {
+            emit_cpi!(event);
+        }
+
+        Ok(())
+    }
+
+    /// Lets the enclave void a bet it could not price, so the bettor is refunded
+    /// immediately rather than after the cancel buffer.
+    pub fn report_settlement_failure(
+        ctx: Context<SettleBet>,
+        bet_id: u64,
+        reason: SettlementFailure,
+    ) -> anchor_lang::Result<()> {
+        if ctx.accounts.settlement.accepted_pair.load()?.pull_oracle {
+            return Err(error!(GameError::WrongOracle));
+        }
+        ctx.accounts.validate_request()?;
+
+        let (event, next_round) = ctx.accounts.settlement.void(bet_id, reason)?;
+        emit_cpi!(event);
+        if let Some(event) = next_round {
+            emit_cpi!(event);
+        }
+
+        Ok(())
+    }
+
+    /// Settles a bet from Pyth price updates posted on-chain through the Pyth
+    /// receiver, for markets configured to settle without Switchboard. Anyone can
+    /// call it; both updates must be fully verified and published within
+    /// `max_publish_delay` seconds after the bet's start and end.
+    pub fn settle_bet_with_pyth(
+        ctx: Context<SettleBetWithPyth>,
+        bet_id: u64,
+    ) -> anchor_lang::Result<()> {
+        let (open, close, price_expo) = {
+            let settlement = &ctx.accounts.settlement;
+            let accepted_pair = settlement.accepted_pair.load()?;
+            if !accepted_pair.pull_oracle {
+                return Err(error!(GameError::WrongOracle));
+            }
+
+            let open = PriceUpdateV2::try_from_account(&ctx.accounts.open_price_update)?;
+            let close = PriceUpdateV2::try_from_account(&ctx.accounts.close_price_update)?;
+            if open.price_message.exponent != close.price_message.exponent {
+                return Err(error!(GameError::InvalidPriceUpdate));
+            }
+
+            let bet = settlement.bet.load()?;
+            let max_publish_delay = settlement.game_config.load()?.max_publish_delay as u64;
+            (
+                open.to_oracle_price(&accepted_pair, bet.start_time, max_publish_delay)?,
+                close.to_oracle_price(&accepted_pair, bet.end_time, max_publish_delay)?,
+                open.price_message.exponent,
+            )
+        };
+
+        let (event, next_round) = ctx
+            .accounts
+            .settlement
+            .settle(bet_id, open, close, price_expo)?;
+        match event {
+            SettlementEvent::Executed(event) => emit_cpi!(event),
+            SettlementEvent::Refunded(event) => emit_cpi!(event),
+        }
+        if let Some(event) = next_round {
+            emit_cpi!(event);
+        }
+
+        Ok(())
+    }
+
+    pub fn cancel_bet(ctx: Context<CancelBet>, bet_id: u64) -> anchor_lang::prelude::Result<()> {
+        let mut bet = ctx.accounts.bet.load_mut()?;
+
+        if bet.active == false {
+            return Err(error!(GameError::InactiveBet));
+        }
 
         let config = ctx.accounts.game_config.load()?;
         let clock = Clock::get()?;
         let current_timestamp = clock.unix_timestamp as u64;
 
-        if bet.end_time + config.cancel_buffer >= current_timestamp {
+        if bet.end_time.saturating_add(config.cancel_buffer) >= current_timestamp {
             return Err(error!(GameError::InvalidTimestamp));
         }
 
+        // Positions are refunded to whoever holds the token, which is burned in the
+        // process. Subscription rounds have no position and are cancelled by the
+        // subscriber, refunding the subscription escrow.
+        if bet.position_mint == Pubkey::default() {
+            if ctx.accounts.payer.key() != bet.user
+                || ctx.accounts.user_token_account.key() != bet.user_token_account
+            {
+                return Err(error!(GameError::Unauthorized));
+            }
+        } else {
+            if ctx.accounts.user_token_account.owner != ctx.accounts.payer.key() {
+                return Err(error!(GameError::Unauthorized));
+            }
+
+            if !holds_position(
+                ctx.accounts
+                    .position_token_account
+                    .as_deref()
+                    .map(|account| &**account),
+                &bet,
+                ctx.accounts.payer.key(),
+            ) {
+                return Err(error!(GameError::InvalidPosition));
+            }
+
+            let (Some(position_mint), Some(position_token_account)) = (
+                &ctx.accounts.position_mint,
+                &ctx.accounts.position_token_account,
+            ) else {
+                return Err(error!(GameError::InvalidPosition));
+            };
+
+            let cpi_accounts = Burn {
+                mint: position_mint.to_account_info().clone(),
+                from: position_token_account.to_account_info().clone(),
+                authority: ctx.accounts.payer.to_account_info().clone(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info().clone();
+            let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+            burn(cpi_ctx, 1)?;
+        }
+
         let mut state = ctx.accounts.game_state.load_mut()?;
         bet.active = false;
-        state.locked_liquidity -= bet.payout;
+        state.locked_liquidity = state
+            .locked_liquidity
+            .checked_sub(bet.payout)
+            .ok_or(error!(GameError::MathUnderflow))?;
+
+        // A voucher stake never left the house, so there is nothing to refund.
+        if bet.voucher == Pubkey::default() {
+            // Transfer token
+            let game_id_bytes = config.game_id.to_le_bytes();
+            let seeds = &[GAME_CONFIG_SEED, game_id_bytes.as_ref(), &[config.bump]];
+            let binding = &[seeds.as_slice()];
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.game_escrow.to_account_info().clone(),
+                to: ctx.accounts.user_token_account.to_account_info().clone(),
+                authority: ctx.accounts.game_config.to_account_info().clone(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info().clone();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, binding);
+            transfer(cpi_ctx, bet.amount)?;
+        }
+
+        ctx.accounts.game_escrow.reload()?;
+        state.check_solvency(ctx.accounts.game_escrow.amount)?;
+
+        release_subscription_round(&ctx.accounts.subscription, &bet, None)?;
+        record_tournament_result(&ctx.accounts.tournament_entry, &bet, 0)?;
+        release_open_bet(&ctx.accounts.user_profile, &bet)?;
+
+        emit_cpi!(BetCancelled {
+            bet_id,
+            user: ctx.accounts.payer.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn claim_position(
+        ctx: Context<ClaimPosition>,
+        bet_id: u64,
+    ) -> anchor_lang::prelude::Result<()> {
+        let mut bet = ctx.accounts.bet.load_mut()?;
+
+        if bet.claimable == 0 {
+            return Err(error!(GameError::NothingToClaim));
+        }
+
+        if (Clock::get()?.unix_timestamp as u64) < bet.disputable_until {
+            return Err(error!(GameError::SettlementDisputable));
+        }
+
+        // Only held payouts are claimed with a dispute deadline set.
+        if bet.disputable_until != 0 {
+            resolve_held_payout(
+                &ctx.accounts.subscription,
+                &ctx.accounts.tournament_entry,
+                &bet,
+                false,
+            )?;
+        }
+
+        let payout = bet.claimable;
+        bet.claimable = 0;
+        bet.position_redeemed = true;
+
+        let mut state = ctx.accounts.game_state.load_mut()?;
+        state.unclaimed_payouts = state
+            .unclaimed_payouts
+            .checked_sub(payout)
+            .ok_or(error!(GameError::MathUnderflow))?;
+
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.position_mint.to_account_info().clone(),
+            from: ctx.accounts.position_token_account.to_account_info().clone(),
+            authority: ctx.accounts.payer.to_account_info().clone(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info().clone();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        burn(cpi_ctx, 1)?;
 
         // Transfer token
-        let seeds = &[GAME_CONFIG_SEED, &[config.bump]];
+        let config = ctx.accounts.game_config.load()?;
+        let game_id_bytes = config.game_id.to_le_bytes();
+        let seeds = &[GAME_CONFIG_SEED, game_id_bytes.as_ref(), &[config.bump]];
         let binding = &[seeds.as_slice()];
         let cpi_accounts = Transfer {
             from: ctx.accounts.game_escrow.to_account_info().clone(),
@@ -298,301 +1123,3394 @@ pub mod game {
         };
         let cpi_program = ctx.accounts.token_program.to_account_info().clone();
         let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, binding);
-        transfer(cpi_ctx, bet.amount)?;
+        transfer(cpi_ctx, payout)?;
 
-        emit!(BetCancelled {
+        emit_cpi!(PositionClaimed {
             bet_id,
-            user: ctx.accounts.payer.key(),
+            holder: ctx.accounts.payer.key(),
+            payout,
         });
 
         Ok(())
     }
 
-    pub fn add_pairs(
-        ctx: Context<ModifyConfig>,
-        pairs_to_add: Vec<[u8; 8]>,
+    /// Pays out a held payout of a bet without a position to the bettor's token
+    /// account once its dispute window has passed. Anyone can claim it, e.g. the
+    /// keeper of a subscription.
+    pub fn claim_payout(
+        ctx: Context<ClaimPayout>,
+        bet_id: u64,
     ) -> anchor_lang::prelude::Result<()> {
-        let mut config = ctx.accounts.game_config.load_mut()?;
+        let mut bet = ctx.accounts.bet.load_mut()?;
+
+        if bet.claimable == 0 {
+            return Err(error!(GameError::NothingToClaim));
+        }
+
+        if (Clock::get()?.unix_timestamp as u64) < bet.disputable_until {
+            return Err(error!(GameError::SettlementDisputable));
+        }
 
-        let num_pairs = config.num_pairs as usize;
-        let total_pairs_needed = num_pairs + pairs_to_add.len();
-        if total_pairs_needed > config.accepted_pairs.len() {
+        if bet.disputable_until != 0 {
+            resolve_held_payout(
+                &ctx.accounts.subscription,
+                &ctx.accounts.tournament_entry,
+                &bet,
+                false,
+            )?;
+        }
+
+        let payout = bet.claimable;
+        bet.claimable = 0;
+        bet.disputable_until = 0;
+
+        let mut state = ctx.accounts.game_state.load_mut()?;
+        state.unclaimed_payouts = state
+            .unclaimed_payouts
+            .checked_sub(payout)
+            .ok_or(error!(GameError::MathUnderflow))?;
+
+        // Transfer token
+        let config = ctx.accounts.game_config.load()?;
+        let game_id_bytes = config.game_id.to_le_bytes();
+        let seeds = &[GAME_CONFIG_SEED, game_id_bytes.as_ref(), &[config.bump]];
+        let binding = &[seeds.as_slice()];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.game_escrow.to_account_info().clone(),
+            to: ctx.accounts.user_token_account.to_account_info().clone(),
+            authority: ctx.accounts.game_config.to_account_info().clone(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info().clone();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, binding);
+        transfer(cpi_ctx, payout)?;
+
+        emit_cpi!(PayoutClaimed {
+            bet_id,
+            user: bet.user,
+            payout,
+        });
+
+        Ok(())
+    }
+
+    /// Voids a held settlement within its dispute window. The stake can then be
+    /// claimed back instead of the payout.
+    pub fn dispute_settlement(
+        ctx: Context<DisputeSettlement>,
+        bet_id: u64,
+    ) -> anchor_lang::prelude::Result<()> {
+        let mut bet = ctx.accounts.bet.load_mut()?;
+        let current_timestamp = Clock::get()?.unix_timestamp as u64;
+
+        if bet.claimable == 0 || current_timestamp >= bet.disputable_until {
+            return Err(error!(GameError::NotDisputable));
+        }
+
+        // A voucher stake never left the house, so there is nothing to refund.
+        let refund = if bet.voucher == Pubkey::default() {
+            bet.amount
+        } else {
+            0
+        };
+
+        let mut state = ctx.accounts.game_state.load_mut()?;
+        state.unclaimed_payouts = state
+            .unclaimed_payouts
+            .checked_sub(bet.claimable)
+            .ok_or(error!(GameError::MathUnderflow))?
+            .checked_add(refund)
+            .ok_or(error!(GameError::MathOverflow))?;
+
+        resolve_held_payout(
+            &ctx.accounts.subscription,
+            &ctx.accounts.tournament_entry,
+            &bet,
+            true,
+        )?;
+
+        bet.payout = 0;
+        bet.claimable = refund;
+        bet.disputable_until = 0;
+
+        emit_cpi!(SettlementDisputed { bet_id, refund });
+
+        Ok(())
+    }
+
+    pub fn create_tournament(
+        ctx: Context<CreateTournament>,
+        tournament_id: u64,
+        entry_fee: u64,
+        start_time: u64,
+        end_time: u64,
+        finalize_time: u64,
+        allowed_pairs: Vec<[u8; 8]>,
+        prize_bps: Vec<u16>,
+    ) -> anchor_lang::prelude::Result<()> {
+        if start_time >= end_time || end_time >= finalize_time {
+            return Err(error!(GameError::InvalidTimestamp));
+        }
+
+        if prize_bps.is_empty()
+            || prize_bps.len() > MAX_TOURNAMENT_WINNERS
+            || prize_bps.iter().map(|bps| *bps as u64).sum::<u64>() > MAX_BPS as u64
+        {
+            return Err(error!(GameError::InvalidPrizeShares));
+        }
+
+        let mut tournament = ctx.accounts.tournament.load_init()?;
+
+        if allowed_pairs.len() > tournament.allowed_pairs.len() {
             return Err(error!(GameError::MaxPairsExceeded));
         }
 
-        for (i, pair) in pairs_to_add.iter().enumerate() {
-            config.accepted_pairs[num_pairs + i] = *pair;
+        tournament.bump = ctx.bumps.tournament;
+        tournament.game_id = ctx.accounts.game_config.load()?.game_id;
+        tournament.tournament_id = tournament_id;
+        tournament.tournament_escrow = ctx.accounts.tournament_escrow.key();
+        tournament.entry_fee = entry_fee;
+        tournament.start_time = start_time;
+        tournament.end_time = end_time;
+        tournament.finalize_time = finalize_time;
+        tournament.num_entrants = 0;
+        tournament.num_pairs = allowed_pairs.len() as u32;
+        for (i, pair) in allowed_pairs.iter().enumerate() {
+            tournament.allowed_pairs[i] = *pair;
+        }
+        let mut tournament_prize_bps = [0u16; MAX_TOURNAMENT_WINNERS];
+        tournament_prize_bps[..prize_bps.len()].copy_from_slice(&prize_bps);
+        tournament.prize_bps = tournament_prize_bps;
+        tournament.num_ranked = 0;
+        tournament.finalized = false;
+
+        emit_cpi!(TournamentCreated {
+            tournament: ctx.accounts.tournament.key(),
+            entry_fee,
+            start_time,
+            end_time,
+        });
+
+        Ok(())
+    }
+
+    pub fn join_tournament(ctx: Context<JoinTournament>) -> anchor_lang::prelude::Result<()> {
+        let mut tournament = ctx.accounts.tournament.load_mut()?;
+
+        let clock = Clock::get()?;
+        let current_timestamp = clock.unix_timestamp as u64;
+
+        if current_timestamp >= tournament.end_time {
+            return Err(error!(GameError::InvalidTimestamp));
+        }
+
+        // Transfer token
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_token_account.to_account_info().clone(),
+            to: ctx.accounts.tournament_escrow.to_account_info().clone(),
+            authority: ctx.accounts.payer.to_account_info().clone(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info().clone();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        transfer(cpi_ctx, tournament.entry_fee)?;
+
+        tournament.num_entrants = tournament
+            .num_entrants
+            .checked_add(1)
+            .ok_or(error!(GameError::MathOverflow))?;
+
+        let mut tournament_entry = ctx.accounts.tournament_entry.load_init()?;
+        tournament_entry.bump = ctx.bumps.tournament_entry;
+        tournament_entry.tournament = ctx.accounts.tournament.key();
+        tournament_entry.user = ctx.accounts.payer.key();
+        tournament_entry.pnl = 0;
+        tournament_entry.open_bets = 0;
+        tournament_entry.num_bets = 0;
+        tournament_entry.ranked = false;
+
+        Ok(())
+    }
+
+    /// Submits a final score to the tournament leaderboard. Anyone can rank an
+    /// entry once the tournament is over and all of its bets are closed.
+    pub fn rank_tournament_entry(
+        ctx: Context<RankTournamentEntry>,
+    ) -> anchor_lang::prelude::Result<()> {
+        let mut tournament = ctx.accounts.tournament.load_mut()?;
+        let mut tournament_entry = ctx.accounts.tournament_entry.load_mut()?;
+
+        let clock = Clock::get()?;
+        let current_timestamp = clock.unix_timestamp as u64;
+
+        if current_timestamp < tournament.end_time || current_timestamp >= tournament.finalize_time
+        {
+            return Err(error!(GameError::InvalidTimestamp));
         }
-        config.num_pairs = total_pairs_needed as u32;
+
+        if tournament_entry.ranked || tournament_entry.open_bets > 0 {
+            return Err(error!(GameError::InvalidTournament));
+        }
+
+        tournament_entry.ranked = true;
+        tournament.rank(tournament_entry.user, tournament_entry.pnl);
 
         Ok(())
     }
 
-    pub fn delete_pairs(
-        ctx: Context<ModifyConfig>,
-        pairs_to_remove: Vec<[u8; 8]>,
+    /// Awards the prize pool to the leaderboard. The remaining accounts are the
+    /// tournament entries of the ranked users in leaderboard order, on which the
+    /// prizes are recorded for claim_tournament_prize; unawarded shares go to the house.
+    pub fn finalize_tournament<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FinalizeTournament<'info>>,
     ) -> anchor_lang::prelude::Result<()> {
-        let mut config = ctx.accounts.game_config.load_mut()?;
-        let mut num_pairs = config.num_pairs;
+        let mut tournament = ctx.accounts.tournament.load_mut()?;
 
-        for remove_pair in pairs_to_remove {
-            for i in 0..(num_pairs as usize) {
-                if config.accepted_pairs[i] == remove_pair {
-                    for j in i..(num_pairs as usize - 1) {
-                        config.accepted_pairs[j] = config.accepted_pairs[j + 1];
-                    }
-                    num_pairs -= 1;
-                    config.accepted_pairs[num_pairs as usize] = [0u8; 8];
-                    break;
-                }
+        let clock = Clock::get()?;
+        let current_timestamp = clock.unix_timestamp as u64;
+
+        if current_timestamp < tournament.finalize_time {
+            return Err(error!(GameError::InvalidTimestamp));
+        }
+
+        if tournament.finalized {
+            return Err(error!(GameError::InvalidTournament));
+        }
+
+        let num_ranked = tournament.num_ranked as usize;
+        if ctx.remaining_accounts.len() != num_ranked {
+            return Err(error!(GameError::InvalidTournament));
+        }
+
+        let game_id_bytes = tournament.game_id.to_le_bytes();
+        let tournament_id_bytes = tournament.tournament_id.to_le_bytes();
+        let seeds = &[
+            TOURNAMENT_SEED,
+            game_id_bytes.as_ref(),
+            tournament_id_bytes.as_ref(),
+            &[tournament.bump],
+        ];
+        let binding = &[seeds.as_slice()];
+        let prize_pool = ctx.accounts.tournament_escrow.amount;
+        let prize_bps = tournament.prize_bps;
+        let ranked_users = tournament.ranked_users;
+        let mut awarded: u64 = 0;
+
+        // Prizes are recorded on the winners' entries and claimed by the winners,
+        // so no winner's token account can hold up the others.
+        for (i, winner_entry) in ctx.remaining_accounts.iter().enumerate() {
+            let winner_entry = AccountLoader::<TournamentEntry>::try_from(winner_entry)?;
+            let mut winner_entry = winner_entry.load_mut()?;
+            if winner_entry.tournament != ctx.accounts.tournament.key()
+                || winner_entry.user != ranked_users[i]
+            {
+                return Err(error!(GameError::InvalidTournament));
             }
+
+            let prize = prize_pool
+                .checked_mul(prize_bps[i] as u64)
+                .ok_or(error!(GameError::MathOverflow))?
+                / MAX_BPS as u64;
+            awarded = awarded.checked_add(prize).ok_or(error!(GameError::MathOverflow))?;
+            winner_entry.prize = prize;
         }
 
-        config.num_pairs = num_pairs;
+        // Transfer token
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.tournament_escrow.to_account_info().clone(),
+            to: ctx.accounts.game_escrow.to_account_info().clone(),
+            authority: ctx.accounts.tournament.to_account_info().clone(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info().clone();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, binding);
+        let remaining = prize_pool
+            .checked_sub(awarded)
+            .ok_or(error!(GameError::MathUnderflow))?;
+        transfer(cpi_ctx, remaining)?;
+
+        tournament.finalized = true;
+
+        emit_cpi!(TournamentFinalized {
+            tournament: ctx.accounts.tournament.key(),
+            prize_pool,
+            num_winners: num_ranked as u32,
+        });
 
         Ok(())
     }
 
-    pub fn set_amounts(
-        ctx: Context<ModifyConfig>,
-        min_bet: u64,
-        max_bet: u64,
-        max_utilized_liquidity: u64,
+    /// Pays out the prize a finalized tournament recorded on the winner's entry.
+    pub fn claim_tournament_prize(
+        ctx: Context<ClaimTournamentPrize>,
     ) -> anchor_lang::prelude::Result<()> {
-        if min_bet == 0 || max_bet == 0 || min_bet >= max_bet {
-            return Err(error!(GameError::InvalidAmount));
+        let mut tournament_entry = ctx.accounts.tournament_entry.load_mut()?;
+
+        if tournament_entry.prize == 0 {
+            return Err(error!(GameError::NothingToClaim));
         }
 
-        let mut config = ctx.accounts.game_config.load_mut()?;
-        config.min_bet = min_bet;
-        config.max_bet = max_bet;
-        config.max_utilized_liquidity = max_utilized_liquidity;
+        let prize = tournament_entry.prize;
+        tournament_entry.prize = 0;
+
+        // Transfer token
+        let tournament = ctx.accounts.tournament.load()?;
+        let game_id_bytes = tournament.game_id.to_le_bytes();
+        let tournament_id_bytes = tournament.tournament_id.to_le_bytes();
+        let seeds = &[
+            TOURNAMENT_SEED,
+            game_id_bytes.as_ref(),
+            tournament_id_bytes.as_ref(),
+            &[tournament.bump],
+        ];
+        let binding = &[seeds.as_slice()];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.tournament_escrow.to_account_info().clone(),
+            to: ctx.accounts.user_token_account.to_account_info().clone(),
+            authority: ctx.accounts.tournament.to_account_info().clone(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info().clone();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, binding);
+        transfer(cpi_ctx, prize)?;
+
+        emit_cpi!(TournamentPrizeClaimed {
+            tournament: ctx.accounts.tournament.key(),
+            user: ctx.accounts.payer.key(),
+            prize,
+        });
+
+        Ok(())
+    }
+
+    pub fn register_referrer(ctx: Context<RegisterReferrer>) -> anchor_lang::prelude::Result<()> {
+        let mut referrer = ctx.accounts.referrer.load_init()?;
+        referrer.bump = ctx.bumps.referrer;
+        referrer.wallet = ctx.accounts.payer.key();
+        referrer.claimable = 0;
+        referrer.total_earned = 0;
+        referrer.referred_volume = 0;
+
+        Ok(())
+    }
+
+    pub fn claim_referral_rewards(
+        ctx: Context<ClaimReferralRewards>,
+    ) -> anchor_lang::prelude::Result<()> {
+        let mut referrer = ctx.accounts.referrer.load_mut()?;
+
+        if referrer.claimable == 0 {
+            return Err(error!(GameError::NothingToClaim));
+        }
+
+        let amount = referrer.claimable;
+        referrer.claimable = 0;
+
+        let mut state = ctx.accounts.game_state.load_mut()?;
+        state.unclaimed_referral_rewards = state
+            .unclaimed_referral_rewards
+            .checked_sub(amount)
+            .ok_or(error!(GameError::MathUnderflow))?;
+
+        // Transfer token
+        let config = ctx.accounts.game_config.load()?;
+        let game_id_bytes = config.game_id.to_le_bytes();
+        let seeds = &[GAME_CONFIG_SEED, game_id_bytes.as_ref(), &[config.bump]];
+        let binding = &[seeds.as_slice()];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.game_escrow.to_account_info().clone(),
+            to: ctx.accounts.user_token_account.to_account_info().clone(),
+            authority: ctx.accounts.game_config.to_account_info().clone(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info().clone();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, binding);
+        transfer(cpi_ctx, amount)?;
+
+        emit_cpi!(ReferralRewardsClaimed {
+            referrer: ctx.accounts.referrer.key(),
+            wallet: ctx.accounts.payer.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn issue_voucher(
+        ctx: Context<IssueVoucher>,
+        voucher_id: u64,
+        amount: u64,
+        expires_at: u64,
+        max_uses: u32,
+        allowed_pairs: Vec<[u8; 8]>,
+    ) -> anchor_lang::prelude::Result<()> {
+        if amount == 0 || max_uses == 0 {
+            return Err(error!(GameError::InvalidVoucher));
+        }
+
+        let mut voucher = ctx.accounts.voucher.load_init()?;
+
+        if allowed_pairs.len() > voucher.allowed_pairs.len() {
+            return Err(error!(GameError::MaxPairsExceeded));
+        }
+
+        voucher.bump = ctx.bumps.voucher;
+        voucher.voucher_id = voucher_id;
+        voucher.user = ctx.accounts.user.key();
+        voucher.amount = amount;
+        voucher.expires_at = expires_at;
+        voucher.max_uses = max_uses;
+        voucher.uses = 0;
+        voucher.num_pairs = allowed_pairs.len() as u32;
+        for (i, pair) in allowed_pairs.iter().enumerate() {
+            voucher.allowed_pairs[i] = *pair;
+        }
+
+        emit_cpi!(VoucherIssued {
+            voucher: ctx.accounts.voucher.key(),
+            user: ctx.accounts.user.key(),
+            amount,
+            expires_at,
+            max_uses,
+        });
+
+        Ok(())
+    }
+
+    pub fn revoke_voucher(ctx: Context<RevokeVoucher>) -> anchor_lang::prelude::Result<()> {
+        emit_cpi!(VoucherRevoked {
+            voucher: ctx.accounts.voucher.key(),
+            user: ctx.accounts.voucher.load()?.user,
+        });
+
+        Ok(())
+    }
+
+    /// Approves `session_key` to place bets for the payer. The game config is
+    /// made delegate of the payer's token account for the spending cap. A token
+    /// account has a single delegate, so it backs one session at a time: the
+    /// earlier session has to be revoked or have used up its cap first.
+    pub fn create_session(
+        ctx: Context<CreateSession>,
+        session_key: Pubkey,
+        expires_at: u64,
+        spending_cap: u64,
+        allowed_pairs: Vec<[u8; 8]>,
+    ) -> anchor_lang::prelude::Result<()> {
+        let current_timestamp = Clock::get()?.unix_timestamp as u64;
+        if expires_at <= current_timestamp || spending_cap == 0 {
+            return Err(error!(GameError::InvalidSession));
+        }
+
+        // Approving again would overwrite the allowance an earlier session still
+        // spends from.
+        let user_token_account = &ctx.accounts.user_token_account;
+        if user_token_account.delegate == COption::Some(ctx.accounts.game_config.key())
+            && user_token_account.delegated_amount > 0
+        {
+            return Err(error!(GameError::SessionActive));
+        }
+
+        let mut session = ctx.accounts.session.load_init()?;
+
+        if allowed_pairs.len() > session.allowed_pairs.len() {
+            return Err(error!(GameError::MaxPairsExceeded));
+        }
+
+        session.bump = ctx.bumps.session;
+        session.user = ctx.accounts.payer.key();
+        session.session_key = session_key;
+        session.expires_at = expires_at;
+        session.spending_cap = spending_cap;
+        session.spent = 0;
+        session.num_pairs = allowed_pairs.len() as u32;
+        for (i, pair) in allowed_pairs.iter().enumerate() {
+            session.allowed_pairs[i] = *pair;
+        }
+
+        let cpi_accounts = Approve {
+            to: ctx.accounts.user_token_account.to_account_info().clone(),
+            delegate: ctx.accounts.game_config.to_account_info().clone(),
+            authority: ctx.accounts.payer.to_account_info().clone(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info().clone();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        approve(cpi_ctx, spending_cap)?;
+
+        emit_cpi!(SessionCreated {
+            session: ctx.accounts.session.key(),
+            user: ctx.accounts.payer.key(),
+            session_key,
+            expires_at,
+            spending_cap,
+        });
+
+        Ok(())
+    }
+
+    pub fn revoke_session(ctx: Context<RevokeSession>) -> anchor_lang::prelude::Result<()> {
+        let cpi_accounts = Revoke {
+            source: ctx.accounts.user_token_account.to_account_info().clone(),
+            authority: ctx.accounts.payer.to_account_info().clone(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info().clone();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        revoke(cpi_ctx)?;
+
+        emit_cpi!(SessionRevoked {
+            session: ctx.accounts.session.key(),
+            user: ctx.accounts.payer.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Queues a change to the bet limits, the pair registry, the settlement
+    /// routine, the admin roles or the user, dispute, oracle, referral and
+    /// allowlist parameters. It can be applied once the config change delay has
+    /// passed, giving bettors time to react.
+    pub fn propose_config_change(
+        ctx: Context<ProposeConfigChange>,
+        change_id: u64,
+        change: ConfigChangeKind,
+    ) -> anchor_lang::prelude::Result<()> {
+        change.validate()?;
+
+        let config = ctx.accounts.game_config.load()?;
+
+        // The timelock itself, the allowlist and the roles are governed by the
+        // super-admin only, and the referral share by the treasurer.
+        let payer = ctx.accounts.payer.key();
+        let authorized = match change {
+            ConfigChangeKind::SetConfigChangeDelay { .. }
+            | ConfigChangeKind::SetAllowlistRoot { .. }
+            | ConfigChangeKind::SetRoles { .. } => payer == config.authority,
+            ConfigChangeKind::SetReferralShare { .. } => config.is_treasurer(&payer),
+            _ => config.is_risk_manager(&payer),
+        };
+        if !authorized {
+            return Err(error!(GameError::Unauthorized));
+        }
+        let current_timestamp = Clock::get()?.unix_timestamp as u64;
+        let execute_after = current_timestamp
+            .checked_add(config.config_change_delay as u64)
+            .ok_or(error!(GameError::MathOverflow))?;
+
+        let config_change = &mut ctx.accounts.config_change;
+        config_change.bump = ctx.bumps.config_change;
+        config_change.game_id = config.game_id;
+        config_change.change_id = change_id;
+        config_change.execute_after = execute_after;
+        config_change.change = change.clone();
+
+        emit_cpi!(ConfigChangeProposed {
+            config_change: config_change.key(),
+            change,
+            execute_after,
+        });
+
+        Ok(())
+    }
+
+    pub fn cancel_config_change(
+        ctx: Context<CancelConfigChange>,
+    ) -> anchor_lang::prelude::Result<()> {
+        emit_cpi!(ConfigChangeCancelled {
+            config_change: ctx.accounts.config_change.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Applies a queued config change once its delay has passed. Pair changes
+    /// take the pair account to create or close; other changes take neither.
+    pub fn execute_config_change(
+        ctx: Context<ExecuteConfigChange>,
+    ) -> anchor_lang::prelude::Result<()> {
+        let config_change = &ctx.accounts.config_change;
+        let current_timestamp = Clock::get()?.unix_timestamp as u64;
+
+        if current_timestamp < config_change.execute_after {
+            return Err(error!(GameError::ConfigChangeNotReady));
+        }
+
+        // Only price bounds and oracle changes update an existing pair, besides
+        // adding back a deleted pair.
+        if ctx.accounts.updated_pair.is_some()
+            && !matches!(
+                config_change.change,
+                ConfigChangeKind::AddPair { .. }
+                    | ConfigChangeKind::SetPriceBounds { .. }
+                    | ConfigChangeKind::SetOracle { .. }
+            )
+        {
+            return Err(error!(GameError::InvalidConfigChange));
+        }
+
+        let mut config = ctx.accounts.game_config.load_mut()?;
+
+        match config_change.change {
+            ConfigChangeKind::SetAmounts {
+                min_bet,
+                max_bet,
+                max_utilized_liquidity,
+            } => {
+                if ctx.accounts.new_pair.is_some() || ctx.accounts.removed_pair.is_some() {
+                    return Err(error!(GameError::InvalidConfigChange));
+                }
+
+                // Bets already placed keep their liquidity locked, so the cap can't
+                // drop below it.
+                if max_utilized_liquidity < ctx.accounts.game_state.load()?.locked_liquidity {
+                    return Err(error!(GameError::LiquidityBelowLocked));
+                }
+
+                config.min_bet = min_bet;
+                config.max_bet = max_bet;
+                config.max_utilized_liquidity = max_utilized_liquidity;
+            }
+            ConfigChangeKind::AddPair {
+                pair,
+                feed_id,
+                max_move_bps,
+                max_conf_bps,
+            } => {
+                // A deleted pair still has its account, which is enabled again.
+                let mut accepted_pair = match (
+                    &ctx.accounts.new_pair,
+                    &ctx.accounts.removed_pair,
+                    &ctx.accounts.updated_pair,
+                ) {
+                    (Some(new_pair), None, None) => {
+                        let mut accepted_pair = new_pair.load_init()?;
+                        accepted_pair.bump = ctx.bumps.new_pair;
+                        accepted_pair
+                    }
+                    (None, None, Some(updated_pair)) => {
+                        let accepted_pair = updated_pair.load_mut()?;
+                        if !accepted_pair.disabled {
+                            return Err(error!(GameError::InvalidConfigChange));
+                        }
+                        accepted_pair
+                    }
+                    _ => return Err(error!(GameError::InvalidConfigChange)),
+                };
+
+                accepted_pair.game_id = config.game_id;
+                accepted_pair.pair = pair;
+                accepted_pair.feed_id = feed_id;
+                accepted_pair.max_move_bps = max_move_bps;
+                accepted_pair.max_conf_bps = max_conf_bps;
+                accepted_pair.pull_oracle = false;
+                accepted_pair.disabled = false;
+            }
+            ConfigChangeKind::DeletePair { .. } => {
+                let removed_pair = match (&ctx.accounts.new_pair, &ctx.accounts.removed_pair) {
+                    (None, Some(removed_pair)) => removed_pair,
+                    _ => return Err(error!(GameError::InvalidConfigChange)),
+                };
+
+                // Open bets on the pair still settle against its feed and bounds,
+                // so the pair only stops taking new bets instead of being closed.
+                removed_pair.load_mut()?.disabled = true;
+            }
+            ConfigChangeKind::SetConfigChangeDelay { delay } => {
+                if ctx.accounts.new_pair.is_some() || ctx.accounts.removed_pair.is_some() {
+                    return Err(error!(GameError::InvalidConfigChange));
+                }
+
+                config.config_change_delay = delay;
+            }
+            ConfigChangeKind::SetPriceBounds {
+                max_move_bps,
+                max_conf_bps,
+                ..
+            } => {
+                let updated_pair = match (
+                    &ctx.accounts.new_pair,
+                    &ctx.accounts.removed_pair,
+                    &ctx.accounts.updated_pair,
+                ) {
+                    (None, None, Some(updated_pair)) => updated_pair,
+                    _ => return Err(error!(GameError::InvalidConfigChange)),
+                };
+
+                let mut accepted_pair = updated_pair.load_mut()?;
+                accepted_pair.max_move_bps = max_move_bps;
+                accepted_pair.max_conf_bps = max_conf_bps;
+            }
+            ConfigChangeKind::SetOracle { pull_oracle, .. } => {
+                let updated_pair = match (
+                    &ctx.accounts.new_pair,
+                    &ctx.accounts.removed_pair,
+                    &ctx.accounts.updated_pair,
+                ) {
+                    (None, None, Some(updated_pair)) => updated_pair,
+                    _ => return Err(error!(GameError::InvalidConfigChange)),
+                };
+
+                updated_pair.load_mut()?.pull_oracle = pull_oracle;
+            }
+            ConfigChangeKind::SetSwitchboardRoutine {
+                switchboard_routine,
+            } => {
+                if ctx.accounts.new_pair.is_some() || ctx.accounts.removed_pair.is_some() {
+                    return Err(error!(GameError::InvalidConfigChange));
+                }
+
+                config.switchboard_routine = switchboard_routine;
+            }
+            ConfigChangeKind::SetUserLimits {
+                bet_cooldown,
+                max_open_bets,
+            } => {
+                if ctx.accounts.new_pair.is_some() || ctx.accounts.removed_pair.is_some() {
+                    return Err(error!(GameError::InvalidConfigChange));
+                }
+
+                config.bet_cooldown = bet_cooldown;
+                config.max_open_bets = max_open_bets;
+            }
+            ConfigChangeKind::SetDisputeParams {
+                dispute_threshold,
+                dispute_window,
+            } => {
+                if ctx.accounts.new_pair.is_some() || ctx.accounts.removed_pair.is_some() {
+                    return Err(error!(GameError::InvalidConfigChange));
+                }
+
+                config.dispute_threshold = dispute_threshold;
+                config.dispute_window = dispute_window;
+            }
+            ConfigChangeKind::SetMaxPublishDelay { max_publish_delay } => {
+                if ctx.accounts.new_pair.is_some() || ctx.accounts.removed_pair.is_some() {
+                    return Err(error!(GameError::InvalidConfigChange));
+                }
+
+                config.max_publish_delay = max_publish_delay;
+            }
+            ConfigChangeKind::SetEntryDelays {
+                min_entry_delay,
+                max_entry_delay,
+            } => {
+                if ctx.accounts.new_pair.is_some() || ctx.accounts.removed_pair.is_some() {
+                    return Err(error!(GameError::InvalidConfigChange));
+                }
+
+                config.min_entry_delay = min_entry_delay;
+                config.max_entry_delay = max_entry_delay;
+            }
+            ConfigChangeKind::SetReferralShare { referral_share_bps } => {
+                if ctx.accounts.new_pair.is_some() || ctx.accounts.removed_pair.is_some() {
+                    return Err(error!(GameError::InvalidConfigChange));
+                }
+
+                config.referral_share_bps = referral_share_bps;
+            }
+            ConfigChangeKind::SetAllowlistRoot { allowlist_root } => {
+                if ctx.accounts.new_pair.is_some() || ctx.accounts.removed_pair.is_some() {
+                    return Err(error!(GameError::InvalidConfigChange));
+                }
+
+                config.allowlist_root = allowlist_root;
+            }
+            ConfigChangeKind::SetRoles {
+                pauser,
+                risk_manager,
+                treasurer,
+            } => {
+                if ctx.accounts.new_pair.is_some() || ctx.accounts.removed_pair.is_some() {
+                    return Err(error!(GameError::InvalidConfigChange));
+                }
+
+                config.pauser = pauser;
+                config.risk_manager = risk_manager;
+                config.treasurer = treasurer;
+            }
+        }
+
+        emit_cpi!(ConfigChangeExecuted {
+            config_change: config_change.key(),
+            change: config_change.change.clone(),
+        });
+
+        Ok(())
+    }
+
+    /// Grows a config created by an older program version to the current layout
+    /// and upgrades it in place. Configs from before versioning (version 0) also
+    /// predate game IDs and are moved by migrate_legacy_game instead.
+    pub fn migrate_config(ctx: Context<MigrateConfig>) -> anchor_lang::prelude::Result<()> {
+        let game_config = &ctx.accounts.game_config;
+        let mut config = game_config.load_mut()?;
+
+        let game_id_bytes = config.game_id.to_le_bytes();
+        let config_key = Pubkey::create_program_address(
+            &[GAME_CONFIG_SEED, game_id_bytes.as_ref(), &[config.bump]],
+            &id(),
+        )
+        .map_err(|_| error!(GameError::Unauthorized))?;
+
+        if config_key != game_config.key() || config.authority != ctx.accounts.payer.key() {
+            return Err(error!(GameError::Unauthorized));
+        }
+
+        if config.version == 0 || config.version > GAME_CONFIG_VERSION {
+            return Err(error!(GameError::InvalidAccountVersion));
+        }
+
+        if config.version < 2 {
+            config.config_change_delay = CONFIG_CHANGE_DELAY;
+        }
+
+        if config.version < 3 {
+            config.pauser = config.authority;
+            config.risk_manager = config.authority;
+            config.treasurer = config.authority;
+        }
+
+        if config.version < 4 {
+            config.max_publish_delay = MAX_PUBLISH_DELAY;
+        }
+
+        // Bets keep settling by request until a routine is set.
+        if config.version < 5 {
+            config.switchboard_routine = Pubkey::default();
+        }
+
+        config.version = GAME_CONFIG_VERSION;
+
+        Ok(())
+    }
+
+    /// Grows the game state to the current layout and upgrades it in place. The
+    /// config has to be migrated first. As with configs, a state from before
+    /// versioning is moved by migrate_legacy_game instead.
+    pub fn migrate_state(ctx: Context<MigrateState>) -> anchor_lang::prelude::Result<()> {
+        let game_state = &ctx.accounts.game_state;
+        let mut state = game_state.load_mut()?;
+
+        let game_id_bytes = ctx.accounts.game_config.load()?.game_id.to_le_bytes();
+        let state_key = Pubkey::create_program_address(
+            &[GAME_STATE_SEED, game_id_bytes.as_ref(), &[state.bump]],
+            &id(),
+        )
+        .map_err(|_| error!(GameError::Unauthorized))?;
+
+        if state_key != game_state.key() {
+            return Err(error!(GameError::Unauthorized));
+        }
+
+        if state.version == 0 || state.version > GAME_STATE_VERSION {
+            return Err(error!(GameError::InvalidAccountVersion));
+        }
+
+        state.version = GAME_STATE_VERSION;
+
+        Ok(())
+    }
+
+    /// Grows a pair registered before pairs could be disabled, after which it reads
+    /// as enabled. Anyone can migrate a pair as the payer funds the extra rent.
+    pub fn migrate_pair(
+        ctx: Context<MigratePair>,
+        pair: [u8; 8],
+    ) -> anchor_lang::prelude::Result<()> {
+        let accepted_pair = ctx.accounts.accepted_pair.load()?;
+
+        let game_id_bytes = ctx.accounts.game_config.load()?.game_id.to_le_bytes();
+        let pair_key = Pubkey::create_program_address(
+            &[PAIR_SEED, game_id_bytes.as_ref(), pair.as_ref(), &[accepted_pair.bump]],
+            &id(),
+        )
+        .map_err(|_| error!(GameError::Unauthorized))?;
+
+        if pair_key != ctx.accounts.accepted_pair.key() || accepted_pair.pair != pair {
+            return Err(error!(GameError::Unauthorized));
+        }
+
+        Ok(())
+    }
+
+    /// Grows a bet to the current layout so it can still be settled or cancelled.
+    /// Anyone can migrate a bet as the payer funds the extra rent. Bets from
+    /// before versioning are moved by migrate_legacy_bet instead.
+    pub fn migrate_bet(ctx: Context<MigrateBet>, bet_id: u64) -> anchor_lang::prelude::Result<()> {
+        let mut bet = ctx.accounts.bet.load_mut()?;
+
+        let game_id_bytes = ctx.accounts.game_config.load()?.game_id.to_le_bytes();
+        let bet_key = Pubkey::create_program_address(
+            &[BET_SEED, game_id_bytes.as_ref(), bet_id.to_le_bytes().as_ref(), &[bet.bump]],
+            &id(),
+        )
+        .map_err(|_| error!(GameError::Unauthorized))?;
+
+        if bet_key != ctx.accounts.bet.key() || bet.bet_id != bet_id {
+            return Err(error!(GameError::Unauthorized));
+        }
+
+        if bet.version == 0 || bet.version > BET_VERSION {
+            return Err(error!(GameError::InvalidAccountVersion));
+        }
+
+        bet.version = BET_VERSION;
+
+        Ok(())
+    }
+
+    /// Moves the game deployed before game IDs and versioning to the accounts of
+    /// `game_id`, re-laying out its config and state. Limits carried by the old
+    /// config are kept and everything it lacked gets its default, as on
+    /// initialize. The escrow balance moves along and the old accounts are
+    /// closed. Pairs are registered again through add_pair.
+    pub fn migrate_legacy_game(
+        ctx: Context<MigrateLegacyGame>,
+        game_id: u64,
+    ) -> anchor_lang::prelude::Result<()> {
+        let legacy_config: LegacyGameConfig =
+            read_legacy_account(&ctx.accounts.legacy_config, GameConfig::DISCRIMINATOR)?;
+        let legacy_state: LegacyGameState =
+            read_legacy_account(&ctx.accounts.legacy_state, GameState::DISCRIMINATOR)?;
+
+        if legacy_config.authority != ctx.accounts.payer.key() {
+            return Err(error!(GameError::Unauthorized));
+        }
+
+        if legacy_config.token_mint != ctx.accounts.token_mint.key()
+            || legacy_config.game_escrow != ctx.accounts.legacy_escrow.key()
+        {
+            return Err(error!(GameError::InvalidAccountVersion));
+        }
+
+        let mut config = ctx.accounts.game_config.load_init()?;
+        config.bump = ctx.bumps.game_config;
+        config.version = GAME_CONFIG_VERSION;
+        config.game_id = game_id;
+        config.authority = legacy_config.authority;
+        config.pauser = legacy_config.authority;
+        config.risk_manager = legacy_config.authority;
+        config.treasurer = legacy_config.authority;
+        config.min_bet = legacy_config.min_bet;
+        config.max_bet = legacy_config.max_bet;
+        config.max_utilized_liquidity = legacy_config.max_utilized_liquidity;
+        config.cancel_buffer = legacy_config.cancel_buffer;
+        config.max_interval = legacy_config.max_interval;
+        config.min_interval = legacy_config.min_interval;
+        config.min_entry_delay = MIN_ENTRY_DELAY;
+        config.max_entry_delay = MAX_ENTRY_DELAY;
+        config.bet_cooldown = BET_COOLDOWN;
+        config.max_open_bets = MAX_OPEN_BETS;
+        config.config_change_delay = CONFIG_CHANGE_DELAY;
+        config.max_publish_delay = MAX_PUBLISH_DELAY;
+        config.leverage = legacy_config.leverage;
+        config.referral_share_bps = REFERRAL_SHARE_BPS;
+        config.switchboard_function = legacy_config.switchboard_function;
+        config.token_mint = legacy_config.token_mint;
+        config.game_escrow = ctx.accounts.game_escrow.key();
+        config.legacy = true;
+
+        // Bets keep their IDs when moved, so new ones carry on from the old state.
+        let mut state = ctx.accounts.game_state.load_init()?;
+        state.bump = ctx.bumps.game_state;
+        state.version = GAME_STATE_VERSION;
+        state.game_id = game_id;
+        state.next_bet_id = legacy_state.next_bet_id;
+        state.locked_liquidity = legacy_state.locked_liquidity;
+
+        // Transfer token
+        let seeds = &[GAME_CONFIG_SEED, &[ctx.bumps.legacy_config]];
+        let binding = &[seeds.as_slice()];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.legacy_escrow.to_account_info().clone(),
+            to: ctx.accounts.game_escrow.to_account_info().clone(),
+            authority: ctx.accounts.legacy_config.to_account_info().clone(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info().clone();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, binding);
+        transfer(cpi_ctx, ctx.accounts.legacy_escrow.amount)?;
+
+        let cpi_accounts = CloseAccount {
+            account: ctx.accounts.legacy_escrow.to_account_info().clone(),
+            destination: ctx.accounts.payer.to_account_info().clone(),
+            authority: ctx.accounts.legacy_config.to_account_info().clone(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info().clone();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, binding);
+        close_account(cpi_ctx)?;
+
+        close_legacy_account(&ctx.accounts.legacy_config, &ctx.accounts.payer)?;
+        close_legacy_account(&ctx.accounts.legacy_state, &ctx.accounts.payer)?;
+
+        Ok(())
+    }
+
+    /// Moves a bet placed before game IDs and versioning to its address in the
+    /// game migrate_legacy_game moved, so it can still be settled or cancelled.
+    /// Anyone can migrate a bet, as the old account's rent goes to the payer of
+    /// the new one.
+    pub fn migrate_legacy_bet(
+        ctx: Context<MigrateLegacyBet>,
+        bet_id: u64,
+    ) -> anchor_lang::prelude::Result<()> {
+        let legacy_bet: LegacyBet =
+            read_legacy_account(&ctx.accounts.legacy_bet, Bet::DISCRIMINATOR)?;
+
+        if legacy_bet.bet_id != bet_id {
+            return Err(error!(GameError::Unauthorized));
+        }
+
+        let mut bet = ctx.accounts.bet.load_init()?;
+        bet.bump = ctx.bumps.bet;
+        bet.version = BET_VERSION;
+        bet.bet_id = bet_id;
+        bet.amount = legacy_bet.amount;
+        bet.payout = legacy_bet.payout;
+        bet.start_time = legacy_bet.start_time;
+        bet.end_time = legacy_bet.end_time;
+        bet.open_price = legacy_bet.open_price;
+        bet.close_price = legacy_bet.close_price;
+        bet.user = legacy_bet.user;
+        bet.user_token_account = legacy_bet.user_token_account;
+        bet.pair = legacy_bet.pair;
+        bet.is_long = legacy_bet.is_long != 0;
+        bet.active = legacy_bet.active != 0;
+        bet.switchboard_request = legacy_bet.switchboard_request;
+
+        close_legacy_account(&ctx.accounts.legacy_bet, &ctx.accounts.payer)?;
+
+        Ok(())
+    }
+
+    /// Stops new bets and subscription rounds. Open bets still settle and can be
+    /// cancelled.
+    pub fn pause(ctx: Context<PauseGame>) -> anchor_lang::prelude::Result<()> {
+        let mut config = ctx.accounts.game_config.load_mut()?;
+        config.paused = true;
+
+        emit_cpi!(GamePaused {
+            pauser: ctx.accounts.payer.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn unpause(ctx: Context<ModifyConfig>) -> anchor_lang::prelude::Result<()> {
+        let mut config = ctx.accounts.game_config.load_mut()?;
+        config.paused = false;
+
+        emit_cpi!(GameUnpaused {});
+
+        Ok(())
+    }
+
+    /// Withdraws house funds that are not owed to bettors or referrers.
+    pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> anchor_lang::prelude::Result<()> {
+        let config = ctx.accounts.game_config.load()?;
+        let state = ctx.accounts.game_state.load()?;
+
+        let owed = state.owed()?;
+        if amount > ctx.accounts.game_escrow.amount.saturating_sub(owed) {
+            return Err(error!(GameError::InsufficientLiquidity));
+        }
+
+        // Transfer token
+        let game_id_bytes = config.game_id.to_le_bytes();
+        let seeds = &[GAME_CONFIG_SEED, game_id_bytes.as_ref(), &[config.bump]];
+        let binding = &[seeds.as_slice()];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.game_escrow.to_account_info().clone(),
+            to: ctx.accounts.destination_token_account.to_account_info().clone(),
+            authority: ctx.accounts.game_config.to_account_info().clone(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info().clone();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, binding);
+        transfer(cpi_ctx, amount)?;
+
+        ctx.accounts.game_escrow.reload()?;
+        state.check_solvency(ctx.accounts.game_escrow.amount)?;
+
+        Ok(())
+    }
+
+    /// Reports the escrow balance against what it owes and fails if it falls short.
+    pub fn check_solvency(ctx: Context<CheckSolvency>) -> anchor_lang::prelude::Result<Solvency> {
+        let state = ctx.accounts.game_state.load()?;
+        let escrow_balance = ctx.accounts.game_escrow.amount;
+        let owed = state.owed()?;
+
+        msg!(
+            "escrow balance: {}, locked liquidity: {}, owed: {}",
+            escrow_balance,
+            { state.locked_liquidity },
+            owed
+        );
+        state.check_solvency(escrow_balance)?;
+
+        Ok(Solvency {
+            escrow_balance,
+            locked_liquidity: state.locked_liquidity,
+            owed,
+            free_liquidity: escrow_balance - owed,
+        })
+    }
+}
+
+impl<'info> BetSettlement<'info> {
+    /// Resolves the bet from its open and close prices and pays out the result.
+    /// The event is left to the instruction, which emits it through a CPI.
+    pub fn settle(
+        &mut self,
+        bet_id: u64,
+        open: OraclePrice,
+        close: OraclePrice,
+        price_expo: i32,
+    ) -> Result<(SettlementEvent, Option<BetPlaced>)> {
+        let mut bet = self.bet.load_mut()?;
+
+        if !bet.active {
+            return Err(error!(GameError::InactiveBet));
+        }
+
+        let fee = bet.house_edge();
+
+        let open_price = open.price;
+        let close_price = close.price;
+
+        // A price outside the market's bounds points at a bad oracle read, so the
+        // bet is left to be cancelled instead.
+        let accepted_pair = self.accepted_pair.load()?;
+        accepted_pair.validate_prices(open_price, close_price)?;
+        let uncertain = accepted_pair.is_uncertain(&open, &close);
+
+        let config = self.game_config.load()?;
+        config.validate_publish_times(&bet, &open, &close)?;
+
+        bet.active = false;
+        bet.open_price = open_price;
+        bet.close_price = close_price;
+        bet.price_expo = i8::try_from(price_expo).map_err(|_| error!(GameError::InvalidPrice))?;
+        bet.open_conf = open.conf;
+        bet.close_conf = close.conf;
+        bet.open_publish_offset = publish_offset(open.publish_time, bet.start_time)?;
+        bet.close_publish_offset = publish_offset(close.publish_time, bet.end_time)?;
+
+        let mut state = self.game_state.load_mut()?;
+        let payout = bet.payout;
+        state.locked_liquidity = state
+            .locked_liquidity
+            .checked_sub(payout)
+            .ok_or(error!(GameError::MathUnderflow))?;
+
+        let won =
+            (bet.is_long && close_price >= open_price) || (!bet.is_long && close_price <= open_price);
+
+        let event = if uncertain || won {
+            // Too uncertain a result refunds the stake instead, which a voucher never paid.
+            let payout = if !uncertain {
+                payout
+            } else if bet.voucher == Pubkey::default() {
+                bet.amount
+            } else {
+                0
+            };
+
+            // Large payouts are held for the dispute window and claimed afterwards
+            // via claim_position or claim_payout.
+            let held =
+                !uncertain && config.dispute_window > 0 && payout >= config.dispute_threshold;
+
+            self.pay_out(&mut bet, &mut state, &config, payout, held)?;
+
+            if uncertain {
+                bet.payout = payout;
+
+                release_subscription_round(&self.subscription, &bet, None)?;
+                record_tournament_result(&self.tournament_entry, &bet, 0)?;
+                release_open_bet(&self.user_profile, &bet)?;
+
+                SettlementEvent::Refunded(BetRefunded {
+                    bet_id,
+                    user: bet.user,
+                    pair: bet.pair,
+                    is_long: bet.is_long,
+                    amount: bet.amount,
+                    start_time: bet.start_time,
+                    end_time: bet.end_time,
+                    open_price,
+                    close_price,
+                    price_expo,
+                    refund: payout,
+                })
+            } else {
+                // A held payout may still be voided, so the subscription and the
+                // tournament only count it once it is claimed or disputed.
+                if !held {
+                    release_subscription_round(&self.subscription, &bet, Some(payout))?;
+                    let pnl = bet_pnl(payout, bet.amount)?;
+                    record_tournament_result(&self.tournament_entry, &bet, pnl)?;
+                }
+                release_open_bet(&self.user_profile, &bet)?;
+
+                SettlementEvent::Executed(BetExecuted {
+                    bet_id,
+                    user: bet.user,
+                    pair: bet.pair,
+                    is_long: bet.is_long,
+                    amount: bet.amount,
+                    start_time: bet.start_time,
+                    end_time: bet.end_time,
+                    open_price,
+                    close_price,
+                    price_expo,
+                    won: true,
+                    payout,
+                    fee,
+                })
+            }
+        } else {
+            bet.payout = 0;
+
+            accrue_referral_reward(
+                &self.user_profile,
+                &self.referrer,
+                &bet,
+                &config,
+                &mut state,
+                fee,
+            )?;
+            release_subscription_round(&self.subscription, &bet, None)?;
+            let pnl = i64::try_from(bet.amount)
+                .ok()
+                .and_then(i64::checked_neg)
+                .ok_or(error!(GameError::MathOverflow))?;
+            record_tournament_result(&self.tournament_entry, &bet, pnl)?;
+            release_open_bet(&self.user_profile, &bet)?;
+
+            SettlementEvent::Executed(BetExecuted {
+                bet_id,
+                user: bet.user,
+                pair: bet.pair,
+                is_long: bet.is_long,
+                amount: bet.amount,
+                start_time: bet.start_time,
+                end_time: bet.end_time,
+                open_price,
+                close_price,
+                price_expo,
+                won: false,
+                payout: 0,
+                fee,
+            })
+        };
+
+        let next_round = self.open_next_round(&bet, &config, &mut state)?;
+
+        self.game_escrow.reload()?;
+        state.check_solvency(self.game_escrow.amount)?;
+
+        // TODO: close account
+
+        Ok((event, next_round))
+    }
+
+    /// Voids a bet the oracle could not price, refunding the stake right away
+    /// instead of after the cancel buffer.
+    pub fn void(
+        &mut self,
+        bet_id: u64,
+        reason: SettlementFailure,
+    ) -> Result<(SettlementFailed, Option<BetPlaced>)> {
+        let mut bet = self.bet.load_mut()?;
+
+        if !bet.active {
+            return Err(error!(GameError::InactiveBet));
+        }
+
+        bet.active = false;
+
+        let config = self.game_config.load()?;
+        let mut state = self.game_state.load_mut()?;
+        state.locked_liquidity = state
+            .locked_liquidity
+            .checked_sub(bet.payout)
+            .ok_or(error!(GameError::MathUnderflow))?;
+
+        // A voucher stake never left the house, so there is nothing to refund.
+        let refund = if bet.voucher == Pubkey::default() {
+            bet.amount
+        } else {
+            0
+        };
+        bet.payout = refund;
+        self.pay_out(&mut bet, &mut state, &config, refund, false)?;
+
+        release_subscription_round(&self.subscription, &bet, None)?;
+        record_tournament_result(&self.tournament_entry, &bet, 0)?;
+        release_open_bet(&self.user_profile, &bet)?;
+
+        let event = SettlementFailed {
+            bet_id,
+            user: bet.user,
+            pair: bet.pair,
+            is_long: bet.is_long,
+            amount: bet.amount,
+            start_time: bet.start_time,
+            end_time: bet.end_time,
+            reason,
+            refund,
+        };
+
+        let next_round = self.open_next_round(&bet, &config, &mut state)?;
+
+        self.game_escrow.reload()?;
+        state.check_solvency(self.game_escrow.amount)?;
+
+        Ok((event, next_round))
+    }
+
+    /// Opens the next round of the subscription `bet` belongs to, creating its bet
+    /// from the rent the subscription prefunded. A round that can't be opened,
+    /// e.g. the subscription ran out of rounds or balance, is left to
+    /// open_subscription_round instead of failing the settlement.
+    fn open_next_round(
+        &self,
+        bet: &Bet,
+        config: &GameConfig,
+        state: &mut GameState,
+    ) -> Result<Option<BetPlaced>> {
+        if bet.subscription == Pubkey::default()
+            || config.paused
+            || self.accepted_pair.load()?.disabled
+        {
+            return Ok(None);
+        }
+
+        let (Some(subscription), Some(user_profile), Some(next_bet), Some(system_program)) = (
+            &self.subscription,
+            &self.user_profile,
+            &self.next_bet,
+            &self.system_program,
+        ) else {
+            return Ok(None);
+        };
+        if user_profile.key() != bet.user_profile {
+            return Ok(None);
+        }
+
+        let game_id_bytes = config.game_id.to_le_bytes();
+        let bet_id_bytes = state.next_bet_id.to_le_bytes();
+        let (next_bet_key, next_bet_bump) = Pubkey::find_program_address(
+            &[BET_SEED, game_id_bytes.as_ref(), bet_id_bytes.as_ref()],
+            &crate::ID,
+        );
+        if next_bet.key() != next_bet_key || !next_bet.data_is_empty() {
+            return Ok(None);
+        }
+
+        // The rent is taken from what the subscription holds above its own.
+        let space = 8 + std::mem::size_of::<Bet>();
+        let rent = Rent::get()?;
+        let subscription_info = subscription.to_account_info();
+        let round_rent = rent
+            .minimum_balance(space)
+            .saturating_sub(next_bet.lamports());
+        let prefunded_rent = subscription_info
+            .lamports()
+            .saturating_sub(rent.minimum_balance(subscription_info.data_len()));
+        if prefunded_rent < round_rent {
+            return Ok(None);
+        }
+
+        let Some(subscription_escrow) = read_token_account(&self.user_token_account) else {
+            return Ok(None);
+        };
+        let current_timestamp = Clock::get()?.unix_timestamp as u64;
+        let opened = subscription.load_mut()?.open_round(
+            config,
+            state,
+            &mut *user_profile.load_mut()?,
+            subscription_escrow.amount,
+            current_timestamp,
+        );
+        let Ok((round, event)) = opened else {
+            return Ok(None);
+        };
+
+        **subscription_info.try_borrow_mut_lamports()? -= round_rent;
+        **next_bet.try_borrow_mut_lamports()? += round_rent;
+
+        let seeds = &[
+            BET_SEED,
+            game_id_bytes.as_ref(),
+            bet_id_bytes.as_ref(),
+            &[next_bet_bump],
+        ];
+        let binding = &[seeds.as_slice()];
+        let cpi_accounts = Allocate {
+            account_to_allocate: next_bet.to_account_info().clone(),
+        };
+        let cpi_program = system_program.to_account_info().clone();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, binding);
+        allocate(cpi_ctx, space as u64)?;
+        let cpi_accounts = Assign {
+            account_to_assign: next_bet.to_account_info().clone(),
+        };
+        let cpi_program = system_program.to_account_info().clone();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, binding);
+        assign(cpi_ctx, &crate::ID)?;
+
+        // Transfer token
+        let subscription = subscription.load()?;
+        let subscription_id_bytes = subscription.subscription_id.to_le_bytes();
+        let seeds = &[
+            SUBSCRIPTION_SEED,
+            game_id_bytes.as_ref(),
+            subscription.user.as_ref(),
+            subscription_id_bytes.as_ref(),
+            &[subscription.bump],
+        ];
+        let binding = &[seeds.as_slice()];
+        let cpi_accounts = Transfer {
+            from: self.user_token_account.to_account_info().clone(),
+            to: self.game_escrow.to_account_info().clone(),
+            authority: subscription_info.clone(),
+        };
+        let cpi_program = self.token_program.to_account_info().clone();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, binding);
+        transfer(cpi_ctx, round.amount)?;
+
+        let mut data = next_bet.try_borrow_mut_data()?;
+        data[..8].copy_from_slice(&Bet::DISCRIMINATOR);
+        let next_bet: &mut Bet = bytemuck::from_bytes_mut(&mut data[8..]);
+        *next_bet = round;
+        next_bet.bump = next_bet_bump;
+        next_bet.subscription = subscription_info.key();
+        next_bet.user_profile = user_profile.key();
+
+        Ok(Some(event))
+    }
+
+    /// Pays `payout` to the bettor, or leaves it claimable by the position holder
+    /// when the position changed hands or the payout is `held` for disputes.
+    fn pay_out(
+        &self,
+        bet: &mut Bet,
+        state: &mut GameState,
+        config: &GameConfig,
+        payout: u64,
+        held: bool,
+    ) -> Result<()> {
+        if payout == 0 {
+            return Ok(());
+        }
+
+        // A position is paid out to its holder via claim_position when it changed
+        // hands, or when the bettor's accounts can't be read, e.g. were closed.
+        if !held && (bet.position_mint == Pubkey::default() || self.bettor_holds_position(bet)) {
+            // Transfer token
+            let game_id_bytes = config.game_id.to_le_bytes();
+            let seeds = &[GAME_CONFIG_SEED, game_id_bytes.as_ref(), &[config.bump]];
+            let binding = &[seeds.as_slice()];
+            let cpi_accounts = Transfer {
+                from: self.game_escrow.to_account_info().clone(),
+                to: self.user_token_account.to_account_info().clone(),
+                authority: self.game_config.to_account_info().clone(),
+            };
+            let cpi_program = self.token_program.to_account_info().clone();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, binding);
+            transfer(cpi_ctx, payout)?;
+
+            // The position token can't be burned without its holder, so it is
+            // marked as redeemed instead.
+            if bet.position_mint != Pubkey::default() {
+                bet.position_redeemed = true;
+            }
+        } else {
+            bet.claimable = payout;
+            state.unclaimed_payouts = state
+                .unclaimed_payouts
+                .checked_add(payout)
+                .ok_or(error!(GameError::MathOverflow))?;
+
+            if held {
+                let current_timestamp = Clock::get()?.unix_timestamp as u64;
+                bet.disputable_until = current_timestamp
+                    .checked_add(config.dispute_window as u64)
+                    .ok_or(error!(GameError::MathOverflow))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns whether the bettor's token account is open and its owner still
+    /// holds the position of `bet`.
+    fn bettor_holds_position(&self, bet: &Bet) -> bool {
+        let (Some(user_token_account), Some(position_token_account)) = (
+            read_token_account(&self.user_token_account),
+            self.position_token_account
+                .as_ref()
+                .and_then(|account| read_token_account(account)),
+        ) else {
+            return false;
+        };
+
+        holds_position(Some(&position_token_account), bet, user_token_account.owner)
+    }
+}
+
+impl ConfigChangeKind {
+    /// The pair a pair change registers or removes.
+    pub fn pair(&self) -> [u8; 8] {
+        match self {
+            ConfigChangeKind::AddPair { pair, .. }
+            | ConfigChangeKind::DeletePair { pair }
+            | ConfigChangeKind::SetPriceBounds { pair, .. }
+            | ConfigChangeKind::SetOracle { pair, .. } => *pair,
+            _ => [0u8; 8],
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            ConfigChangeKind::SetAmounts {
+                min_bet, max_bet, ..
+            } => {
+                if *min_bet == 0 || *max_bet == 0 || min_bet >= max_bet {
+                    return Err(error!(GameError::InvalidAmount));
+                }
+            }
+            // Tickers are 8 bytes of upper case letters and digits, padded with
+            // `X`, e.g. `BTCUSDXX`. The Switchboard function prices the pair from
+            // the Pyth feed `feed_id`.
+            ConfigChangeKind::AddPair { pair, feed_id, .. } => {
+                if !pair
+                    .iter()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+                {
+                    return Err(error!(GameError::InvalidTicker));
+                }
+
+                if *feed_id == [0u8; 32] {
+                    return Err(error!(GameError::InvalidFeedId));
+                }
+            }
+            ConfigChangeKind::DeletePair { .. } => {}
+            ConfigChangeKind::SetConfigChangeDelay { .. } => {}
+            ConfigChangeKind::SetPriceBounds { .. } => {}
+            ConfigChangeKind::SetOracle { .. } => {}
+            ConfigChangeKind::SetSwitchboardRoutine { .. } => {}
+            ConfigChangeKind::SetUserLimits { .. } => {}
+            // Winning payouts of at least `dispute_threshold` are held for
+            // `dispute_window` seconds. A zero window pays every settlement
+            // immediately.
+            ConfigChangeKind::SetDisputeParams { dispute_window, .. } => {
+                if *dispute_window > MAX_DISPUTE_WINDOW {
+                    return Err(error!(GameError::InvalidDisputeWindow));
+                }
+            }
+            // Bounds how far the oracle publish times may be from a bet's start
+            // and end.
+            ConfigChangeKind::SetMaxPublishDelay { max_publish_delay } => {
+                if *max_publish_delay == 0 || *max_publish_delay > PUBLISH_DELAY_LIMIT {
+                    return Err(error!(GameError::InvalidPublishDelay));
+                }
+            }
+            ConfigChangeKind::SetEntryDelays {
+                min_entry_delay,
+                max_entry_delay,
+            } => {
+                if *min_entry_delay == 0
+                    || min_entry_delay > max_entry_delay
+                    || *max_entry_delay > ENTRY_DELAY_LIMIT
+                {
+                    return Err(error!(GameError::InvalidEntryDelay));
+                }
+            }
+            ConfigChangeKind::SetReferralShare { referral_share_bps } => {
+                if *referral_share_bps > MAX_BPS {
+                    return Err(error!(GameError::InvalidReferralShare));
+                }
+            }
+            // A zero root turns gating off; bets and subscriptions opened before
+            // a rotation are unaffected.
+            ConfigChangeKind::SetAllowlistRoot { .. } => {}
+            // The authority is the super-admin and keeps every role.
+            ConfigChangeKind::SetRoles { .. } => {}
+        }
+
+        Ok(())
+    }
+}
+
+impl GameConfig {
+    /// Roles are held by their assignee and by the authority, the super-admin.
+    pub fn is_pauser(&self, key: &Pubkey) -> bool {
+        *key == self.authority || *key == self.pauser
+    }
+
+    pub fn is_risk_manager(&self, key: &Pubkey) -> bool {
+        *key == self.authority || *key == self.risk_manager
+    }
+
+    pub fn is_treasurer(&self, key: &Pubkey) -> bool {
+        *key == self.authority || *key == self.treasurer
+    }
+
+    /// Pairs are checked by the `AcceptedPair` account the instruction requires.
+    pub fn validate_bet(&self, amount: u64, interval: u32) -> Result<()> {
+        if amount < self.min_bet || amount > self.max_bet {
+            return Err(error!(GameError::InvalidAmount));
+        }
+
+        if interval < self.min_interval || interval > self.max_interval {
+            return Err(error!(GameError::InvalidInterval));
+        }
+
+        Ok(())
+    }
+
+    /// Both prices have to be published within `max_publish_delay` seconds of the
+    /// bet's start and end time.
+    pub fn validate_publish_times(
+        &self,
+        bet: &Bet,
+        open: &OraclePrice,
+        close: &OraclePrice,
+    ) -> Result<()> {
+        let max_publish_delay = self.max_publish_delay as u64;
+        if open.publish_time.abs_diff(bet.start_time) > max_publish_delay
+            || close.publish_time.abs_diff(bet.end_time) > max_publish_delay
+        {
+            return Err(error!(GameError::StalePrice));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that `user` is a leaf of the allowlist Merkle tree when gating is on.
+    /// Leaves are `keccak(user)` and each level hashes the sorted pair of nodes.
+    pub fn validate_allowlist(&self, user: &Pubkey, proof: &[[u8; 32]]) -> Result<()> {
+        if self.allowlist_root == [0u8; 32] {
+            return Ok(());
+        }
+
+        let mut node = keccak::hash(user.as_ref()).to_bytes();
+        for sibling in proof {
+            node = if node <= *sibling {
+                keccak::hashv(&[&node, sibling]).to_bytes()
+            } else {
+                keccak::hashv(&[sibling, &node]).to_bytes()
+            };
+        }
+
+        if node != self.allowlist_root {
+            return Err(error!(GameError::NotAllowlisted));
+        }
+
+        Ok(())
+    }
+}
+
+impl Subscription {
+    /// Returns the next round of the subscription, staked from an escrow holding
+    /// `escrow_balance`, along with its event. Its payout is locked in `state` and
+    /// it takes a slot on the subscriber's profile. The caller moves the stake to
+    /// the game escrow and sets the round's bump, subscription and user profile.
+    pub fn open_round(
+        &mut self,
+        config: &GameConfig,
+        state: &mut GameState,
+        user_profile: &mut UserProfile,
+        escrow_balance: u64,
+        current_timestamp: u64,
+    ) -> Result<(Bet, BetPlaced)> {
+        if self.has_active_bet {
+            return Err(error!(GameError::ActiveSubscriptionRound));
+        }
+
+        if self.rounds_played >= self.max_rounds {
+            return Err(error!(GameError::InvalidRounds));
+        }
+
+        // Compounded stakes are capped by the max bet so the subscription can keep
+        // rolling after a streak of wins.
+        let amount = self.next_stake.min(config.max_bet);
+        config.validate_bet(amount, self.interval)?;
+
+        if escrow_balance < amount {
+            return Err(error!(GameError::InsufficientSubscriptionBalance));
+        }
+
+        let start_time = current_timestamp
+            .checked_add(config.min_entry_delay as u64)
+            .ok_or(error!(GameError::MathOverflow))?;
+        let end_time = start_time
+            .checked_add(self.interval as u64)
+            .ok_or(error!(GameError::MathOverflow))?;
+
+        let bet_id = state.next_bet_id;
+        let available_liquidity = config
+            .max_utilized_liquidity
+            .checked_sub(state.locked_liquidity)
+            .ok_or(error!(GameError::InsufficientLiquidity))?;
+        let payout = amount
+            .checked_mul(config.leverage as u64)
+            .ok_or(error!(GameError::MathOverflow))?
+            / 1000;
+
+        if payout > available_liquidity {
+            return Err(error!(GameError::InsufficientLiquidity));
+        }
+
+        user_profile.open_bet(config, current_timestamp)?;
+
+        state.next_bet_id = state
+            .next_bet_id
+            .checked_add(1)
+            .ok_or(error!(GameError::MathOverflow))?;
+        state.locked_liquidity = state
+            .locked_liquidity
+            .checked_add(payout)
+            .ok_or(error!(GameError::MathOverflow))?;
+
+        self.rounds_played = self
+            .rounds_played
+            .checked_add(1)
+            .ok_or(error!(GameError::MathOverflow))?;
+        self.active_bet_id = bet_id;
+        self.has_active_bet = true;
+
+        let mut bet = Bet::zeroed();
+        bet.version = BET_VERSION;
+        bet.bet_id = bet_id;
+        bet.amount = amount;
+        bet.payout = payout;
+        bet.start_time = start_time;
+        bet.end_time = end_time;
+        bet.user = self.user;
+        bet.user_token_account = self.subscription_escrow;
+        bet.pair = self.pair;
+        bet.is_long = self.is_long;
+        bet.active = true;
+
+        let event = BetPlaced {
+            bet_id,
+            user: self.user,
+            amount,
+            pair: self.pair,
+            interval: self.interval,
+            is_long: self.is_long,
+            placed_time: current_timestamp,
+            start_time,
+        };
+
+        Ok((bet, event))
+    }
+}
+
+impl UserProfile {
+    /// Takes an open bet slot for a bet placed at `current_timestamp`. Each wallet
+    /// is rate limited so bots can't stack tiny concurrent bets against oracle
+    /// latency. The slot is released on settle and cancel.
+    pub fn open_bet(&mut self, config: &GameConfig, current_timestamp: u64) -> Result<()> {
+        let cooldown_end = self.last_bet_time.saturating_add(config.bet_cooldown as u64);
+        if current_timestamp < cooldown_end {
+            return Err(error!(GameError::BetCooldown));
+        }
+
+        if config.max_open_bets > 0 && self.open_bets >= config.max_open_bets {
+            return Err(error!(GameError::TooManyOpenBets));
+        }
+
+        self.last_bet_time = current_timestamp;
+        self.open_bets = self
+            .open_bets
+            .checked_add(1)
+            .ok_or(error!(GameError::MathOverflow))?;
+
+        Ok(())
+    }
+}
+
+impl GameState {
+    /// Everything the escrow owes to open bets, position holders and referrers.
+    pub fn owed(&self) -> Result<u64> {
+        self.locked_liquidity
+            .checked_add(self.unclaimed_referral_rewards)
+            .and_then(|owed| owed.checked_add(self.unclaimed_payouts))
+            .ok_or(error!(GameError::MathOverflow))
+    }
+
+    /// Fails unless `escrow_balance` covers everything the game owes.
+    pub fn check_solvency(&self, escrow_balance: u64) -> Result<()> {
+        if escrow_balance < self.owed()? {
+            return Err(error!(GameError::Insolvent));
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads an account as laid out before game IDs and versioning, checking its
+/// discriminator and size.
+fn read_legacy_account<T: bytemuck::Pod>(
+    account: &AccountInfo,
+    discriminator: [u8; 8],
+) -> Result<T> {
+    let data = account.try_borrow_data()?;
+    if data.len() != 8 + std::mem::size_of::<T>() || data[..8] != discriminator {
+        return Err(error!(GameError::InvalidAccountVersion));
+    }
+
+    Ok(bytemuck::pod_read_unaligned(&data[8..]))
+}
+
+/// Closes an account a legacy migration moved, refunding its rent to `destination`.
+fn close_legacy_account(account: &AccountInfo, destination: &AccountInfo) -> Result<()> {
+    let lamports = account.lamports();
+    **destination.try_borrow_mut_lamports()? = destination
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(error!(GameError::MathOverflow))?;
+    **account.try_borrow_mut_lamports()? = 0;
+
+    account.assign(&anchor_lang::system_program::ID);
+    account.realloc(0, false)?;
+
+    Ok(())
+}
+
+/// Seconds from `time` to `publish_time`, which `validate_publish_times` bounds
+/// by `max_publish_delay`.
+fn publish_offset(publish_time: u64, time: u64) -> Result<i16> {
+    let offset = if publish_time >= time {
+        i64::try_from(publish_time - time)
+    } else {
+        i64::try_from(time - publish_time).map(|offset| -offset)
+    };
+
+    offset
+        .ok()
+        .and_then(|offset| i16::try_from(offset).ok())
+        .ok_or(error!(GameError::StalePrice))
+}
+
+/// Reads a token account that may have been closed or replaced.
+fn read_token_account(account: &AccountInfo) -> Option<TokenAccount> {
+    if *account.owner != anchor_spl::token::ID {
+        return None;
+    }
+
+    TokenAccount::try_deserialize(&mut &account.try_borrow_data().ok()?[..]).ok()
+}
+
+/// Returns whether `holder` owns the position token of `bet`.
+fn holds_position(
+    position_token_account: Option<&TokenAccount>,
+    bet: &Bet,
+    holder: Pubkey,
+) -> bool {
+    match position_token_account {
+        Some(position_token_account) => {
+            position_token_account.mint == bet.position_mint
+                && position_token_account.owner == holder
+                && position_token_account.amount == 1
+        }
+        None => false,
+    }
+}
+
+impl Tournament {
+    /// Inserts a score into the leaderboard, which is kept sorted from best to worst
+    /// and capped at `MAX_TOURNAMENT_WINNERS`. Ties keep the earlier submission first.
+    pub fn rank(&mut self, user: Pubkey, pnl: i64) {
+        let num_ranked = self.num_ranked as usize;
+        let mut ranked_users = self.ranked_users;
+        let mut ranked_pnl = self.ranked_pnl;
+
+        let position = ranked_pnl[..num_ranked]
+            .iter()
+            .position(|ranked| pnl > *ranked)
+            .unwrap_or(num_ranked);
+        if position >= MAX_TOURNAMENT_WINNERS {
+            return;
+        }
+
+        let last = num_ranked.min(MAX_TOURNAMENT_WINNERS - 1);
+        for i in (position..last).rev() {
+            ranked_users[i + 1] = ranked_users[i];
+            ranked_pnl[i + 1] = ranked_pnl[i];
+        }
+        ranked_users[position] = user;
+        ranked_pnl[position] = pnl;
+
+        self.ranked_users = ranked_users;
+        self.ranked_pnl = ranked_pnl;
+        self.num_ranked = (num_ranked + 1).min(MAX_TOURNAMENT_WINNERS) as u32;
+    }
+}
+
+/// Adds the result of a settled or cancelled tournament bet to its entry score.
+fn record_tournament_result(
+    tournament_entry: &Option<AccountLoader<TournamentEntry>>,
+    bet: &Bet,
+    pnl: i64,
+) -> Result<()> {
+    if bet.tournament_entry == Pubkey::default() {
+        return Ok(());
+    }
+
+    let tournament_entry = match tournament_entry {
+        Some(tournament_entry) if tournament_entry.key() == bet.tournament_entry => {
+            tournament_entry
+        }
+        _ => return Err(error!(GameError::InvalidTournament)),
+    };
+
+    let mut tournament_entry = tournament_entry.load_mut()?;
+    tournament_entry.pnl = tournament_entry
+        .pnl
+        .checked_add(pnl)
+        .ok_or(error!(GameError::MathOverflow))?;
+    tournament_entry.open_bets = tournament_entry
+        .open_bets
+        .checked_sub(1)
+        .ok_or(error!(GameError::MathUnderflow))?;
+
+    Ok(())
+}
+
+/// Credits the bettor's referrer its share of the `house_edge` kept on a lost
+/// bet. Won or refunded bets earn the referrer nothing.
+fn accrue_referral_reward(
+    user_profile: &Option<AccountLoader<UserProfile>>,
+    referrer: &Option<AccountLoader<Referrer>>,
+    bet: &Bet,
+    config: &GameConfig,
+    state: &mut GameState,
+    house_edge: u64,
+) -> Result<()> {
+    if bet.user_profile == Pubkey::default() {
+        return Ok(());
+    }
+
+    let referrer_key = match user_profile {
+        Some(user_profile) if user_profile.key() == bet.user_profile => {
+            user_profile.load()?.referrer
+        }
+        _ => return Err(error!(GameError::InvalidUserProfile)),
+    };
+    if referrer_key == Pubkey::default() {
+        return Ok(());
+    }
+
+    let referrer = match referrer {
+        Some(referrer) if referrer.key() == referrer_key => referrer,
+        _ => return Err(error!(GameError::InvalidReferrer)),
+    };
+
+    let reward = house_edge
+        .checked_mul(config.referral_share_bps as u64)
+        .ok_or(error!(GameError::MathOverflow))?
+        / MAX_BPS as u64;
+
+    let mut referrer = referrer.load_mut()?;
+    referrer.claimable = referrer
+        .claimable
+        .checked_add(reward)
+        .ok_or(error!(GameError::MathOverflow))?;
+    referrer.total_earned = referrer
+        .total_earned
+        .checked_add(reward)
+        .ok_or(error!(GameError::MathOverflow))?;
+    state.unclaimed_referral_rewards = state
+        .unclaimed_referral_rewards
+        .checked_add(reward)
+        .ok_or(error!(GameError::MathOverflow))?;
+
+    Ok(())
+}
+
+/// The result of a bet paying `payout` on its stake of `amount`.
+fn bet_pnl(payout: u64, amount: u64) -> Result<i64> {
+    i64::try_from(payout)
+        .ok()
+        .zip(i64::try_from(amount).ok())
+        .and_then(|(payout, amount)| payout.checked_sub(amount))
+        .ok_or(error!(GameError::MathOverflow))
+}
+
+/// Counts a held payout on the bet's subscription and tournament entry once it is
+/// claimed, or the refund instead once the settlement was `disputed`.
+fn resolve_held_payout(
+    subscription: &Option<AccountLoader<Subscription>>,
+    tournament_entry: &Option<AccountLoader<TournamentEntry>>,
+    bet: &Bet,
+    disputed: bool,
+) -> Result<()> {
+    if disputed {
+        release_subscription_round(subscription, bet, None)?;
+        record_tournament_result(tournament_entry, bet, 0)
+    } else {
+        release_subscription_round(subscription, bet, Some(bet.payout))?;
+        record_tournament_result(tournament_entry, bet, bet_pnl(bet.payout, bet.amount)?)
+    }
+}
+
+/// Releases the open bet slot a bet held on its bettor's profile.
+fn release_open_bet(user_profile: &Option<AccountLoader<UserProfile>>, bet: &Bet) -> Result<()> {
+    if bet.user_profile == Pubkey::default() {
+        return Ok(());
+    }
+
+    let user_profile = match user_profile {
+        Some(user_profile) if user_profile.key() == bet.user_profile => user_profile,
+        _ => return Err(error!(GameError::InvalidUserProfile)),
+    };
+
+    let mut user_profile = user_profile.load_mut()?;
+    user_profile.open_bets = user_profile
+        .open_bets
+        .checked_sub(1)
+        .ok_or(error!(GameError::MathUnderflow))?;
+
+    Ok(())
+}
+
+/// Frees the subscription for its next round once one of its bets is settled or
+/// cancelled. A won payout becomes the next stake when the subscription compounds,
+/// any other outcome resets it to the base stake.
+fn release_subscription_round(
+    subscription: &Option<AccountLoader<Subscription>>,
+    bet: &Bet,
+    won_payout: Option<u64>,
+) -> Result<()> {
+    if bet.subscription == Pubkey::default() {
+        return Ok(());
+    }
+
+    let subscription = match subscription {
+        Some(subscription) if subscription.key() == bet.subscription => subscription,
+        _ => return Err(error!(GameError::InvalidSubscription)),
+    };
+
+    let mut subscription = subscription.load_mut()?;
+    subscription.has_active_bet = false;
+    subscription.next_stake = match won_payout {
+        Some(payout) if subscription.compound => payout,
+        _ => subscription.stake,
+    };
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(game_id: u64)]
+pub struct Initialize<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init,
+        space = 8 + std::mem::size_of::< GameConfig > (),
+        payer = payer,
+        seeds = [GAME_CONFIG_SEED, game_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    #[account(
+        init,
+        space = 8 + std::mem::size_of::< GameState > (),
+        payer = payer,
+        seeds = [GAME_STATE_SEED, game_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub game_state: AccountLoader<'info, GameState>,
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = token_mint,
+        associated_token::authority = game_config,
+    )]
+    pub game_escrow: Account<'info, TokenAccount>,
+    /// CHECK: a token mint account
+    pub token_mint: Account<'info, Mint>,
+    /// CHECK: an account authorized to change the program config.
+    pub authority: AccountInfo<'info>,
+    #[account(constraint = switchboard_function.load() ?.requests_disabled == 0)]
+    pub switchboard_function: AccountLoader<'info, FunctionAccountData>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(amount: u64, pair: [u8; 8])]
+pub struct PlaceBet<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump,
+        has_one = game_escrow
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    #[account(
+        mut,
+        seeds = [GAME_STATE_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_state.load() ?.bump
+    )]
+    pub game_state: AccountLoader<'info, GameState>,
+    #[account(
+        seeds = [PAIR_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), pair.as_ref()],
+        bump = accepted_pair.load()?.bump
+    )]
+    pub accepted_pair: AccountLoader<'info, AcceptedPair>,
+    #[account(
+        init,
+        space = 8 + std::mem::size_of::< Bet > (),
+        payer = payer,
+        seeds = [
+            BET_SEED,
+            game_config.load()?.game_id.to_le_bytes().as_ref(),
+            game_state.load()?.next_bet_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub bet: AccountLoader<'info, Bet>,
+    /// CHECK: the wallet the bet is placed for, the payer itself unless a session is passed.
+    pub user: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [SESSION_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), user.key().as_ref(), payer.key().as_ref()],
+        bump = session.load()?.bump
+    )]
+    pub session: Option<AccountLoader<'info, Session>>,
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key() && user_token_account.mint == game_config.load()?.token_mint
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub game_escrow: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            POSITION_MINT_SEED,
+            game_config.load()?.game_id.to_le_bytes().as_ref(),
+            game_state.load()?.next_bet_id.to_le_bytes().as_ref()
+        ],
+        bump,
+        mint::decimals = 0,
+        mint::authority = game_config,
+    )]
+    pub position_mint: Box<Account<'info, Mint>>,
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = position_mint,
+        associated_token::authority = user,
+    )]
+    pub position_token_account: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [
+            VOUCHER_SEED,
+            game_config.load()?.game_id.to_le_bytes().as_ref(),
+            voucher.load()?.user.as_ref(),
+            voucher.load()?.voucher_id.to_le_bytes().as_ref()
+        ],
+        bump = voucher.load()?.bump
+    )]
+    pub voucher: Option<AccountLoader<'info, Voucher>>,
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::< UserProfile > (),
+        payer = payer,
+        seeds = [USER_PROFILE_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub user_profile: AccountLoader<'info, UserProfile>,
+    #[account(
+        mut,
+        seeds = [REFERRER_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), referrer.load()?.wallet.as_ref()],
+        bump = referrer.load()?.bump
+    )]
+    pub referrer: Option<AccountLoader<'info, Referrer>>,
+    #[account(
+        seeds = [
+            TOURNAMENT_SEED,
+            game_config.load()?.game_id.to_le_bytes().as_ref(),
+            tournament.load()?.tournament_id.to_le_bytes().as_ref()
+        ],
+        bump = tournament.load()?.bump
+    )]
+    pub tournament: Option<AccountLoader<'info, Tournament>>,
+    #[account(mut)]
+    pub tournament_entry: Option<AccountLoader<'info, TournamentEntry>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(bet_id: u64)]
+pub struct RequestBetExecution<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump,
+        has_one = switchboard_function
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    #[account(
+        seeds = [BET_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), bet_id.to_le_bytes().as_ref()],
+        bump = bet.load()?.bump,
+        has_one = switchboard_request
+    )]
+    pub bet: AccountLoader<'info, Bet>,
+
+    // SWITCHBOARD ACCOUNTS
+    /// CHECK: program ID checked.
+    #[account(executable, address = SWITCHBOARD_ATTESTATION_PROGRAM_ID)]
+    pub switchboard: AccountInfo<'info>,
+    /// CHECK: validated by Switchboard CPI
+    pub switchboard_state: AccountLoader<'info, AttestationProgramState>,
+    pub switchboard_attestation_queue: AccountLoader<'info, AttestationQueueAccountData>,
+    /// CHECK: validated by Switchboard CPI
+    #[account(mut)]
+    pub switchboard_function: AccountLoader<'info, FunctionAccountData>,
+    /// CHECK: validated by Switchboard CPI
+    #[account(mut)]
+    pub switchboard_request: AccountInfo<'info>,
+    /// CHECK: validated by Switchboard CPI
+    #[account(mut)]
+    pub switchboard_request_escrow: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(bet_id: u64)]
+pub struct BetSettlement<'info> {
+    #[account(
+        mut,
+        seeds = [BET_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), bet_id.to_le_bytes().as_ref()],
+        bump = bet.load()?.bump,
+        has_one = user_token_account
+    )]
+    pub bet: AccountLoader<'info, Bet>,
+    #[account(
+        mut,
+        seeds = [GAME_STATE_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_state.load() ?.bump
+    )]
+    pub game_state: AccountLoader<'info, GameState>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump,
+        has_one = game_escrow
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    /// CHECK: The bettor's token account, checked against the bet. A bettor may
+    /// close it, so it is only read when paying out.
+    #[account(mut)]
+    pub user_token_account: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub game_escrow: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    #[account(mut)]
+    pub subscription: Option<AccountLoader<'info, Subscription>>,
+    /// CHECK: The bettor's position token account, which may have been closed.
+    pub position_token_account: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub tournament_entry: Option<AccountLoader<'info, TournamentEntry>>,
+    #[account(mut)]
+    pub user_profile: Option<AccountLoader<'info, UserProfile>>,
+    #[account(
+        seeds = [PAIR_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), bet.load()?.pair.as_ref()],
+        bump = accepted_pair.load()?.bump
+    )]
+    pub accepted_pair: AccountLoader<'info, AcceptedPair>,
+    // Checked against the bettor's profile when a lost bet earns it a reward.
+    #[account(mut)]
+    pub referrer: Option<AccountLoader<'info, Referrer>>,
+    /// CHECK: The bet account of the next round of a subscription, created by the
+    /// settlement at the address of the next bet ID.
+    #[account(mut)]
+    pub next_bet: Option<UncheckedAccount<'info>>,
+    pub system_program: Option<Program<'info, System>>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(bet_id: u64)]
+pub struct SettleBet<'info> {
+    pub settlement: BetSettlement<'info>,
+    #[account(address = settlement.game_config.load()?.switchboard_function)]
+    pub switchboard_function: AccountLoader<'info, FunctionAccountData>,
+    pub switchboard_request: Option<Box<Account<'info, FunctionRequestAccountData>>>,
+    pub enclave_signer: Signer<'info>,
+    pub settlement_batch: Option<AccountLoader<'info, SettlementBatch>>,
+    pub switchboard_routine: Option<Box<Account<'info, FunctionRoutineAccountData>>>,
+}
+
+impl<'info> SettleBet<'info> {
+    /// The game's settlement routine may settle any bet. Otherwise a bet is
+    /// settled by its own request, or by a batch request covering the bucket
+    /// its end time falls in.
+    fn validate_request(&self) -> Result<()> {
+        let function = self.switchboard_function.load()?;
+        let enclave_signer = self.enclave_signer.to_account_info();
+
+        if let Some(switchboard_routine) = &self.switchboard_routine {
+            let config = self.settlement.game_config.load()?;
+            if switchboard_routine.key() != config.switchboard_routine
+                || !function.validate_routine(switchboard_routine, &enclave_signer)?
+            {
+                return Err(error!(GameError::Unauthorized));
+            }
+            return Ok(());
+        }
+
+        let switchboard_request = match &self.switchboard_request {
+            Some(switchboard_request) => switchboard_request,
+            None => return Err(error!(GameError::Unauthorized)),
+        };
+        if !function.validate_request(switchboard_request, &enclave_signer)? {
+            return Err(error!(GameError::Unauthorized));
+        }
+
+        let bet = self.settlement.bet.load()?;
+        if bet.switchboard_request == switchboard_request.key() {
+            return Ok(());
+        }
+
+        match &self.settlement_batch {
+            Some(settlement_batch) => {
+                let batch = settlement_batch.load()?;
+                let game_id_bytes = self.settlement.game_config.load()?.game_id.to_le_bytes();
+                let batch_key = Pubkey::create_program_address(
+                    &[
+                        SETTLEMENT_BATCH_SEED,
+                        game_id_bytes.as_ref(),
+                        switchboard_request.key().as_ref(),
+                        &[batch.bump],
+                    ],
+                    &id(),
+                )
+                .map_err(|_| error!(GameError::InvalidBatch))?;
+                let batch_bet_ids = batch.bet_ids;
+                if batch_key != settlement_batch.key()
+                    || batch.bucket != bet.end_time / SETTLEMENT_BUCKET
+                    || !batch_bet_ids[..batch.num_bets as usize].contains(&{ bet.bet_id })
+                {
+                    return Err(error!(GameError::InvalidBatch));
+                }
+                Ok(())
+            }
+            None => Err(error!(GameError::Unauthorized)),
+        }
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(bucket: u64)]
+pub struct RequestBatchSettlement<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump,
+        has_one = switchboard_function
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + std::mem::size_of::< SettlementBatch > (),
+        seeds = [
+            SETTLEMENT_BATCH_SEED,
+            game_config.load()?.game_id.to_le_bytes().as_ref(),
+            switchboard_request.key().as_ref()
+        ],
+        bump
+    )]
+    pub settlement_batch: AccountLoader<'info, SettlementBatch>,
+
+    // SWITCHBOARD ACCOUNTS
+    /// CHECK: program ID checked.
+    #[account(executable, address = SWITCHBOARD_ATTESTATION_PROGRAM_ID)]
+    pub switchboard: AccountInfo<'info>,
+    /// CHECK: validated by Switchboard CPI
+    #[account(
+        seeds = [STATE_SEED],
+        seeds::program = switchboard.key(),
+        bump = switchboard_state.load()?.bump,
+    )]
+    pub switchboard_state: AccountLoader<'info, AttestationProgramState>,
+    pub switchboard_attestation_queue: AccountLoader<'info, AttestationQueueAccountData>,
+    /// CHECK: validated by Switchboard CPI
+    #[account(mut)]
+    pub switchboard_function: AccountLoader<'info, FunctionAccountData>,
+    /// CHECK: validated by Switchboard CPI
+    #[account(
+        mut,
+        signer,
+        owner = system_program.key(),
+        constraint = switchboard_request.data_len() == 0 && switchboard_request.lamports() == 0
+    )]
+    pub switchboard_request: AccountInfo<'info>,
+    /// CHECK:
+    #[account(
+        mut,
+        owner = system_program.key(),
+        constraint = switchboard_request_escrow.data_len() == 0 && switchboard_request_escrow.lamports() == 0
+    )]
+    pub switchboard_request_escrow: AccountInfo<'info>,
+    #[account(address = anchor_spl::token::spl_token::native_mint::ID)]
+    pub switchboard_mint: Account<'info, Mint>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(bet_id: u64)]
+pub struct SettleBetWithPyth<'info> {
+    pub settlement: BetSettlement<'info>,
+    /// CHECK: a Pyth receiver price update, validated when it is decoded.
+    pub open_price_update: AccountInfo<'info>,
+    /// CHECK: a Pyth receiver price update, validated when it is decoded.
+    pub close_price_update: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseSettlementBatch<'info> {
+    #[account(mut, close = keeper, has_one = keeper)]
+    pub settlement_batch: AccountLoader<'info, SettlementBatch>,
+    /// CHECK: the keeper that paid for the batch, checked against it.
+    #[account(mut)]
+    pub keeper: UncheckedAccount<'info>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(bet_id: u64)]
+pub struct CancelBet<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [GAME_STATE_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_state.load() ?.bump
+    )]
+    pub game_state: AccountLoader<'info, GameState>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump,
+        has_one = game_escrow
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    #[account(
+        mut,
+        seeds = [BET_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), bet_id.to_le_bytes().as_ref()],
+        bump = bet.load()?.bump
+    )]
+    pub bet: AccountLoader<'info, Bet>,
+    // Owned by the payer, or the subscription escrow of a subscription round.
+    #[account(
+        mut,
+        constraint = user_token_account.mint == game_config.load()?.token_mint
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub game_escrow: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    #[account(mut)]
+    pub subscription: Option<AccountLoader<'info, Subscription>>,
+    #[account(mut, address = bet.load()?.position_mint)]
+    pub position_mint: Option<Box<Account<'info, Mint>>>,
+    #[account(mut)]
+    pub position_token_account: Option<Box<Account<'info, TokenAccount>>>,
+    #[account(mut)]
+    pub tournament_entry: Option<AccountLoader<'info, TournamentEntry>>,
+    #[account(mut)]
+    pub user_profile: Option<AccountLoader<'info, UserProfile>>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(bet_id: u64)]
+pub struct ClaimPosition<'info> {
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump,
+        has_one = game_escrow
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    #[account(
+        mut,
+        seeds = [GAME_STATE_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_state.load()?.bump
+    )]
+    pub game_state: AccountLoader<'info, GameState>,
+    #[account(
+        mut,
+        seeds = [BET_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), bet_id.to_le_bytes().as_ref()],
+        bump = bet.load()?.bump,
+        has_one = position_mint
+    )]
+    pub bet: AccountLoader<'info, Bet>,
+    #[account(mut)]
+    pub position_mint: Box<Account<'info, Mint>>,
+    #[account(
+        mut,
+        constraint = position_token_account.owner == payer.key() && position_token_account.mint == position_mint.key()
+    )]
+    pub position_token_account: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = user_token_account.owner == payer.key() && user_token_account.mint == game_config.load()?.token_mint
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub game_escrow: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    #[account(mut)]
+    pub subscription: Option<AccountLoader<'info, Subscription>>,
+    #[account(mut)]
+    pub tournament_entry: Option<AccountLoader<'info, TournamentEntry>>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(bet_id: u64)]
+pub struct ClaimPayout<'info> {
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump,
+        has_one = game_escrow
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    #[account(
+        mut,
+        seeds = [GAME_STATE_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_state.load()?.bump
+    )]
+    pub game_state: AccountLoader<'info, GameState>,
+    #[account(
+        mut,
+        seeds = [BET_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), bet_id.to_le_bytes().as_ref()],
+        bump = bet.load()?.bump,
+        has_one = user_token_account,
+        constraint = bet.load()?.position_mint == Pubkey::default() @ GameError::InvalidPosition
+    )]
+    pub bet: AccountLoader<'info, Bet>,
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub game_escrow: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    #[account(mut)]
+    pub subscription: Option<AccountLoader<'info, Subscription>>,
+    #[account(mut)]
+    pub tournament_entry: Option<AccountLoader<'info, TournamentEntry>>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(bet_id: u64)]
+pub struct DisputeSettlement<'info> {
+    #[account(
+        constraint = payer.key() == game_config.load()?.authority @ GameError::Unauthorized
+    )]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    #[account(
+        mut,
+        seeds = [GAME_STATE_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_state.load()?.bump
+    )]
+    pub game_state: AccountLoader<'info, GameState>,
+    #[account(
+        mut,
+        seeds = [BET_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), bet_id.to_le_bytes().as_ref()],
+        bump = bet.load()?.bump
+    )]
+    pub bet: AccountLoader<'info, Bet>,
+    #[account(mut)]
+    pub subscription: Option<AccountLoader<'info, Subscription>>,
+    #[account(mut)]
+    pub tournament_entry: Option<AccountLoader<'info, TournamentEntry>>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(subscription_id: u64, deposit: u64, stake: u64, pair: [u8; 8])]
+pub struct CreateSubscription<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump,
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    #[account(
+        seeds = [PAIR_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), pair.as_ref()],
+        bump = accepted_pair.load()?.bump
+    )]
+    pub accepted_pair: AccountLoader<'info, AcceptedPair>,
+    #[account(
+        init,
+        space = 8 + std::mem::size_of::< Subscription > (),
+        payer = payer,
+        seeds = [
+            SUBSCRIPTION_SEED,
+            game_config.load()?.game_id.to_le_bytes().as_ref(),
+            payer.key().as_ref(),
+            subscription_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub subscription: AccountLoader<'info, Subscription>,
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = token_mint,
+        associated_token::authority = subscription,
+    )]
+    pub subscription_escrow: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = user_token_account.owner == payer.key() && user_token_account.mint == token_mint.key()
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(address = game_config.load()?.token_mint)]
+    pub token_mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct OpenSubscriptionRound<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump,
+        has_one = game_escrow
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    #[account(
+        mut,
+        seeds = [GAME_STATE_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_state.load() ?.bump
+    )]
+    pub game_state: AccountLoader<'info, GameState>,
+    #[account(
+        mut,
+        seeds = [
+            SUBSCRIPTION_SEED,
+            game_config.load()?.game_id.to_le_bytes().as_ref(),
+            subscription.load()?.user.as_ref(),
+            subscription.load()?.subscription_id.to_le_bytes().as_ref()
+        ],
+        bump = subscription.load()?.bump,
+        has_one = subscription_escrow
+    )]
+    pub subscription: AccountLoader<'info, Subscription>,
+    #[account(mut)]
+    pub subscription_escrow: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [PAIR_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), subscription.load()?.pair.as_ref()],
+        bump = accepted_pair.load()?.bump
+    )]
+    pub accepted_pair: AccountLoader<'info, AcceptedPair>,
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::< UserProfile > (),
+        payer = payer,
+        seeds = [USER_PROFILE_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), subscription.load()?.user.as_ref()],
+        bump
+    )]
+    pub user_profile: AccountLoader<'info, UserProfile>,
+    #[account(
+        init,
+        space = 8 + std::mem::size_of::< Bet > (),
+        payer = payer,
+        seeds = [
+            BET_SEED,
+            game_config.load()?.game_id.to_le_bytes().as_ref(),
+            game_state.load()?.next_bet_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub bet: AccountLoader<'info, Bet>,
+    #[account(mut)]
+    pub game_escrow: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CancelSubscription<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        close = payer,
+        seeds = [
+            SUBSCRIPTION_SEED,
+            subscription.load()?.game_id.to_le_bytes().as_ref(),
+            payer.key().as_ref(),
+            subscription.load()?.subscription_id.to_le_bytes().as_ref()
+        ],
+        bump = subscription.load()?.bump,
+        has_one = subscription_escrow,
+        has_one = user_token_account
+    )]
+    pub subscription: AccountLoader<'info, Subscription>,
+    #[account(mut)]
+    pub subscription_escrow: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(tournament_id: u64)]
+pub struct CreateTournament<'info> {
+    #[account(
+        mut,
+        constraint = payer.key() == game_config.load()?.authority
+    )]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    #[account(
+        init,
+        space = 8 + std::mem::size_of::< Tournament > (),
+        payer = payer,
+        seeds = [TOURNAMENT_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), tournament_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub tournament: AccountLoader<'info, Tournament>,
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = token_mint,
+        associated_token::authority = tournament,
+    )]
+    pub tournament_escrow: Account<'info, TokenAccount>,
+    #[account(address = game_config.load()?.token_mint)]
+    pub token_mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct JoinTournament<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    #[account(
+        mut,
+        seeds = [
+            TOURNAMENT_SEED,
+            game_config.load()?.game_id.to_le_bytes().as_ref(),
+            tournament.load()?.tournament_id.to_le_bytes().as_ref()
+        ],
+        bump = tournament.load()?.bump,
+        has_one = tournament_escrow
+    )]
+    pub tournament: AccountLoader<'info, Tournament>,
+    #[account(
+        init,
+        space = 8 + std::mem::size_of::< TournamentEntry > (),
+        payer = payer,
+        seeds = [TOURNAMENT_ENTRY_SEED, tournament.key().as_ref(), payer.key().as_ref()],
+        bump
+    )]
+    pub tournament_entry: AccountLoader<'info, TournamentEntry>,
+    #[account(mut)]
+    pub tournament_escrow: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = user_token_account.owner == payer.key() && user_token_account.mint == game_config.load()?.token_mint
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RankTournamentEntry<'info> {
+    #[account(
+        mut,
+        seeds = [
+            TOURNAMENT_SEED,
+            tournament.load()?.game_id.to_le_bytes().as_ref(),
+            tournament.load()?.tournament_id.to_le_bytes().as_ref()
+        ],
+        bump = tournament.load()?.bump
+    )]
+    pub tournament: AccountLoader<'info, Tournament>,
+    #[account(
+        mut,
+        constraint = tournament_entry.load()?.tournament == tournament.key()
+    )]
+    pub tournament_entry: AccountLoader<'info, TournamentEntry>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct FinalizeTournament<'info> {
+    #[account(
+        mut,
+        constraint = payer.key() == game_config.load()?.authority
+    )]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump,
+        has_one = game_escrow
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    #[account(
+        mut,
+        seeds = [
+            TOURNAMENT_SEED,
+            game_config.load()?.game_id.to_le_bytes().as_ref(),
+            tournament.load()?.tournament_id.to_le_bytes().as_ref()
+        ],
+        bump = tournament.load()?.bump,
+        has_one = tournament_escrow
+    )]
+    pub tournament: AccountLoader<'info, Tournament>,
+    #[account(mut)]
+    pub tournament_escrow: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub game_escrow: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClaimTournamentPrize<'info> {
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    #[account(
+        seeds = [
+            TOURNAMENT_SEED,
+            game_config.load()?.game_id.to_le_bytes().as_ref(),
+            tournament.load()?.tournament_id.to_le_bytes().as_ref()
+        ],
+        bump = tournament.load()?.bump,
+        has_one = tournament_escrow
+    )]
+    pub tournament: AccountLoader<'info, Tournament>,
+    #[account(
+        mut,
+        seeds = [TOURNAMENT_ENTRY_SEED, tournament.key().as_ref(), payer.key().as_ref()],
+        bump = tournament_entry.load()?.bump
+    )]
+    pub tournament_entry: AccountLoader<'info, TournamentEntry>,
+    #[account(mut)]
+    pub tournament_escrow: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = user_token_account.owner == payer.key() && user_token_account.mint == game_config.load()?.token_mint
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterReferrer<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    #[account(
+        init,
+        space = 8 + std::mem::size_of::< Referrer > (),
+        payer = payer,
+        seeds = [REFERRER_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), payer.key().as_ref()],
+        bump
+    )]
+    pub referrer: AccountLoader<'info, Referrer>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClaimReferralRewards<'info> {
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump,
+        has_one = game_escrow
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    #[account(
+        mut,
+        seeds = [GAME_STATE_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_state.load() ?.bump
+    )]
+    pub game_state: AccountLoader<'info, GameState>,
+    #[account(
+        mut,
+        seeds = [REFERRER_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), payer.key().as_ref()],
+        bump = referrer.load()?.bump
+    )]
+    pub referrer: AccountLoader<'info, Referrer>,
+    #[account(
+        mut,
+        constraint = user_token_account.owner == payer.key() && user_token_account.mint == game_config.load()?.token_mint
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub game_escrow: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(voucher_id: u64)]
+pub struct IssueVoucher<'info> {
+    #[account(
+        mut,
+        constraint = payer.key() == game_config.load()?.authority
+    )]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    #[account(
+        init,
+        space = 8 + std::mem::size_of::< Voucher > (),
+        payer = payer,
+        seeds = [
+            VOUCHER_SEED,
+            game_config.load()?.game_id.to_le_bytes().as_ref(),
+            user.key().as_ref(),
+            voucher_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub voucher: AccountLoader<'info, Voucher>,
+    /// CHECK: the wallet the voucher is issued to.
+    pub user: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RevokeVoucher<'info> {
+    #[account(
+        mut,
+        constraint = payer.key() == game_config.load()?.authority
+    )]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    #[account(
+        mut,
+        close = payer,
+        seeds = [
+            VOUCHER_SEED,
+            game_config.load()?.game_id.to_le_bytes().as_ref(),
+            voucher.load()?.user.as_ref(),
+            voucher.load()?.voucher_id.to_le_bytes().as_ref()
+        ],
+        bump = voucher.load()?.bump
+    )]
+    pub voucher: AccountLoader<'info, Voucher>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(session_key: Pubkey)]
+pub struct CreateSession<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    #[account(
+        init,
+        space = 8 + std::mem::size_of::< Session > (),
+        payer = payer,
+        seeds = [SESSION_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), payer.key().as_ref(), session_key.as_ref()],
+        bump
+    )]
+    pub session: AccountLoader<'info, Session>,
+    #[account(
+        mut,
+        constraint = user_token_account.owner == payer.key() && user_token_account.mint == game_config.load()?.token_mint
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
 
-        Ok(())
-    }
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RevokeSession<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        close = payer,
+        constraint = session.load()?.user == payer.key() @ GameError::Unauthorized
+    )]
+    pub session: AccountLoader<'info, Session>,
+    #[account(
+        mut,
+        constraint = user_token_account.owner == payer.key()
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
 }
 
+#[event_cpi]
 #[derive(Accounts)]
-pub struct Initialize<'info> {
+#[instruction(change_id: u64)]
+pub struct ProposeConfigChange<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
     #[account(
-        init,
-        space = 8 + std::mem::size_of::< GameConfig > (),
-        payer = payer,
-        seeds = [GAME_CONFIG_SEED],
-        bump
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump
     )]
     pub game_config: AccountLoader<'info, GameConfig>,
     #[account(
         init,
-        space = 8 + std::mem::size_of::< GameState > (),
+        space = 8 + ConfigChange::INIT_SPACE,
         payer = payer,
-        seeds = [GAME_STATE_SEED],
+        seeds = [CONFIG_CHANGE_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), change_id.to_le_bytes().as_ref()],
         bump
     )]
-    pub game_state: AccountLoader<'info, GameState>,
+    pub config_change: Box<Account<'info, ConfigChange>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CancelConfigChange<'info> {
     #[account(
-        init,
-        payer = payer,
-        associated_token::mint = token_mint,
-        associated_token::authority = game_config,
+        mut,
+        constraint = game_config.load()?.is_risk_manager(&payer.key()) @ GameError::Unauthorized
     )]
-    pub game_escrow: Account<'info, TokenAccount>,
-    /// CHECK: a token mint account
-    pub token_mint: Account<'info, Mint>,
-    /// CHECK: an account authorized to change the program config.
-    pub authority: AccountInfo<'info>,
-    #[account(constraint = switchboard_function.load() ?.requests_disabled == 0)]
-    pub switchboard_function: AccountLoader<'info, FunctionAccountData>,
-    pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
-    pub system_program: Program<'info, System>,
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    #[account(
+        mut,
+        close = payer,
+        seeds = [CONFIG_CHANGE_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), config_change.change_id.to_le_bytes().as_ref()],
+        bump = config_change.bump
+    )]
+    pub config_change: Box<Account<'info, ConfigChange>>,
 }
 
+#[event_cpi]
 #[derive(Accounts)]
-pub struct PlaceBet<'info> {
+pub struct ExecuteConfigChange<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
     #[account(
-        seeds = [GAME_CONFIG_SEED],
-        bump = game_config.load()?.bump,
-        has_one = game_escrow,
-        has_one = switchboard_function
+        mut,
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump
     )]
     pub game_config: AccountLoader<'info, GameConfig>,
     #[account(
         mut,
-        seeds = [GAME_STATE_SEED],
+        close = authority,
+        seeds = [CONFIG_CHANGE_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), config_change.change_id.to_le_bytes().as_ref()],
+        bump = config_change.bump
+    )]
+    pub config_change: Box<Account<'info, ConfigChange>>,
+    #[account(
+        seeds = [GAME_STATE_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
         bump = game_state.load() ?.bump
     )]
     pub game_state: AccountLoader<'info, GameState>,
+    /// CHECK: the config authority, refunded the rent of closed accounts.
+    #[account(mut, address = game_config.load()?.authority)]
+    pub authority: AccountInfo<'info>,
     #[account(
         init,
-        space = 8 + std::mem::size_of::< Bet > (),
+        space = 8 + std::mem::size_of::< AcceptedPair > (),
         payer = payer,
-        seeds = [BET_SEED, game_state.load() ?.next_bet_id.to_le_bytes().as_ref()],
+        seeds = [PAIR_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), config_change.change.pair().as_ref()],
         bump
     )]
-    pub bet: AccountLoader<'info, Bet>,
+    pub new_pair: Option<AccountLoader<'info, AcceptedPair>>,
     #[account(
         mut,
-        constraint = user_token_account.owner == payer.key() && user_token_account.mint == game_config.load()?.token_mint
-    )]
-    pub user_token_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub game_escrow: Account<'info, TokenAccount>,
-
-    // SWITCHBOARD ACCOUNTS
-    /// CHECK: program ID checked.
-    #[account(executable, address = SWITCHBOARD_ATTESTATION_PROGRAM_ID)]
-    pub switchboard: AccountInfo<'info>,
-    /// CHECK: validated by Switchboard CPI
-    #[account(
-        seeds = [STATE_SEED],
-        seeds::program = switchboard.key(),
-        bump = switchboard_state.load()?.bump,
+        seeds = [PAIR_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), config_change.change.pair().as_ref()],
+        bump = removed_pair.load()?.bump
     )]
-    pub switchboard_state: AccountLoader<'info, AttestationProgramState>,
-    pub switchboard_attestation_queue: AccountLoader<'info, AttestationQueueAccountData>,
-    /// CHECK: validated by Switchboard CPI
-    #[account(mut)]
-    pub switchboard_function: AccountLoader<'info, FunctionAccountData>,
-    /// CHECK: validated by Switchboard CPI
+    pub removed_pair: Option<AccountLoader<'info, AcceptedPair>>,
     #[account(
         mut,
-        signer,
-        owner = system_program.key(),
-        constraint = switchboard_request.data_len() == 0 && switchboard_request.lamports() == 0
+        seeds = [PAIR_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), config_change.change.pair().as_ref()],
+        bump = updated_pair.load()?.bump
     )]
-    pub switchboard_request: AccountInfo<'info>,
-    /// CHECK:
+    pub updated_pair: Option<AccountLoader<'info, AcceptedPair>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateConfig<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    // May predate the current layout, so it is only loaded after the realloc.
     #[account(
         mut,
-        owner = system_program.key(),
-        constraint = switchboard_request_escrow.data_len() == 0 && switchboard_request_escrow.lamports() == 0
+        realloc = 8 + std::mem::size_of::< GameConfig > (),
+        realloc::payer = payer,
+        realloc::zero = true
     )]
-    pub switchboard_request_escrow: AccountInfo<'info>,
-    #[account(address = anchor_spl::token::spl_token::native_mint::ID)]
-    pub switchboard_mint: Account<'info, Mint>,
-
-    pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub game_config: AccountLoader<'info, GameConfig>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
 #[instruction(bet_id: u64)]
-pub struct RequestBetExecution<'info> {
+pub struct MigrateBet<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
     #[account(
-        seeds = [GAME_CONFIG_SEED],
-        bump = game_config.load()?.bump,
-        has_one = switchboard_function
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump
     )]
     pub game_config: AccountLoader<'info, GameConfig>,
+    // May predate the current layout, so it is only loaded after the realloc.
     #[account(
-        seeds = [BET_SEED, bet_id.to_le_bytes().as_ref()],
-        bump = bet.load()?.bump,
-        has_one = switchboard_request
+        mut,
+        realloc = 8 + std::mem::size_of::< Bet > (),
+        realloc::payer = payer,
+        realloc::zero = true
     )]
     pub bet: AccountLoader<'info, Bet>,
+    pub system_program: Program<'info, System>,
+}
 
-    // SWITCHBOARD ACCOUNTS
-    /// CHECK: program ID checked.
-    #[account(executable, address = SWITCHBOARD_ATTESTATION_PROGRAM_ID)]
-    pub switchboard: AccountInfo<'info>,
-    /// CHECK: validated by Switchboard CPI
-    pub switchboard_state: AccountLoader<'info, AttestationProgramState>,
-    pub switchboard_attestation_queue: AccountLoader<'info, AttestationQueueAccountData>,
-    /// CHECK: validated by Switchboard CPI
-    #[account(mut)]
-    pub switchboard_function: AccountLoader<'info, FunctionAccountData>,
-    /// CHECK: validated by Switchboard CPI
-    #[account(mut)]
-    pub switchboard_request: AccountInfo<'info>,
-    /// CHECK: validated by Switchboard CPI
+#[derive(Accounts)]
+#[instruction(pair: [u8; 8])]
+pub struct MigratePair<'info> {
     #[account(mut)]
-    pub switchboard_request_escrow: AccountInfo<'info>,
-
-    pub token_program: Program<'info, Token>,
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    // May predate the current layout, so it is only loaded after the realloc.
+    #[account(
+        mut,
+        realloc = 8 + std::mem::size_of::< AcceptedPair > (),
+        realloc::payer = payer,
+        realloc::zero = true
+    )]
+    pub accepted_pair: AccountLoader<'info, AcceptedPair>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(bet_id: u64)]
-pub struct SettleBet<'info> {
+pub struct MigrateState<'info> {
     #[account(
         mut,
-        seeds = [BET_SEED, bet_id.to_le_bytes().as_ref()],
-        bump = bet.load()?.bump,
-        has_one = switchboard_request,
-        has_one = user_token_account
+        constraint = payer.key() == game_config.load()?.authority
     )]
-    pub bet: AccountLoader<'info, Bet>,
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
     #[account(
         mut,
-        seeds = [GAME_STATE_SEED],
-        bump = game_state.load() ?.bump
+        realloc = 8 + std::mem::size_of::< GameState > (),
+        realloc::payer = payer,
+        realloc::zero = true
     )]
     pub game_state: AccountLoader<'info, GameState>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(game_id: u64)]
+pub struct MigrateLegacyGame<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: The config from before game IDs, read as `LegacyGameConfig` and
+    /// closed by the migration.
+    #[account(mut, seeds = [GAME_CONFIG_SEED], bump, owner = id())]
+    pub legacy_config: UncheckedAccount<'info>,
+    /// CHECK: The state from before game IDs, read as `LegacyGameState` and
+    /// closed by the migration.
+    #[account(mut, seeds = [GAME_STATE_SEED], bump, owner = id())]
+    pub legacy_state: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub legacy_escrow: Account<'info, TokenAccount>,
     #[account(
-        seeds = [GAME_CONFIG_SEED],
-        bump = game_config.load()?.bump,
-        has_one = game_escrow,
-        has_one = switchboard_function
+        init,
+        space = 8 + std::mem::size_of::< GameConfig > (),
+        payer = payer,
+        seeds = [GAME_CONFIG_SEED, game_id.to_le_bytes().as_ref()],
+        bump
     )]
     pub game_config: AccountLoader<'info, GameConfig>,
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub game_escrow: Account<'info, TokenAccount>,
     #[account(
-        constraint = switchboard_function.load()?.validate_request(
-            &switchboard_request,
-            &enclave_signer.to_account_info()
-        )?
+        init,
+        space = 8 + std::mem::size_of::< GameState > (),
+        payer = payer,
+        seeds = [GAME_STATE_SEED, game_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub game_state: AccountLoader<'info, GameState>,
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = token_mint,
+        associated_token::authority = game_config,
     )]
-    pub switchboard_function: AccountLoader<'info, FunctionAccountData>,
-    pub switchboard_request: Box<Account<'info, FunctionRequestAccountData>>,
-    pub enclave_signer: Signer<'info>,
-
+    pub game_escrow: Account<'info, TokenAccount>,
+    pub token_mint: Account<'info, Mint>,
     pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
 #[instruction(bet_id: u64)]
-pub struct CancelBet<'info> {
+pub struct MigrateLegacyBet<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump,
+        constraint = game_config.load()?.legacy @ GameError::InvalidAccountVersion
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    /// CHECK: The bet from before game IDs, read as `LegacyBet` and closed by the
+    /// migration.
+    #[account(mut, seeds = [BET_SEED, bet_id.to_le_bytes().as_ref()], bump, owner = id())]
+    pub legacy_bet: UncheckedAccount<'info>,
+    #[account(
+        init,
+        space = 8 + std::mem::size_of::< Bet > (),
+        payer = payer,
+        seeds = [BET_SEED, game_config.load()?.game_id.to_le_bytes().as_ref(), bet_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub bet: AccountLoader<'info, Bet>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct PauseGame<'info> {
+    #[account(
+        constraint = game_config.load()?.is_pauser(&payer.key()) @ GameError::Unauthorized
+    )]
+    pub payer: Signer<'info>,
     #[account(
         mut,
-        seeds = [GAME_STATE_SEED],
-        bump = game_state.load() ?.bump
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump
     )]
-    pub game_state: AccountLoader<'info, GameState>,
+    pub game_config: AccountLoader<'info, GameConfig>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
     #[account(
-        seeds = [GAME_CONFIG_SEED],
+        constraint = game_config.load()?.is_treasurer(&payer.key()) @ GameError::Unauthorized
+    )]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
         bump = game_config.load()?.bump,
         has_one = game_escrow
     )]
     pub game_config: AccountLoader<'info, GameConfig>,
     #[account(
-        mut,
-        seeds = [BET_SEED, bet_id.to_le_bytes().as_ref()],
-        bump = bet.load()?.bump,
-        constraint = payer.key() == bet.load()?.user,
-        has_one = user_token_account
+        seeds = [GAME_STATE_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_state.load()?.bump
     )]
-    pub bet: AccountLoader<'info, Bet>,
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    pub game_state: AccountLoader<'info, GameState>,
     #[account(mut)]
     pub game_escrow: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = destination_token_account.mint == game_config.load()?.token_mint
+    )]
+    pub destination_token_account: Account<'info, TokenAccount>,
     pub token_program: Program<'info, Token>,
 }
 
+#[derive(Accounts)]
+pub struct CheckSolvency<'info> {
+    #[account(
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_config.load()?.bump,
+        has_one = game_escrow
+    )]
+    pub game_config: AccountLoader<'info, GameConfig>,
+    #[account(
+        seeds = [GAME_STATE_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
+        bump = game_state.load()?.bump
+    )]
+    pub game_state: AccountLoader<'info, GameState>,
+    pub game_escrow: Account<'info, TokenAccount>,
+}
+
+#[event_cpi]
 #[derive(Accounts)]
 pub struct ModifyConfig<'info> {
     #[account(
@@ -602,7 +4520,7 @@ pub struct ModifyConfig<'info> {
     pub payer: Signer<'info>,
     #[account(
         mut,
-        seeds = [GAME_CONFIG_SEED],
+        seeds = [GAME_CONFIG_SEED, game_config.load()?.game_id.to_le_bytes().as_ref()],
         bump = game_config.load()?.bump
     )]
     pub game_config: AccountLoader<'info, GameConfig>,
@@ -610,33 +4528,211 @@ pub struct ModifyConfig<'info> {
 }
 
 #[event]
-pub struct BetPlaced {
+pub struct BetPlaced {
+    pub bet_id: u64,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub pair: [u8; 8],
+    pub interval: u32,
+    pub is_long: bool,
+    pub placed_time: u64,
+    pub start_time: u64,
+}
+
+#[event]
+pub struct BetExecuted {
+    pub bet_id: u64,
+    pub user: Pubkey,
+    pub pair: [u8; 8],
+    pub is_long: bool,
+    pub amount: u64,
+    pub start_time: u64,
+    pub end_time: u64,
+    pub open_price: u64,
+    pub close_price: u64,
+    pub price_expo: i32,
+    pub won: bool,
+    pub payout: u64,
+    /// The house edge on the stake, of which referrers earn a share.
+    pub fee: u64,
+}
+
+#[event]
+pub struct BetRefunded {
     pub bet_id: u64,
     pub user: Pubkey,
+    pub pair: [u8; 8],
+    pub is_long: bool,
     pub amount: u64,
+    pub start_time: u64,
+    pub end_time: u64,
+    pub open_price: u64,
+    pub close_price: u64,
+    pub price_expo: i32,
+    pub refund: u64,
+}
+
+#[event]
+pub struct SettlementFailed {
+    pub bet_id: u64,
+    pub user: Pubkey,
     pub pair: [u8; 8],
-    pub interval: u32,
     pub is_long: bool,
+    pub amount: u64,
     pub start_time: u64,
+    pub end_time: u64,
+    pub reason: SettlementFailure,
+    pub refund: u64,
+}
+
+/// The event of a settled bet, returned by `BetSettlement::settle`.
+pub enum SettlementEvent {
+    Executed(BetExecuted),
+    Refunded(BetRefunded),
 }
 
 #[event]
-pub struct BetExecuted {
+pub struct BetCancelled {
     pub bet_id: u64,
     pub user: Pubkey,
-    pub won: bool,
+}
+
+#[event]
+pub struct PositionClaimed {
+    pub bet_id: u64,
+    pub holder: Pubkey,
     pub payout: u64,
 }
 
 #[event]
-pub struct BetCancelled {
+pub struct PayoutClaimed {
+    pub bet_id: u64,
+    pub user: Pubkey,
+    pub payout: u64,
+}
+
+#[event]
+pub struct SettlementDisputed {
     pub bet_id: u64,
+    pub refund: u64,
+}
+
+#[event]
+pub struct TournamentCreated {
+    pub tournament: Pubkey,
+    pub entry_fee: u64,
+    pub start_time: u64,
+    pub end_time: u64,
+}
+
+#[event]
+pub struct TournamentFinalized {
+    pub tournament: Pubkey,
+    pub prize_pool: u64,
+    pub num_winners: u32,
+}
+
+#[event]
+pub struct TournamentPrizeClaimed {
+    pub tournament: Pubkey,
+    pub user: Pubkey,
+    pub prize: u64,
+}
+
+#[event]
+pub struct ReferralRewardsClaimed {
+    pub referrer: Pubkey,
+    pub wallet: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct VoucherIssued {
+    pub voucher: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub expires_at: u64,
+    pub max_uses: u32,
+}
+
+#[event]
+pub struct VoucherRevoked {
+    pub voucher: Pubkey,
+    pub user: Pubkey,
+}
+
+#[event]
+pub struct SessionCreated {
+    pub session: Pubkey,
+    pub user: Pubkey,
+    pub session_key: Pubkey,
+    pub expires_at: u64,
+    pub spending_cap: u64,
+}
+
+#[event]
+pub struct SessionRevoked {
+    pub session: Pubkey,
+    pub user: Pubkey,
+}
+
+#[event]
+pub struct ConfigChangeProposed {
+    pub config_change: Pubkey,
+    pub change: ConfigChangeKind,
+    pub execute_after: u64,
+}
+
+#[event]
+pub struct ConfigChangeExecuted {
+    pub config_change: Pubkey,
+    pub change: ConfigChangeKind,
+}
+
+#[event]
+pub struct BatchSettlementRequested {
+    pub settlement_batch: Pubkey,
+    pub bucket: u64,
+    pub bet_ids: Vec<u64>,
+}
+
+#[event]
+pub struct GamePaused {
+    pub pauser: Pubkey,
+}
+
+#[event]
+pub struct GameUnpaused {}
+
+#[event]
+pub struct ConfigChangeCancelled {
+    pub config_change: Pubkey,
+}
+
+#[event]
+pub struct SubscriptionCreated {
+    pub subscription: Pubkey,
+    pub user: Pubkey,
+    pub deposit: u64,
+    pub stake: u64,
+    pub pair: [u8; 8],
+    pub interval: u32,
+    pub is_long: bool,
+    pub max_rounds: u32,
+    pub compound: bool,
+}
+
+#[event]
+pub struct SubscriptionCancelled {
+    pub subscription: Pubkey,
     pub user: Pubkey,
+    pub rounds_played: u32,
 }
 
 #[account(zero_copy(unsafe))]
 pub struct GameConfig {
     pub bump: u8,
+    pub game_id: u64,
     pub authority: Pubkey,
     pub min_bet: u64,
     pub max_bet: u64,
@@ -644,21 +4740,208 @@ pub struct GameConfig {
     pub cancel_buffer: u64,
     pub max_interval: u32,
     pub min_interval: u32,
+    pub min_entry_delay: u32,
+    pub max_entry_delay: u32,
+    pub bet_cooldown: u32,
+    pub max_open_bets: u32,
     pub leverage: u16,
+    pub referral_share_bps: u16,
     pub switchboard_function: Pubkey,
     pub token_mint: Pubkey,
     pub game_escrow: Pubkey,
-    pub num_pairs: u32,
-    pub accepted_pairs: [[u8; 8]; MAX_PAIRS],
+    pub allowlist_root: [u8; 32],
+    // Accounts created before versioning also predate game IDs, so they live at
+    // other addresses and are moved by migrate_legacy_game rather than migrated
+    // in place. Later fields are carved out of the reserved space; version 5
+    // outgrew it and grew the account, which migrate_config reallocs.
+    pub version: u8,
+    pub config_change_delay: u32,
+    pub paused: bool,
+    pub pauser: Pubkey,
+    pub risk_manager: Pubkey,
+    pub treasurer: Pubkey,
+    pub dispute_threshold: u64,
+    pub dispute_window: u32,
+    pub max_publish_delay: u32,
+    pub switchboard_routine: Pubkey,
+    // Set on the game migrate_legacy_game moved, whose old bets can be moved too.
+    pub legacy: bool,
+    pub reserved: [u8; 63],
 }
 
 #[account(zero_copy(unsafe))]
 pub struct GameState {
+    pub bump: u8,
+    pub game_id: u64,
+    pub locked_liquidity: u64,
+    pub next_bet_id: u64,
+    pub unclaimed_referral_rewards: u64,
+    pub version: u8,
+    pub unclaimed_payouts: u64,
+    pub reserved: [u8; 56],
+}
+
+/// `GameConfig` as laid out before game IDs and versioning, at the address
+/// seeded without a game ID.
+#[zero_copy]
+#[repr(C, packed)]
+pub struct LegacyGameConfig {
+    pub bump: u8,
+    pub authority: Pubkey,
+    pub min_bet: u64,
+    pub max_bet: u64,
+    pub max_utilized_liquidity: u64,
+    pub cancel_buffer: u64,
+    pub max_interval: u32,
+    pub min_interval: u32,
+    pub leverage: u16,
+    pub switchboard_function: Pubkey,
+    pub token_mint: Pubkey,
+    pub game_escrow: Pubkey,
+    pub num_pairs: u32,
+    pub accepted_pairs: [[u8; 8]; MAX_PAIRS],
+}
+
+/// `GameState` as laid out before game IDs and versioning.
+#[zero_copy]
+#[repr(C, packed)]
+pub struct LegacyGameState {
     pub bump: u8,
     pub locked_liquidity: u64,
     pub next_bet_id: u64,
 }
 
+/// `Bet` as laid out before game IDs and versioning.
+#[zero_copy]
+#[repr(C, packed)]
+pub struct LegacyBet {
+    pub bump: u8,
+    pub bet_id: u64,
+    pub amount: u64,
+    pub payout: u64,
+    pub start_time: u64,
+    pub end_time: u64,
+    pub open_price: u64,
+    pub close_price: u64,
+    pub user: Pubkey,
+    pub user_token_account: Pubkey,
+    pub pair: [u8; 8],
+    // Flags are read as bytes, so a corrupt one can't make an invalid bool.
+    pub is_long: u8,
+    pub active: u8,
+    pub switchboard_request: Pubkey,
+}
+
+/// Why the Switchboard function could not settle a bet.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub enum SettlementFailure {
+    PriceUnavailable,
+    PriceExpoMismatch,
+}
+
+/// An oracle price as read by the Switchboard function.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct OraclePrice {
+    pub price: u64,
+    pub conf: u64,
+    pub publish_time: u64,
+}
+
+/// Verification level of a Pyth price update, as posted by the Pyth receiver.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub enum VerificationLevel {
+    Partial { num_signatures: u8 },
+    Full,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PriceFeedMessage {
+    pub feed_id: [u8; 32],
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+    pub prev_publish_time: i64,
+    pub ema_price: i64,
+    pub ema_conf: u64,
+}
+
+/// The `PriceUpdateV2` account of the Pyth receiver program, decoded by hand so
+/// the program doesn't depend on the receiver SDK.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PriceUpdateV2 {
+    pub write_authority: Pubkey,
+    pub verification_level: VerificationLevel,
+    pub price_message: PriceFeedMessage,
+    pub posted_slot: u64,
+}
+
+impl PriceUpdateV2 {
+    pub fn try_from_account(account: &AccountInfo) -> Result<Self> {
+        if *account.owner != pyth_receiver::ID {
+            return Err(error!(GameError::InvalidPriceUpdate));
+        }
+
+        let data = account.try_borrow_data()?;
+        let discriminator = hash::hash(b"account:PriceUpdateV2").to_bytes();
+        if data.len() < 8 || data[..8] != discriminator[..8] {
+            return Err(error!(GameError::InvalidPriceUpdate));
+        }
+
+        let price_update = Self::deserialize(&mut &data[8..])
+            .map_err(|_| error!(GameError::InvalidPriceUpdate))?;
+        if price_update.verification_level != VerificationLevel::Full {
+            return Err(error!(GameError::InvalidPriceUpdate));
+        }
+
+        Ok(price_update)
+    }
+
+    /// The first price of the pair's feed published at or after `time`, i.e. the
+    /// update whose previous publish time precedes `time`, and at most
+    /// `max_publish_delay` seconds after it. Anyone may settle, so only this one
+    /// update is accepted; otherwise the settler could pick the best paying price
+    /// of the window.
+    pub fn to_oracle_price(
+        &self,
+        accepted_pair: &AcceptedPair,
+        time: u64,
+        max_publish_delay: u64,
+    ) -> Result<OraclePrice> {
+        let message = &self.price_message;
+        if message.feed_id != accepted_pair.feed_id {
+            return Err(error!(GameError::InvalidPriceUpdate));
+        }
+
+        let publish_time = u64::try_from(message.publish_time)
+            .map_err(|_| error!(GameError::InvalidPriceUpdate))?;
+        if publish_time < time || publish_time - time > max_publish_delay {
+            return Err(error!(GameError::StalePrice));
+        }
+
+        let prev_publish_time = u64::try_from(message.prev_publish_time)
+            .map_err(|_| error!(GameError::InvalidPriceUpdate))?;
+        if prev_publish_time >= time {
+            return Err(error!(GameError::NotFirstPriceUpdate));
+        }
+
+        Ok(OraclePrice {
+            price: u64::try_from(message.price).map_err(|_| error!(GameError::InvalidPrice))?,
+            conf: message.conf,
+            publish_time,
+        })
+    }
+}
+
+/// Returned by `check_solvency`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct Solvency {
+    pub escrow_balance: u64,
+    pub locked_liquidity: u64,
+    pub owed: u64,
+    pub free_liquidity: u64,
+}
+
 #[account(zero_copy(unsafe))]
 pub struct Bet {
     pub bump: u8,
@@ -675,6 +4958,270 @@ pub struct Bet {
     pub is_long: bool,
     pub active: bool,
     pub switchboard_request: Pubkey,
+    pub subscription: Pubkey,
+    pub position_mint: Pubkey,
+    pub claimable: u64,
+    pub voucher: Pubkey,
+    pub tournament_entry: Pubkey,
+    pub user_profile: Pubkey,
+    pub version: u8,
+    pub disputable_until: u64,
+    // Carved out of the reserved space so bets placed before settlements carried
+    // price details still load. Publish times are stored as seconds from the
+    // start and end time, bounded by `PUBLISH_DELAY_LIMIT`.
+    pub price_expo: i8,
+    pub open_conf: u64,
+    pub close_conf: u64,
+    pub open_publish_offset: i16,
+    pub close_publish_offset: i16,
+    pub position_redeemed: bool,
+    // Version 3 grew the bet so later fields fit, which migrate_bet reallocs.
+    pub reserved: [u8; 64],
+}
+
+/// Authority of a Switchboard request settling the `bet_ids` that ended in
+/// `bucket`, i.e. with `end_time / SETTLEMENT_BUCKET == bucket`.
+#[account(zero_copy(unsafe))]
+pub struct SettlementBatch {
+    pub bump: u8,
+    pub game_id: u64,
+    pub bucket: u64,
+    pub switchboard_request: Pubkey,
+    // Refunded the rent once the batch is closed.
+    pub keeper: Pubkey,
+    pub num_bets: u8,
+    pub bet_ids: [u64; MAX_BATCH_BETS],
+}
+
+impl Bet {
+    /// The edge the house keeps on the stake, of which referrers earn a share.
+    /// Voucher stakes are house money and carry none.
+    pub fn house_edge(&self) -> u64 {
+        if self.voucher != Pubkey::default() {
+            return 0;
+        }
+        self.amount.saturating_sub(self.payout / 2)
+    }
+}
+
+#[account(zero_copy(unsafe))]
+pub struct Subscription {
+    pub bump: u8,
+    pub game_id: u64,
+    pub subscription_id: u64,
+    pub user: Pubkey,
+    pub user_token_account: Pubkey,
+    pub subscription_escrow: Pubkey,
+    pub stake: u64,
+    pub next_stake: u64,
+    pub pair: [u8; 8],
+    pub interval: u32,
+    pub is_long: bool,
+    pub compound: bool,
+    pub max_rounds: u32,
+    pub rounds_played: u32,
+    pub active_bet_id: u64,
+    pub has_active_bet: bool,
+}
+
+#[account(zero_copy(unsafe))]
+pub struct UserProfile {
+    pub bump: u8,
+    pub user: Pubkey,
+    pub referrer: Pubkey,
+    pub last_bet_time: u64,
+    pub open_bets: u32,
+}
+
+#[account(zero_copy(unsafe))]
+pub struct Referrer {
+    pub bump: u8,
+    pub wallet: Pubkey,
+    pub claimable: u64,
+    pub total_earned: u64,
+    pub referred_volume: u64,
+}
+
+#[account(zero_copy(unsafe))]
+pub struct Tournament {
+    pub bump: u8,
+    pub game_id: u64,
+    pub tournament_id: u64,
+    pub tournament_escrow: Pubkey,
+    pub entry_fee: u64,
+    pub start_time: u64,
+    pub end_time: u64,
+    pub finalize_time: u64,
+    pub num_entrants: u32,
+    pub num_pairs: u32,
+    pub allowed_pairs: [[u8; 8]; MAX_PAIRS],
+    pub prize_bps: [u16; MAX_TOURNAMENT_WINNERS],
+    pub num_ranked: u32,
+    pub ranked_users: [Pubkey; MAX_TOURNAMENT_WINNERS],
+    pub ranked_pnl: [i64; MAX_TOURNAMENT_WINNERS],
+    pub finalized: bool,
+}
+
+#[account(zero_copy(unsafe))]
+pub struct TournamentEntry {
+    pub bump: u8,
+    pub tournament: Pubkey,
+    pub user: Pubkey,
+    pub pnl: i64,
+    pub open_bets: u32,
+    pub num_bets: u32,
+    pub ranked: bool,
+    pub prize: u64,
+}
+
+#[account(zero_copy(unsafe))]
+pub struct Voucher {
+    pub bump: u8,
+    pub voucher_id: u64,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub expires_at: u64,
+    pub max_uses: u32,
+    pub uses: u32,
+    pub num_pairs: u32,
+    pub allowed_pairs: [[u8; 8]; MAX_PAIRS],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, InitSpace)]
+pub enum ConfigChangeKind {
+    SetAmounts {
+        min_bet: u64,
+        max_bet: u64,
+        max_utilized_liquidity: u64,
+    },
+    AddPair {
+        pair: [u8; 8],
+        feed_id: [u8; 32],
+        max_move_bps: u32,
+        max_conf_bps: u32,
+    },
+    DeletePair {
+        pair: [u8; 8],
+    },
+    SetConfigChangeDelay {
+        delay: u32,
+    },
+    SetPriceBounds {
+        pair: [u8; 8],
+        max_move_bps: u32,
+        max_conf_bps: u32,
+    },
+    SetOracle {
+        pair: [u8; 8],
+        pull_oracle: bool,
+    },
+    SetSwitchboardRoutine {
+        switchboard_routine: Pubkey,
+    },
+    SetUserLimits {
+        bet_cooldown: u32,
+        max_open_bets: u32,
+    },
+    SetDisputeParams {
+        dispute_threshold: u64,
+        dispute_window: u32,
+    },
+    SetMaxPublishDelay {
+        max_publish_delay: u32,
+    },
+    SetEntryDelays {
+        min_entry_delay: u32,
+        max_entry_delay: u32,
+    },
+    SetReferralShare {
+        referral_share_bps: u16,
+    },
+    SetAllowlistRoot {
+        allowlist_root: [u8; 32],
+    },
+    SetRoles {
+        pauser: Pubkey,
+        risk_manager: Pubkey,
+        treasurer: Pubkey,
+    },
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct ConfigChange {
+    pub bump: u8,
+    pub game_id: u64,
+    pub change_id: u64,
+    pub execute_after: u64,
+    pub change: ConfigChangeKind,
+}
+
+#[account(zero_copy(unsafe))]
+pub struct AcceptedPair {
+    pub bump: u8,
+    pub game_id: u64,
+    pub pair: [u8; 8],
+    pub feed_id: [u8; 32],
+    pub max_move_bps: u32,
+    pub max_conf_bps: u32,
+    pub pull_oracle: bool,
+    pub disabled: bool,
+}
+
+impl AcceptedPair {
+    /// Rejects zero prices and, when the market has a bound, a move between open
+    /// and close larger than `max_move_bps` of the open price.
+    pub fn validate_prices(&self, open_price: u64, close_price: u64) -> Result<()> {
+        if open_price == 0 || close_price == 0 {
+            return Err(error!(GameError::InvalidPrice));
+        }
+
+        let max_move_bps = self.max_move_bps;
+        if max_move_bps == 0 {
+            return Ok(());
+        }
+
+        let price_move = open_price.abs_diff(close_price) as u128;
+        if price_move * MAX_BPS as u128 > open_price as u128 * max_move_bps as u128 {
+            return Err(error!(GameError::PriceMoveTooLarge));
+        }
+
+        Ok(())
+    }
+
+    /// Whether a result can't be told from oracle noise: either confidence is wider
+    /// than `max_conf_bps` of its price, or the move lies within both confidences.
+    /// Markets without a ratio never refund.
+    pub fn is_uncertain(&self, open: &OraclePrice, close: &OraclePrice) -> bool {
+        let max_conf_bps = self.max_conf_bps as u128;
+        if max_conf_bps == 0 {
+            return false;
+        }
+
+        let too_wide = |price: &OraclePrice| {
+            price.conf as u128 * MAX_BPS as u128 > price.price as u128 * max_conf_bps
+        };
+        let price_move = open.price.abs_diff(close.price) as u128;
+
+        too_wide(open) || too_wide(close) || price_move <= open.conf as u128 + close.conf as u128
+    }
+
+    /// Hex encoded feed ID, as passed to the Switchboard function.
+    pub fn feed_id_hex(&self) -> String {
+        self.feed_id.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+#[account(zero_copy(unsafe))]
+pub struct Session {
+    pub bump: u8,
+    pub user: Pubkey,
+    pub session_key: Pubkey,
+    pub expires_at: u64,
+    pub spending_cap: u64,
+    pub spent: u64,
+    pub num_pairs: u32,
+    pub allowed_pairs: [[u8; 8]; MAX_PAIRS],
 }
 
 #[error_code]
@@ -696,4 +5243,90 @@ pub enum GameError {
     InsufficientLiquidity,
     #[msg("Exceeded max amount of pairs to store")]
     MaxPairsExceeded,
+    #[msg("Bet start time is not within the permitted range")]
+    InvalidStartTime,
+    #[msg("Entry delay is not within the permitted range")]
+    InvalidEntryDelay,
+    #[msg("Subscription rounds are not within the permitted range")]
+    InvalidRounds,
+    #[msg("Subscription balance does not cover the stake")]
+    InsufficientSubscriptionBalance,
+    #[msg("The subscription round is still active")]
+    ActiveSubscriptionRound,
+    #[msg("Passed subscription does not match the bet")]
+    InvalidSubscription,
+    #[msg("The caller does not hold the bet position")]
+    InvalidPosition,
+    #[msg("The bet has no payout to claim")]
+    NothingToClaim,
+    #[msg("The voucher is expired, used up or not issued to the caller")]
+    InvalidVoucher,
+    #[msg("Passed referrer does not match the bettor profile")]
+    InvalidReferrer,
+    #[msg("Referral share is not within the permitted range")]
+    InvalidReferralShare,
+    #[msg("The tournament does not accept this action")]
+    InvalidTournament,
+    #[msg("Tournament prize shares are not within the permitted range")]
+    InvalidPrizeShares,
+    #[msg("The wallet is not on the allowlist")]
+    NotAllowlisted,
+    #[msg("The wallet placed a bet too recently")]
+    BetCooldown,
+    #[msg("The wallet has too many open bets")]
+    TooManyOpenBets,
+    #[msg("Passed user profile does not match the bet")]
+    InvalidUserProfile,
+    #[msg("The session is invalid or expired")]
+    InvalidSession,
+    #[msg("The session spending cap is exceeded")]
+    SessionCapExceeded,
+    #[msg("Pair tickers must be 8 upper case letters or digits")]
+    InvalidTicker,
+    #[msg("The oracle feed ID cannot be empty")]
+    InvalidFeedId,
+    #[msg("The account version is not supported")]
+    InvalidAccountVersion,
+    #[msg("The config change delay has not passed yet")]
+    ConfigChangeNotReady,
+    #[msg("Passed accounts do not match the config change")]
+    InvalidConfigChange,
+    #[msg("The game is paused")]
+    GamePaused,
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    #[msg("Arithmetic underflow")]
+    MathUnderflow,
+    #[msg("The liquidity limit cannot be below the locked liquidity")]
+    LiquidityBelowLocked,
+    #[msg("The escrow does not cover the liquidity owed")]
+    Insolvent,
+    #[msg("Settlement prices must be non-zero")]
+    InvalidPrice,
+    #[msg("The price moved more than the market allows")]
+    PriceMoveTooLarge,
+    #[msg("The settlement can still be disputed")]
+    SettlementDisputable,
+    #[msg("The settlement is not held for dispute")]
+    NotDisputable,
+    #[msg("Dispute window is not within the permitted range")]
+    InvalidDisputeWindow,
+    #[msg("The oracle price was not published close enough to the bet times")]
+    StalePrice,
+    #[msg("The publish delay must be between 1 and 32767 seconds")]
+    InvalidPublishDelay,
+    #[msg("The market settles through the other oracle")]
+    WrongOracle,
+    #[msg("The Pyth price update is invalid")]
+    InvalidPriceUpdate,
+    #[msg("The settlement batch is invalid")]
+    InvalidBatch,
+    #[msg("The price update is not the first one published at the bet time")]
+    NotFirstPriceUpdate,
+    #[msg("The pair was deleted and takes no new bets")]
+    PairDisabled,
+    #[msg("The token account already backs an active session")]
+    SessionActive,
+    #[msg("The batch still has unsettled bets")]
+    BatchNotSettled,
 }
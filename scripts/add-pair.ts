@@ -0,0 +1,59 @@
+import * as anchor from "@coral-xyz/anchor";
+import {Game} from '../target/types/game';
+import {CONFIG_CHANGE_SEED, formatValue, GAME_CONFIG_SEED, GAME_ID, PAIR_SEED, toLEBytesFromUInt64} from './utils'
+
+
+(async () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+
+    const program: anchor.Program<Game> = anchor.workspace.Game;
+
+    const payer = (provider.wallet as anchor.Wallet).payer;
+    console.log(`PAYER: ${payer.publicKey}`);
+
+    const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`CONFIG: ${gameConfigPubKey}`);
+
+    // 8 byte ticker padded with X, e.g. BTCUSDXX
+    const pair = Buffer.from(process.env.PAIR ?? "BTCUSDXX");
+    const [acceptedPairPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(PAIR_SEED), toLEBytesFromUInt64(GAME_ID), pair], program.programId
+    );
+    console.log(`PAIR: ${acceptedPairPubKey}`);
+
+    // Hex encoded Pyth feed ID the Switchboard function prices the pair with, BTC/USD by default
+    const feedID = Array.from(Buffer.from(
+        process.env.FEED_ID ?? "e62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43",
+        "hex",
+    ));
+
+    // Largest move between open and close a settlement may report, zero for no bound
+    const maxMoveBps = Number(process.env.MAX_MOVE_BPS ?? 1000);
+    // Widest confidence relative to the price before a bet is refunded, zero to never refund
+    const maxConfBps = Number(process.env.MAX_CONF_BPS ?? 10);
+
+    // The pair is registered by execute-config-change.ts once the config change delay passed
+    const changeID: number = Number(process.env.CHANGE_ID ?? Date.now());
+    const [configChangePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(CONFIG_CHANGE_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(changeID)],
+        program.programId
+    );
+    console.log(`CONFIG CHANGE: ${configChangePubKey}`);
+
+    const tx = await program.methods
+        .proposeConfigChange(new anchor.BN(changeID), {addPair: {pair: Array.from(pair), feedId: feedID, maxMoveBps, maxConfBps}})
+        .accounts({
+            payer: payer.publicKey,
+            gameConfig: gameConfigPubKey,
+            configChange: configChangePubKey,
+        })
+        .signers([payer])
+        .rpc();
+
+    console.log(`[TX] propose add pair: ${tx}`);
+    const configChange = await program.account.configChange.fetch(configChangePubKey)
+    console.log("Config change:", formatValue(configChange));
+})().then(() => console.log("Finished successfully")).catch(console.error)
@@ -4,8 +4,10 @@ import {
     BET_SEED,
     GAME_CONFIG_SEED,
     GAME_STATE_SEED,
+    POSITION_MINT_SEED,
     toLEBytesFromUInt64,
     formatValue,
+    GAME_ID,
 } from './utils'
 import {getAssociatedTokenAddress} from "@solana/spl-token";
 
@@ -19,12 +21,12 @@ import {getAssociatedTokenAddress} from "@solana/spl-token";
     console.log(`[env] PAYER: ${payer.publicKey}`);
 
     const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from(GAME_CONFIG_SEED)], program.programId
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
     );
     console.log(`CONFIG: ${gameConfigPubKey}`);
 
     const [gameStatePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from(GAME_STATE_SEED)], program.programId
+        [Buffer.from(GAME_STATE_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
     );
     console.log(`STATE: ${gameStatePubKey}`);
 
@@ -33,10 +35,17 @@ import {getAssociatedTokenAddress} from "@solana/spl-token";
     console.log(`BET ID: ${betID}`);
 
     const [betPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from(BET_SEED), toLEBytesFromUInt64(betID)], program.programId
+        [Buffer.from(BET_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(betID)], program.programId
     );
     console.log(`BET: ${betPubKey}`);
 
+    // The open bet slot is released on the profile of whoever placed the bet
+    const bet = await program.account.bet.fetch(betPubKey)
+    const userProfilePubKey = bet.userProfile.equals(anchor.web3.PublicKey.default)
+        ? null
+        : bet.userProfile;
+    console.log(`USER PROFILE: ${userProfilePubKey}`);
+
     console.log(`TOKEN: ${process.env.TOKEN}`);
     const token = new anchor.web3.PublicKey(process.env.TOKEN)
 
@@ -54,6 +63,18 @@ import {getAssociatedTokenAddress} from "@solana/spl-token";
     );
     console.log(`USER TOKEN ACCOUNT: ${userTokenAccountPubKey}`);
 
+    const [positionMintPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(POSITION_MINT_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(betID)], program.programId
+    );
+    console.log(`POSITION MINT: ${positionMintPubKey}`);
+
+    const positionTokenAccountPubKey = await getAssociatedTokenAddress(
+        positionMintPubKey,
+        payer.publicKey,
+        true,
+    );
+    console.log(`POSITION TOKEN ACCOUNT: ${positionTokenAccountPubKey}`);
+
     const tx = await program.methods
         .cancelBet(new anchor.BN(betID))
         .accounts({
@@ -63,6 +84,11 @@ import {getAssociatedTokenAddress} from "@solana/spl-token";
             bet: betPubKey,
             userTokenAccount: userTokenAccountPubKey,
             gameEscrow: gameEscrowPubKey,
+            subscription: null,
+            positionMint: positionMintPubKey,
+            positionTokenAccount: positionTokenAccountPubKey,
+            tournamentEntry: null,
+            userProfile: userProfilePubKey,
         })
         .rpc();
     console.log(`[TX] place bet: ${tx}`);
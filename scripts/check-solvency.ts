@@ -0,0 +1,35 @@
+import * as anchor from "@coral-xyz/anchor";
+import {Game} from '../target/types/game';
+import {formatValue, GAME_CONFIG_SEED, GAME_STATE_SEED, GAME_ID, toLEBytesFromUInt64} from './utils'
+
+
+(async () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+
+    const program: anchor.Program<Game> = anchor.workspace.Game;
+
+    const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`CONFIG: ${gameConfigPubKey}`);
+
+    const [gameStatePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_STATE_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`STATE: ${gameStatePubKey}`);
+
+    const config = await program.account.gameConfig.fetch(gameConfigPubKey)
+
+    // Read-only, so it is simulated rather than sent
+    const solvency = await program.methods
+        .checkSolvency()
+        .accounts({
+            gameConfig: gameConfigPubKey,
+            gameState: gameStatePubKey,
+            gameEscrow: config.gameEscrow,
+        })
+        .view();
+
+    console.log("Solvency:", formatValue(solvency));
+})().then(() => console.log("Finished successfully")).catch(console.error)
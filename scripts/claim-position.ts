@@ -0,0 +1,90 @@
+import * as anchor from "@coral-xyz/anchor";
+import {Game} from '../target/types/game';
+import {
+    BET_SEED,
+    GAME_CONFIG_SEED,
+    GAME_STATE_SEED,
+    POSITION_MINT_SEED,
+    toLEBytesFromUInt64,
+    formatValue,
+    GAME_ID,
+} from './utils'
+import {getAssociatedTokenAddress} from "@solana/spl-token";
+
+(async () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+
+    const program: anchor.Program<Game> = anchor.workspace.Game;
+
+    const payer = (provider.wallet as anchor.Wallet).payer;
+    console.log(`[env] PAYER: ${payer.publicKey}`);
+
+    const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`CONFIG: ${gameConfigPubKey}`);
+
+    const [gameStatePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_STATE_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`STATE: ${gameStatePubKey}`);
+
+    const betID: number = Number(process.env.BET_ID);
+    console.log(`BET ID: ${betID}`);
+
+    const [betPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(BET_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(betID)], program.programId
+    );
+    console.log(`BET: ${betPubKey}`);
+
+    console.log(`TOKEN: ${process.env.TOKEN}`);
+    const token = new anchor.web3.PublicKey(process.env.TOKEN)
+
+    const gameEscrowPubKey = await getAssociatedTokenAddress(
+        token,
+        gameConfigPubKey,
+        true,
+    );
+    console.log(`ESCROW: ${gameEscrowPubKey}`);
+
+    const userTokenAccountPubKey = await getAssociatedTokenAddress(
+        token,
+        payer.publicKey,
+        true,
+    );
+    console.log(`USER TOKEN ACCOUNT: ${userTokenAccountPubKey}`);
+
+    const [positionMintPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(POSITION_MINT_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(betID)], program.programId
+    );
+    console.log(`POSITION MINT: ${positionMintPubKey}`);
+
+    const positionTokenAccountPubKey = await getAssociatedTokenAddress(
+        positionMintPubKey,
+        payer.publicKey,
+        true,
+    );
+    console.log(`POSITION TOKEN ACCOUNT: ${positionTokenAccountPubKey}`);
+
+    const heldBet = await program.account.bet.fetch(betPubKey)
+    const tx = await program.methods
+        .claimPosition(new anchor.BN(betID))
+        .accounts({
+            payer: payer.publicKey,
+            gameConfig: gameConfigPubKey,
+            gameState: gameStatePubKey,
+            bet: betPubKey,
+            positionMint: positionMintPubKey,
+            positionTokenAccount: positionTokenAccountPubKey,
+            userTokenAccount: userTokenAccountPubKey,
+            gameEscrow: gameEscrowPubKey,
+            subscription: heldBet.subscription.equals(anchor.web3.PublicKey.default) ? null : heldBet.subscription,
+            tournamentEntry: heldBet.tournamentEntry.equals(anchor.web3.PublicKey.default) ? null : heldBet.tournamentEntry,
+        })
+        .rpc();
+    console.log(`[TX] claim position: ${tx}`);
+
+    const bet = await program.account.bet.fetch(betPubKey)
+    console.log("BET DATA:", formatValue(bet));
+})().then(() => console.log("Finished successfully")).catch(console.error)
@@ -0,0 +1,63 @@
+import * as anchor from "@coral-xyz/anchor";
+import {Game} from '../target/types/game';
+import {formatValue, GAME_CONFIG_SEED, GAME_STATE_SEED, REFERRER_SEED, GAME_ID, toLEBytesFromUInt64} from './utils'
+import {getAssociatedTokenAddress} from "@solana/spl-token";
+
+
+(async () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+
+    const program: anchor.Program<Game> = anchor.workspace.Game;
+
+    const payer = (provider.wallet as anchor.Wallet).payer;
+    console.log(`PAYER: ${payer.publicKey}`);
+
+    const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`CONFIG: ${gameConfigPubKey}`);
+
+    const [gameStatePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_STATE_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`STATE: ${gameStatePubKey}`);
+
+    const [referrerPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(REFERRER_SEED), toLEBytesFromUInt64(GAME_ID), payer.publicKey.toBuffer()], program.programId
+    );
+    console.log(`REFERRER: ${referrerPubKey}`);
+
+    console.log(`TOKEN: ${process.env.TOKEN}`);
+    const token = new anchor.web3.PublicKey(process.env.TOKEN)
+
+    const gameEscrowPubKey = await getAssociatedTokenAddress(
+        token,
+        gameConfigPubKey,
+        true,
+    );
+    console.log(`ESCROW: ${gameEscrowPubKey}`);
+
+    const userTokenAccountPubKey = await getAssociatedTokenAddress(
+        token,
+        payer.publicKey,
+        true,
+    );
+    console.log(`USER TOKEN ACCOUNT: ${userTokenAccountPubKey}`);
+
+    const tx = await program.methods
+        .claimReferralRewards()
+        .accounts({
+            payer: payer.publicKey,
+            gameConfig: gameConfigPubKey,
+            gameState: gameStatePubKey,
+            referrer: referrerPubKey,
+            userTokenAccount: userTokenAccountPubKey,
+            gameEscrow: gameEscrowPubKey,
+        })
+        .rpc();
+
+    console.log(`[TX] claim referral rewards: ${tx}`);
+    const referrer = await program.account.referrer.fetch(referrerPubKey)
+    console.log("Referrer:", formatValue(referrer));
+})().then(() => console.log("Finished successfully")).catch(console.error)
@@ -0,0 +1,72 @@
+import * as anchor from "@coral-xyz/anchor";
+import {Game} from '../target/types/game';
+import {
+    formatValue,
+    GAME_CONFIG_SEED,
+    toLEBytesFromUInt64,
+    TOURNAMENT_ENTRY_SEED,
+    TOURNAMENT_SEED,
+    GAME_ID,
+} from './utils'
+import {getAssociatedTokenAddress} from "@solana/spl-token";
+
+
+(async () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+
+    const program: anchor.Program<Game> = anchor.workspace.Game;
+
+    const payer = (provider.wallet as anchor.Wallet).payer;
+    console.log(`PAYER: ${payer.publicKey}`);
+
+    const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`CONFIG: ${gameConfigPubKey}`);
+
+    const tournamentID: number = Number(process.env.TOURNAMENT_ID ?? 0);
+    const [tournamentPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(TOURNAMENT_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(tournamentID)], program.programId
+    );
+    console.log(`TOURNAMENT: ${tournamentPubKey}`);
+
+    const [tournamentEntryPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(TOURNAMENT_ENTRY_SEED), tournamentPubKey.toBuffer(), payer.publicKey.toBuffer()],
+        program.programId
+    );
+    console.log(`TOURNAMENT ENTRY: ${tournamentEntryPubKey}`);
+
+    console.log(`TOKEN: ${process.env.TOKEN}`);
+    const token = new anchor.web3.PublicKey(process.env.TOKEN)
+
+    const tournamentEscrowPubKey = await getAssociatedTokenAddress(
+        token,
+        tournamentPubKey,
+        true,
+    );
+    console.log(`TOURNAMENT ESCROW: ${tournamentEscrowPubKey}`);
+
+    const userTokenAccountPubKey = await getAssociatedTokenAddress(
+        token,
+        payer.publicKey,
+        true,
+    );
+    console.log(`USER TOKEN ACCOUNT: ${userTokenAccountPubKey}`);
+
+    const tx = await program.methods
+        .claimTournamentPrize()
+        .accounts({
+            payer: payer.publicKey,
+            gameConfig: gameConfigPubKey,
+            tournament: tournamentPubKey,
+            tournamentEntry: tournamentEntryPubKey,
+            tournamentEscrow: tournamentEscrowPubKey,
+            userTokenAccount: userTokenAccountPubKey,
+        })
+        .rpc();
+
+    console.log(`[TX] claim tournament prize: ${tx}`);
+    const tournamentEntry = await program.account.tournamentEntry.fetch(tournamentEntryPubKey)
+    console.log("Tournament entry:", formatValue(tournamentEntry));
+})().then(() => console.log("Finished successfully")).catch(console.error)
@@ -0,0 +1,42 @@
+import * as anchor from "@coral-xyz/anchor";
+import {Game} from '../target/types/game';
+import {
+    BET_SEED,
+    toLEBytesFromUInt64,
+    GAME_ID,
+} from './utils'
+
+(async () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+    const program: anchor.Program<Game> = anchor.workspace.Game;
+
+    const payer = (provider.wallet as anchor.Wallet).payer;
+    console.log(`[env] PAYER: ${payer.publicKey}`);
+
+    // The function closes its batch after settling it; this closes one whose
+    // bets were settled or cancelled otherwise
+    console.log(`BATCH: ${process.env.BATCH}`);
+    const settlementBatchPubKey = new anchor.web3.PublicKey(process.env.BATCH);
+    const batch = await program.account.settlementBatch.fetch(settlementBatchPubKey);
+
+    const remainingAccounts: anchor.web3.AccountMeta[] = batch.betIds
+        .slice(0, batch.numBets)
+        .map((betID) => {
+            const [betPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from(BET_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(betID.toNumber())], program.programId
+            );
+            console.log(`BET: ${betPubKey}`);
+            return {pubkey: betPubKey, isWritable: false, isSigner: false};
+        });
+
+    const tx = await program.methods
+        .closeSettlementBatch()
+        .accounts({
+            settlementBatch: settlementBatchPubKey,
+            keeper: batch.keeper,
+        })
+        .remainingAccounts(remainingAccounts)
+        .rpc();
+    console.log(`[TX] close settlement batch: ${tx}`);
+})().then(() => console.log("Finished successfully")).catch(console.error)
@@ -0,0 +1,54 @@
+import * as anchor from "@coral-xyz/anchor";
+import {Game} from '../target/types/game';
+import {formatValue, GAME_CONFIG_SEED, SESSION_SEED, GAME_ID, toLEBytesFromUInt64} from './utils'
+import {getAssociatedTokenAddress} from "@solana/spl-token";
+
+
+(async () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+
+    const program: anchor.Program<Game> = anchor.workspace.Game;
+
+    const payer = (provider.wallet as anchor.Wallet).payer;
+    console.log(`PAYER: ${payer.publicKey}`);
+
+    const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`CONFIG: ${gameConfigPubKey}`);
+
+    const sessionKey = new anchor.web3.PublicKey(process.env.SESSION_KEY)
+    const [sessionPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(SESSION_SEED), toLEBytesFromUInt64(GAME_ID), payer.publicKey.toBuffer(), sessionKey.toBuffer()], program.programId
+    );
+    console.log(`SESSION: ${sessionPubKey}`);
+
+    console.log(`TOKEN: ${process.env.TOKEN}`);
+    const token = new anchor.web3.PublicKey(process.env.TOKEN)
+
+    const userTokenAccountPubKey = await getAssociatedTokenAddress(
+        token,
+        payer.publicKey,
+        true,
+    );
+    console.log(`USER TOKEN ACCOUNT: ${userTokenAccountPubKey}`);
+
+    // One day, up to 50 tokens staked on any accepted pair
+    const expiresAt = Math.floor(Date.now() / 1000) + 24 * 60 * 60;
+
+    const tx = await program.methods
+        .createSession(sessionKey, new anchor.BN(expiresAt), new anchor.BN(50_000_000), [])
+        .accounts({
+            payer: payer.publicKey,
+            gameConfig: gameConfigPubKey,
+            session: sessionPubKey,
+            userTokenAccount: userTokenAccountPubKey,
+        })
+        .signers([payer])
+        .rpc();
+
+    console.log(`[TX] create session: ${tx}`);
+    const session = await program.account.session.fetch(sessionPubKey)
+    console.log("Session:", formatValue(session));
+})().then(() => console.log("Finished successfully")).catch(console.error)
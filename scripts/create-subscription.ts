@@ -0,0 +1,84 @@
+import * as anchor from "@coral-xyz/anchor";
+import {Game} from '../target/types/game';
+import {
+    GAME_CONFIG_SEED,
+    PAIR_SEED,
+    SUBSCRIPTION_SEED,
+    toLEBytesFromUInt64,
+    formatValue,
+    GAME_ID,
+} from './utils'
+import {getAssociatedTokenAddress} from "@solana/spl-token";
+
+(async () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+
+    const program: anchor.Program<Game> = anchor.workspace.Game;
+
+    const payer = (provider.wallet as anchor.Wallet).payer;
+    console.log(`[env] PAYER: ${payer.publicKey}`);
+
+    const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`CONFIG: ${gameConfigPubKey}`);
+
+    const subscriptionID: number = Number(process.env.SUBSCRIPTION_ID ?? 0);
+    const [subscriptionPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(SUBSCRIPTION_SEED), toLEBytesFromUInt64(GAME_ID), payer.publicKey.toBuffer(), toLEBytesFromUInt64(subscriptionID)],
+        program.programId
+    );
+    console.log(`SUBSCRIPTION: ${subscriptionPubKey}`);
+
+    console.log(`TOKEN: ${process.env.TOKEN}`);
+    const token = new anchor.web3.PublicKey(process.env.TOKEN)
+
+    const subscriptionEscrowPubKey = await getAssociatedTokenAddress(
+        token,
+        subscriptionPubKey,
+        true,
+    );
+    console.log(`SUBSCRIPTION ESCROW: ${subscriptionEscrowPubKey}`);
+
+    const userTokenAccountPubKey = await getAssociatedTokenAddress(
+        token,
+        payer.publicKey,
+        true,
+    );
+    console.log(`USER TOKEN ACCOUNT: ${userTokenAccountPubKey}`);
+
+    const pairArray = [66, 84, 67, 85, 83, 68, 88, 88]; // Equivalent to ['B', 'T', 'C', 'U', 'S', 'D', 'X', 'X']
+    const [acceptedPairPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(PAIR_SEED), toLEBytesFromUInt64(GAME_ID), Buffer.from(pairArray)], program.programId
+    );
+    console.log(`PAIR: ${acceptedPairPubKey}`);
+
+    const tx = await program.methods
+        // 5 rounds of a 5 minute long, compounding winnings
+        .createSubscription(
+            new anchor.BN(subscriptionID),
+            new anchor.BN(25_000_000),
+            new anchor.BN(5_000_000),
+            pairArray,
+            300,
+            true,
+            5,
+            true,
+            [],
+        )
+        .accounts({
+            payer: payer.publicKey,
+            gameConfig: gameConfigPubKey,
+            acceptedPair: acceptedPairPubKey,
+            subscription: subscriptionPubKey,
+            subscriptionEscrow: subscriptionEscrowPubKey,
+            userTokenAccount: userTokenAccountPubKey,
+            tokenMint: token,
+        })
+        .rpc();
+    console.log(`[TX] create subscription: ${tx}`);
+
+    const subscription = await program.account.subscription.fetch(subscriptionPubKey)
+    console.log("New subscription:", formatValue(subscription));
+})().then(() => console.log("Finished successfully")).catch(console.error)
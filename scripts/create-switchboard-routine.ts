@@ -0,0 +1,70 @@
+import * as anchor from "@coral-xyz/anchor";
+import {Game} from '../target/types/game';
+import {FunctionRoutineAccount, SwitchboardProgram} from "@switchboard-xyz/solana.js";
+import {
+    CONFIG_CHANGE_SEED,
+    formatValue,
+    GAME_CONFIG_SEED,
+    GAME_ID,
+    loadSwitchboardFunctionEnv,
+    toLEBytesFromUInt64,
+} from './utils'
+
+(async () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+    const program: anchor.Program<Game> = anchor.workspace.Game;
+
+    const payer = (provider.wallet as anchor.Wallet).payer;
+    console.log(`[env] PAYER: ${payer.publicKey}`);
+
+    const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`CONFIG: ${gameConfigPubKey}`);
+    const config = await program.account.gameConfig.fetch(gameConfigPubKey)
+
+    const switchboardProgram = await SwitchboardProgram.fromProvider(provider);
+    let [switchboardFunction] = await loadSwitchboardFunctionEnv(switchboardProgram);
+
+    // The routine scans the game for expired bets on every run, e.g. SCHEDULE="0 * * * * *"
+    let [switchboardRoutine, routineTx] = await FunctionRoutineAccount.create(
+        switchboardProgram,
+        {
+            name: "SETTLE",
+            functionAccount: switchboardFunction,
+            schedule: process.env.SCHEDULE ?? "0 * * * * *",
+            containerParams: Buffer.from(
+                `PID=${program.programId},GAME_ID=${GAME_ID},ESCROW=${config.gameEscrow}`
+            ),
+            authority: payer.publicKey,
+        }
+    );
+    console.log(`[TX] create switchboard routine: ${routineTx}`);
+    console.log(`ROUTINE: ${switchboardRoutine.publicKey}`);
+
+    // The routine is set by execute-config-change.ts once the config change delay passed
+    const changeID: number = Number(process.env.CHANGE_ID ?? Date.now());
+    const [configChangePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(CONFIG_CHANGE_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(changeID)],
+        program.programId
+    );
+    console.log(`CONFIG CHANGE: ${configChangePubKey}`);
+
+    const tx = await program.methods
+        .proposeConfigChange(
+            new anchor.BN(changeID),
+            {setSwitchboardRoutine: {switchboardRoutine: switchboardRoutine.publicKey}}
+        )
+        .accounts({
+            payer: payer.publicKey,
+            gameConfig: gameConfigPubKey,
+            configChange: configChangePubKey,
+        })
+        .signers([payer])
+        .rpc();
+
+    console.log(`[TX] propose switchboard routine: ${tx}`);
+    const configChange = await program.account.configChange.fetch(configChangePubKey)
+    console.log("Config change:", formatValue(configChange));
+})().then(() => console.log("Finished successfully")).catch(console.error)
@@ -0,0 +1,65 @@
+import * as anchor from "@coral-xyz/anchor";
+import {Game} from '../target/types/game';
+import {formatValue, GAME_CONFIG_SEED, toLEBytesFromUInt64, TOURNAMENT_SEED, GAME_ID} from './utils'
+import {getAssociatedTokenAddress} from "@solana/spl-token";
+
+
+(async () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+
+    const program: anchor.Program<Game> = anchor.workspace.Game;
+
+    const payer = (provider.wallet as anchor.Wallet).payer;
+    console.log(`PAYER: ${payer.publicKey}`);
+
+    const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`CONFIG: ${gameConfigPubKey}`);
+
+    const tournamentID: number = Number(process.env.TOURNAMENT_ID ?? 0);
+    const [tournamentPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(TOURNAMENT_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(tournamentID)], program.programId
+    );
+    console.log(`TOURNAMENT: ${tournamentPubKey}`);
+
+    console.log(`TOKEN: ${process.env.TOKEN}`);
+    const token = new anchor.web3.PublicKey(process.env.TOKEN)
+
+    const tournamentEscrowPubKey = await getAssociatedTokenAddress(
+        token,
+        tournamentPubKey,
+        true,
+    );
+    console.log(`TOURNAMENT ESCROW: ${tournamentEscrowPubKey}`);
+
+    // Weekly tournament starting now, finalized a day after it ends, top three share the pool 50/30/20
+    const startTime = Math.floor(Date.now() / 1000);
+    const endTime = startTime + 7 * 24 * 60 * 60;
+    const finalizeTime = endTime + 24 * 60 * 60;
+
+    const tx = await program.methods
+        .createTournament(
+            new anchor.BN(tournamentID),
+            new anchor.BN(5_000_000),
+            new anchor.BN(startTime),
+            new anchor.BN(endTime),
+            new anchor.BN(finalizeTime),
+            [],
+            [5000, 3000, 2000],
+        )
+        .accounts({
+            payer: payer.publicKey,
+            gameConfig: gameConfigPubKey,
+            tournament: tournamentPubKey,
+            tournamentEscrow: tournamentEscrowPubKey,
+            tokenMint: token,
+        })
+        .signers([payer])
+        .rpc();
+
+    console.log(`[TX] create tournament: ${tx}`);
+    const tournament = await program.account.tournament.fetch(tournamentPubKey)
+    console.log("Tournament:", formatValue(tournament));
+})().then(() => console.log("Finished successfully")).catch(console.error)
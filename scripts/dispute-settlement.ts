@@ -0,0 +1,58 @@
+import * as anchor from "@coral-xyz/anchor";
+import {Game} from '../target/types/game';
+import {
+    BET_SEED,
+    GAME_CONFIG_SEED,
+    GAME_STATE_SEED,
+    toLEBytesFromUInt64,
+    formatValue,
+    GAME_ID,
+} from './utils'
+
+(async () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+
+    const program: anchor.Program<Game> = anchor.workspace.Game;
+
+    const payer = (provider.wallet as anchor.Wallet).payer;
+    console.log(`[env] PAYER: ${payer.publicKey}`);
+
+    const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`CONFIG: ${gameConfigPubKey}`);
+
+    const [gameStatePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_STATE_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`STATE: ${gameStatePubKey}`);
+
+    const betID: number = Number(process.env.BET_ID);
+    console.log(`BET ID: ${betID}`);
+
+    const [betPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(BET_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(betID)], program.programId
+    );
+    console.log(`BET: ${betPubKey}`);
+
+    // The stake is claimed back afterwards with claim-position.ts, or claim-payout.ts
+    // for bets without a position
+    const heldBet = await program.account.bet.fetch(betPubKey)
+    const tx = await program.methods
+        .disputeSettlement(new anchor.BN(betID))
+        .accounts({
+            payer: payer.publicKey,
+            gameConfig: gameConfigPubKey,
+            gameState: gameStatePubKey,
+            bet: betPubKey,
+            subscription: heldBet.subscription.equals(anchor.web3.PublicKey.default) ? null : heldBet.subscription,
+            tournamentEntry: heldBet.tournamentEntry.equals(anchor.web3.PublicKey.default) ? null : heldBet.tournamentEntry,
+        })
+        .signers([payer])
+        .rpc();
+    console.log(`[TX] dispute settlement: ${tx}`);
+
+    const bet = await program.account.bet.fetch(betPubKey)
+    console.log("BET DATA:", formatValue(bet));
+})().then(() => console.log("Finished successfully")).catch(console.error)
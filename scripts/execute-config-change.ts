@@ -0,0 +1,77 @@
+import * as anchor from "@coral-xyz/anchor";
+import {Game} from '../target/types/game';
+import {
+    CONFIG_CHANGE_SEED,
+    formatValue,
+    GAME_CONFIG_SEED,
+    GAME_ID,
+    GAME_STATE_SEED,
+    PAIR_SEED,
+    toLEBytesFromUInt64
+} from './utils'
+
+
+(async () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+
+    const program: anchor.Program<Game> = anchor.workspace.Game;
+
+    const payer = (provider.wallet as anchor.Wallet).payer;
+    console.log(`PAYER: ${payer.publicKey}`);
+
+    const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`CONFIG: ${gameConfigPubKey}`);
+
+    const [gameStatePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_STATE_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`STATE: ${gameStatePubKey}`);
+
+    const changeID: number = Number(process.env.CHANGE_ID);
+    const [configChangePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(CONFIG_CHANGE_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(changeID)],
+        program.programId
+    );
+    console.log(`CONFIG CHANGE: ${configChangePubKey}`);
+
+    const config = await program.account.gameConfig.fetch(gameConfigPubKey)
+    const configChange = await program.account.configChange.fetch(configChangePubKey)
+    console.log("Config change:", formatValue(configChange));
+
+    // Pair changes pass the pair account to create, disable or update
+    const change: any = configChange.change;
+    const pair = change.addPair?.pair ?? change.deletePair?.pair ?? change.setPriceBounds?.pair
+        ?? change.setOracle?.pair;
+    const pairPubKey = pair
+        ? anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from(PAIR_SEED), toLEBytesFromUInt64(GAME_ID), Buffer.from(pair)], program.programId
+        )[0]
+        : null;
+    // A deleted pair keeps its account, so adding it back updates it
+    const pairExists = pairPubKey !== null
+        && await provider.connection.getAccountInfo(pairPubKey) !== null;
+
+    const tx = await program.methods
+        .executeConfigChange()
+        .accounts({
+            payer: payer.publicKey,
+            gameConfig: gameConfigPubKey,
+            configChange: configChangePubKey,
+            gameState: gameStatePubKey,
+            authority: config.authority,
+            newPair: change.addPair && !pairExists ? pairPubKey : null,
+            removedPair: change.deletePair ? pairPubKey : null,
+            updatedPair: (change.addPair && pairExists) || change.setPriceBounds || change.setOracle
+                ? pairPubKey
+                : null,
+        })
+        .signers([payer])
+        .rpc();
+
+    console.log(`[TX] execute config change: ${tx}`);
+    const newConfig = await program.account.gameConfig.fetch(gameConfigPubKey)
+    console.log("Game config:", formatValue(newConfig));
+})().then(() => console.log("Finished successfully")).catch(console.error)
@@ -6,6 +6,7 @@ import {
     GAME_STATE_SEED,
     toLEBytesFromUInt64,
     formatValue,
+    GAME_ID,
 } from './utils'
 
 (async () => {
@@ -18,12 +19,12 @@ import {
     console.log(`[env] PAYER: ${payer.publicKey}`);
 
     const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from(GAME_CONFIG_SEED)], program.programId
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
     );
     console.log(`CONFIG: ${gameConfigPubKey}`);
 
     const [gameStatePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from(GAME_STATE_SEED)], program.programId
+        [Buffer.from(GAME_STATE_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
     );
     console.log(`STATE: ${gameStatePubKey}`);
 
@@ -31,7 +32,7 @@ import {
     const betID: number = state.nextBetId.toNumber() - 1;
 
     const [betPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from(BET_SEED), toLEBytesFromUInt64(betID)], program.programId
+        [Buffer.from(BET_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(betID)], program.programId
     );
     console.log(`BET: ${betPubKey}`);
 
@@ -1,6 +1,6 @@
 import * as anchor from "@coral-xyz/anchor";
 import {Game} from '../target/types/game';
-import {formatValue, GAME_CONFIG_SEED, GAME_STATE_SEED, loadSwitchboardFunctionEnv} from './utils'
+import {formatValue, GAME_CONFIG_SEED, GAME_STATE_SEED, loadSwitchboardFunctionEnv, GAME_ID, toLEBytesFromUInt64} from './utils'
 import {SwitchboardProgram} from "@switchboard-xyz/solana.js";
 import {getAssociatedTokenAddress} from "@solana/spl-token";
 
@@ -14,12 +14,12 @@ import {getAssociatedTokenAddress} from "@solana/spl-token";
     console.log(`[env] PAYER: ${payer.publicKey}`);
 
     const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from(GAME_CONFIG_SEED)], program.programId
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
     );
     console.log(`CONFIG: ${gameConfigPubKey}`);
 
     const [gameStatePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from(GAME_STATE_SEED)], program.programId
+        [Buffer.from(GAME_STATE_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
     );
     console.log(`STATE: ${gameStatePubKey}`);
 
@@ -36,8 +36,14 @@ import {getAssociatedTokenAddress} from "@solana/spl-token";
     let [switchboardFunction,] = await loadSwitchboardFunctionEnv(switchboardProgram);
     console.log(`SWITCHBOARD FUNCTION: ${switchboardFunction.publicKey}`);
 
+    // Bets are settled by request until a routine is set, see create-switchboard-routine.ts
+    const switchboardRoutine = process.env.SWITCHBOARD_ROUTINE
+        ? new anchor.web3.PublicKey(process.env.SWITCHBOARD_ROUTINE)
+        : anchor.web3.PublicKey.default;
+    console.log(`SWITCHBOARD ROUTINE: ${switchboardRoutine}`);
+
     const tx = await program.methods
-        .initialize()
+        .initialize(new anchor.BN(GAME_ID), switchboardRoutine)
         .accounts({
             payer: payer.publicKey,
             gameConfig: gameConfigPubKey,
@@ -0,0 +1,44 @@
+import * as anchor from "@coral-xyz/anchor";
+import {Game} from '../target/types/game';
+import {formatValue, GAME_CONFIG_SEED, toLEBytesFromUInt64, VOUCHER_SEED, GAME_ID} from './utils'
+
+
+(async () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+
+    const program: anchor.Program<Game> = anchor.workspace.Game;
+
+    const payer = (provider.wallet as anchor.Wallet).payer;
+    console.log(`PAYER: ${payer.publicKey}`);
+
+    const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`CONFIG: ${gameConfigPubKey}`);
+
+    const user = new anchor.web3.PublicKey(process.env.VOUCHER_USER)
+    const voucherID: number = Number(process.env.VOUCHER_ID ?? 0);
+    const [voucherPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(VOUCHER_SEED), toLEBytesFromUInt64(GAME_ID), user.toBuffer(), toLEBytesFromUInt64(voucherID)], program.programId
+    );
+    console.log(`VOUCHER: ${voucherPubKey}`);
+
+    // One week, three free bets on any accepted pair
+    const expiresAt = Math.floor(Date.now() / 1000) + 7 * 24 * 60 * 60;
+
+    const tx = await program.methods
+        .issueVoucher(new anchor.BN(voucherID), new anchor.BN(5_000_000), new anchor.BN(expiresAt), 3, [])
+        .accounts({
+            payer: payer.publicKey,
+            gameConfig: gameConfigPubKey,
+            voucher: voucherPubKey,
+            user,
+        })
+        .signers([payer])
+        .rpc();
+
+    console.log(`[TX] issue voucher: ${tx}`);
+    const voucher = await program.account.voucher.fetch(voucherPubKey)
+    console.log("Voucher:", formatValue(voucher));
+})().then(() => console.log("Finished successfully")).catch(console.error)
@@ -0,0 +1,82 @@
+import * as anchor from "@coral-xyz/anchor";
+import {Game} from '../target/types/game';
+import {BET_SEED, formatValue, GAME_CONFIG_SEED, GAME_STATE_SEED, GAME_ID, toLEBytesFromUInt64} from './utils'
+import {getAssociatedTokenAddress} from "@solana/spl-token";
+
+
+(async () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+
+    const program: anchor.Program<Game> = anchor.workspace.Game;
+
+    const payer = (provider.wallet as anchor.Wallet).payer;
+    console.log(`PAYER: ${payer.publicKey}`);
+
+    // The game deployed before game IDs, at addresses seeded without one
+    const [legacyConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_CONFIG_SEED)], program.programId
+    );
+    console.log(`LEGACY CONFIG: ${legacyConfigPubKey}`);
+
+    const [legacyStatePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_STATE_SEED)], program.programId
+    );
+    console.log(`LEGACY STATE: ${legacyStatePubKey}`);
+
+    const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`CONFIG: ${gameConfigPubKey}`);
+
+    const [gameStatePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_STATE_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`STATE: ${gameStatePubKey}`);
+
+    const tokenMintPubKey = new anchor.web3.PublicKey(process.env.TOKEN_MINT)
+    console.log(`TOKEN MINT: ${tokenMintPubKey}`);
+
+    const gameTx = await program.methods
+        .migrateLegacyGame(new anchor.BN(GAME_ID))
+        .accounts({
+            payer: payer.publicKey,
+            legacyConfig: legacyConfigPubKey,
+            legacyState: legacyStatePubKey,
+            legacyEscrow: await getAssociatedTokenAddress(tokenMintPubKey, legacyConfigPubKey, true),
+            gameConfig: gameConfigPubKey,
+            gameState: gameStatePubKey,
+            gameEscrow: await getAssociatedTokenAddress(tokenMintPubKey, gameConfigPubKey, true),
+            tokenMint: tokenMintPubKey,
+        })
+        .signers([payer])
+        .rpc();
+    console.log(`[TX] migrate legacy game: ${gameTx}`);
+
+    // Open bets placed before game IDs, e.g. BET_IDS=3,4,7
+    const betIDs = (process.env.BET_IDS ?? "").split(",").filter((id) => id !== "").map(Number);
+    for (const betID of betIDs) {
+        const [legacyBetPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from(BET_SEED), toLEBytesFromUInt64(betID)], program.programId
+        );
+        const [betPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from(BET_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(betID)], program.programId
+        );
+        const betTx = await program.methods
+            .migrateLegacyBet(new anchor.BN(betID))
+            .accounts({
+                payer: payer.publicKey,
+                gameConfig: gameConfigPubKey,
+                legacyBet: legacyBetPubKey,
+                bet: betPubKey,
+            })
+            .signers([payer])
+            .rpc();
+        console.log(`[TX] migrate legacy bet ${betID}: ${betTx}`);
+    }
+
+    const config = await program.account.gameConfig.fetch(gameConfigPubKey)
+    console.log("Game config:", formatValue(config));
+    const state = await program.account.gameState.fetch(gameStatePubKey)
+    console.log("Game state:", formatValue(state));
+})().then(() => console.log("Finished successfully")).catch(console.error)
@@ -0,0 +1,87 @@
+import * as anchor from "@coral-xyz/anchor";
+import {Game} from '../target/types/game';
+import {BET_SEED, formatValue, GAME_CONFIG_SEED, GAME_STATE_SEED, GAME_ID, PAIR_SEED, toLEBytesFromUInt64} from './utils'
+
+
+(async () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+
+    const program: anchor.Program<Game> = anchor.workspace.Game;
+
+    const payer = (provider.wallet as anchor.Wallet).payer;
+    console.log(`PAYER: ${payer.publicKey}`);
+
+    const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`CONFIG: ${gameConfigPubKey}`);
+
+    const [gameStatePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_STATE_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`STATE: ${gameStatePubKey}`);
+
+    // The config is migrated first, the state migration loads it with the current layout
+    const configTx = await program.methods
+        .migrateConfig()
+        .accounts({
+            payer: payer.publicKey,
+            gameConfig: gameConfigPubKey,
+        })
+        .signers([payer])
+        .rpc();
+    console.log(`[TX] migrate config: ${configTx}`);
+
+    const stateTx = await program.methods
+        .migrateState()
+        .accounts({
+            payer: payer.publicKey,
+            gameConfig: gameConfigPubKey,
+            gameState: gameStatePubKey,
+        })
+        .signers([payer])
+        .rpc();
+    console.log(`[TX] migrate state: ${stateTx}`);
+
+    // Pairs registered before the upgrade, e.g. PAIRS=BTCUSDXX,ETHUSDXX
+    const pairs = (process.env.PAIRS ?? "").split(",").filter((pair) => pair !== "");
+    for (const pair of pairs) {
+        const [pairPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from(PAIR_SEED), toLEBytesFromUInt64(GAME_ID), Buffer.from(pair)], program.programId
+        );
+        const pairTx = await program.methods
+            .migratePair(Array.from(Buffer.from(pair)))
+            .accounts({
+                payer: payer.publicKey,
+                gameConfig: gameConfigPubKey,
+                acceptedPair: pairPubKey,
+            })
+            .signers([payer])
+            .rpc();
+        console.log(`[TX] migrate pair ${pair}: ${pairTx}`);
+    }
+
+    // Open bets from before the upgrade, e.g. BET_IDS=3,4,7
+    const betIDs = (process.env.BET_IDS ?? "").split(",").filter((id) => id !== "").map(Number);
+    for (const betID of betIDs) {
+        const [betPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from(BET_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(betID)], program.programId
+        );
+        const betTx = await program.methods
+            .migrateBet(new anchor.BN(betID))
+            .accounts({
+                payer: payer.publicKey,
+                gameConfig: gameConfigPubKey,
+                bet: betPubKey,
+            })
+            .signers([payer])
+            .rpc();
+        console.log(`[TX] migrate bet ${betID}: ${betTx}`);
+    }
+
+    const config = await program.account.gameConfig.fetch(gameConfigPubKey)
+    console.log("Game config:", formatValue(config));
+    const state = await program.account.gameState.fetch(gameStatePubKey)
+    console.log("Game state:", formatValue(state));
+})().then(() => console.log("Finished successfully")).catch(console.error)
@@ -0,0 +1,100 @@
+import * as anchor from "@coral-xyz/anchor";
+import {Game} from '../target/types/game';
+import {
+    BET_SEED,
+    GAME_CONFIG_SEED,
+    GAME_STATE_SEED,
+    PAIR_SEED,
+    SUBSCRIPTION_SEED,
+    USER_PROFILE_SEED,
+    toLEBytesFromUInt64,
+    formatValue,
+    GAME_ID,
+} from './utils'
+import {getAssociatedTokenAddress} from "@solana/spl-token";
+
+(async () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+
+    const program: anchor.Program<Game> = anchor.workspace.Game;
+
+    const payer = (provider.wallet as anchor.Wallet).payer;
+    console.log(`[env] PAYER: ${payer.publicKey}`);
+
+    const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`CONFIG: ${gameConfigPubKey}`);
+
+    const [gameStatePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_STATE_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`STATE: ${gameStatePubKey}`);
+
+    // Any wallet can open the next round, the subscription owner is only needed to derive the address
+    const subscriptionOwner = new anchor.web3.PublicKey(process.env.SUBSCRIPTION_OWNER ?? payer.publicKey);
+    const subscriptionID: number = Number(process.env.SUBSCRIPTION_ID ?? 0);
+    const [subscriptionPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(SUBSCRIPTION_SEED), toLEBytesFromUInt64(GAME_ID), subscriptionOwner.toBuffer(), toLEBytesFromUInt64(subscriptionID)],
+        program.programId
+    );
+    console.log(`SUBSCRIPTION: ${subscriptionPubKey}`);
+
+    const subscription = await program.account.subscription.fetch(subscriptionPubKey)
+    const [acceptedPairPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(PAIR_SEED), toLEBytesFromUInt64(GAME_ID), Buffer.from(subscription.pair)], program.programId
+    );
+    console.log(`PAIR: ${acceptedPairPubKey}`);
+
+    // The round takes one of the subscriber's open bet slots
+    const [userProfilePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(USER_PROFILE_SEED), toLEBytesFromUInt64(GAME_ID), subscription.user.toBuffer()], program.programId
+    );
+    console.log(`USER PROFILE: ${userProfilePubKey}`);
+
+    const state = await program.account.gameState.fetch(gameStatePubKey)
+    const betID: number = state.nextBetId.toNumber();
+    console.log(`New bet ID: ${betID}`);
+
+    const [betPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(BET_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(betID)], program.programId
+    );
+    console.log(`BET: ${betPubKey}`);
+
+    console.log(`TOKEN: ${process.env.TOKEN}`);
+    const token = new anchor.web3.PublicKey(process.env.TOKEN)
+
+    const gameEscrowPubKey = await getAssociatedTokenAddress(
+        token,
+        gameConfigPubKey,
+        true,
+    );
+    console.log(`ESCROW: ${gameEscrowPubKey}`);
+
+    const subscriptionEscrowPubKey = await getAssociatedTokenAddress(
+        token,
+        subscriptionPubKey,
+        true,
+    );
+    console.log(`SUBSCRIPTION ESCROW: ${subscriptionEscrowPubKey}`);
+
+    const tx = await program.methods
+        .openSubscriptionRound()
+        .accounts({
+            payer: payer.publicKey,
+            gameConfig: gameConfigPubKey,
+            gameState: gameStatePubKey,
+            subscription: subscriptionPubKey,
+            subscriptionEscrow: subscriptionEscrowPubKey,
+            acceptedPair: acceptedPairPubKey,
+            userProfile: userProfilePubKey,
+            bet: betPubKey,
+            gameEscrow: gameEscrowPubKey,
+        })
+        .rpc();
+    console.log(`[TX] open subscription round: ${tx}`);
+
+    const new_bet = await program.account.bet.fetch(betPubKey)
+    console.log("New bet:", formatValue(new_bet));
+})().then(() => console.log("Finished successfully")).catch(console.error)
@@ -4,12 +4,15 @@ import {
     BET_SEED,
     GAME_CONFIG_SEED,
     GAME_STATE_SEED,
+    POSITION_MINT_SEED,
+    REFERRER_SEED,
+    USER_PROFILE_SEED,
+    PAIR_SEED,
     toLEBytesFromUInt64,
     formatValue,
-    loadSwitchboardFunctionEnv
+    GAME_ID,
 } from './utils'
 import {getAssociatedTokenAddress} from "@solana/spl-token";
-import {AttestationQueueAccount, SwitchboardProgram} from "@switchboard-xyz/solana.js";
 
 (async () => {
     const provider = anchor.AnchorProvider.env()
@@ -21,12 +24,12 @@ import {AttestationQueueAccount, SwitchboardProgram} from "@switchboard-xyz/sola
     console.log(`[env] PAYER: ${payer.publicKey}`);
 
     const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from(GAME_CONFIG_SEED)], program.programId
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
     );
     console.log(`CONFIG: ${gameConfigPubKey}`);
 
     const [gameStatePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from(GAME_STATE_SEED)], program.programId
+        [Buffer.from(GAME_STATE_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
     );
     console.log(`STATE: ${gameStatePubKey}`);
 
@@ -35,7 +38,7 @@ import {AttestationQueueAccount, SwitchboardProgram} from "@switchboard-xyz/sola
     console.log(`New bet ID: ${betID}`);
 
     const [betPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from(BET_SEED), toLEBytesFromUInt64(betID)], program.programId
+        [Buffer.from(BET_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(betID)], program.programId
     );
     console.log(`BET: ${betPubKey}`);
 
@@ -56,41 +59,59 @@ import {AttestationQueueAccount, SwitchboardProgram} from "@switchboard-xyz/sola
     );
     console.log(`USER TOKEN ACCOUNT: ${userTokenAccountPubKey}`);
 
-    const switchboardProgram = await SwitchboardProgram.fromProvider(provider);
-    let [switchboardFunction, functionState] = await loadSwitchboardFunctionEnv(switchboardProgram);
-    let attestationQueue = new AttestationQueueAccount(
-        switchboardProgram,
-        functionState.attestationQueue
+    const [positionMintPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(POSITION_MINT_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(betID)], program.programId
     );
+    console.log(`POSITION MINT: ${positionMintPubKey}`);
 
-    // Create a new request account with a fresh keypair
-    const switchboardRequestKeypair = anchor.web3.Keypair.generate();
-    const switchboardRequestEscrowPubkey = anchor.utils.token.associatedAddress({
-        mint: switchboardProgram.mint.address,
-        owner: switchboardRequestKeypair.publicKey,
-    });
-    console.log(`REQUEST ACCOUNT: ${switchboardRequestKeypair.publicKey}`);
+    const positionTokenAccountPubKey = await getAssociatedTokenAddress(
+        positionMintPubKey,
+        payer.publicKey,
+        true,
+    );
+    console.log(`POSITION TOKEN ACCOUNT: ${positionTokenAccountPubKey}`);
+
+    const [userProfilePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(USER_PROFILE_SEED), toLEBytesFromUInt64(GAME_ID), payer.publicKey.toBuffer()], program.programId
+    );
+    console.log(`USER PROFILE: ${userProfilePubKey}`);
+
+    // The referrer is recorded on the first bet and must be passed on every bet after that
+    const referrerPubKey = process.env.REFERRER
+        ? anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from(REFERRER_SEED), toLEBytesFromUInt64(GAME_ID), new anchor.web3.PublicKey(process.env.REFERRER).toBuffer()],
+            program.programId
+        )[0]
+        : null;
+    console.log(`REFERRER: ${referrerPubKey}`);
 
     const pairArray = [66, 84, 67, 85, 83, 68, 88, 88]; // Equivalent to ['B', 'T', 'C', 'U', 'S', 'D', 'X', 'X']
+    const [acceptedPairPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(PAIR_SEED), toLEBytesFromUInt64(GAME_ID), Buffer.from(pairArray)], program.programId
+    );
+    console.log(`PAIR: ${acceptedPairPubKey}`);
 
     const tx = await program.methods
-        .placeBet(new anchor.BN(1_000_000), pairArray, 120, true)
+        // Start time 0 opens the bet after the minimum entry delay
+        .placeBet(new anchor.BN(1_000_000), pairArray, 120, true, new anchor.BN(0), [])
         .accounts({
             payer: payer.publicKey,
             gameConfig: gameConfigPubKey,
             gameState: gameStatePubKey,
+            acceptedPair: acceptedPairPubKey,
             bet: betPubKey,
+            user: payer.publicKey,
+            session: null,
             userTokenAccount: userTokenAccountPubKey,
             gameEscrow: gameEscrowPubKey,
-            switchboard: switchboardProgram.attestationProgramId,
-            switchboardState: switchboardProgram.attestationProgramState.publicKey,
-            switchboardAttestationQueue: attestationQueue.publicKey,
-            switchboardFunction: switchboardFunction.publicKey,
-            switchboardRequest: switchboardRequestKeypair.publicKey,
-            switchboardRequestEscrow: switchboardRequestEscrowPubkey,
-            switchboardMint: switchboardProgram.mint.address,
+            positionMint: positionMintPubKey,
+            positionTokenAccount: positionTokenAccountPubKey,
+            voucher: null,
+            userProfile: userProfilePubKey,
+            referrer: referrerPubKey,
+            tournament: null,
+            tournamentEntry: null,
         })
-        .signers([switchboardRequestKeypair])
         .rpc();
     console.log(`[TX] place bet: ${tx}`);
 
@@ -0,0 +1,38 @@
+import * as anchor from "@coral-xyz/anchor";
+import {Game} from '../target/types/game';
+import {formatValue, GAME_CONFIG_SEED, REFERRER_SEED, GAME_ID, toLEBytesFromUInt64} from './utils'
+
+
+(async () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+
+    const program: anchor.Program<Game> = anchor.workspace.Game;
+
+    const payer = (provider.wallet as anchor.Wallet).payer;
+    console.log(`PAYER: ${payer.publicKey}`);
+
+    const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`CONFIG: ${gameConfigPubKey}`);
+
+    const [referrerPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(REFERRER_SEED), toLEBytesFromUInt64(GAME_ID), payer.publicKey.toBuffer()], program.programId
+    );
+    console.log(`REFERRER: ${referrerPubKey}`);
+
+    const tx = await program.methods
+        .registerReferrer()
+        .accounts({
+            payer: payer.publicKey,
+            gameConfig: gameConfigPubKey,
+            referrer: referrerPubKey,
+        })
+        .signers([payer])
+        .rpc();
+
+    console.log(`[TX] register referrer: ${tx}`);
+    const referrer = await program.account.referrer.fetch(referrerPubKey)
+    console.log("Referrer:", formatValue(referrer));
+})().then(() => console.log("Finished successfully")).catch(console.error)
@@ -0,0 +1,100 @@
+import * as anchor from "@coral-xyz/anchor";
+import {Game} from '../target/types/game';
+import {
+    BET_SEED,
+    MAX_BATCH_BETS,
+    PAIR_SEED,
+    SETTLEMENT_BATCH_SEED,
+    SETTLEMENT_BUCKET,
+    toLEBytesFromUInt64,
+    loadSwitchboardFunctionEnv,
+    GAME_CONFIG_SEED,
+    GAME_ID,
+} from './utils'
+import {AttestationQueueAccount, SwitchboardProgram} from "@switchboard-xyz/solana.js";
+
+(async () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+    const program: anchor.Program<Game> = anchor.workspace.Game;
+
+    const payer = (provider.wallet as anchor.Wallet).payer;
+    console.log(`[env] PAYER: ${payer.publicKey}`);
+
+    const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`CONFIG: ${gameConfigPubKey}`);
+
+    // Every active bet of the game that ended in the bucket and settles through
+    // Switchboard, e.g. BUCKET=5712345
+    const bucket: number = Number(process.env.BUCKET);
+    console.log(`BUCKET: ${bucket}`);
+    const bets: anchor.web3.AccountMeta[][] = [];
+    for (const {publicKey, account: bet} of await program.account.bet.all()) {
+        const [betPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from(BET_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(bet.betId.toNumber())], program.programId
+        );
+        if (!betPubKey.equals(publicKey) || !bet.active || Math.floor(bet.endTime.toNumber() / SETTLEMENT_BUCKET) !== bucket) {
+            continue;
+        }
+
+        const [acceptedPairPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from(PAIR_SEED), toLEBytesFromUInt64(GAME_ID), Buffer.from(bet.pair)], program.programId
+        );
+        if ((await program.account.acceptedPair.fetch(acceptedPairPubKey)).pullOracle) {
+            continue;
+        }
+        console.log(`BET: ${betPubKey}`);
+        bets.push([
+            {pubkey: betPubKey, isWritable: false, isSigner: false},
+            {pubkey: acceptedPairPubKey, isWritable: false, isSigner: false},
+        ]);
+    }
+
+    const switchboardProgram = await SwitchboardProgram.fromProvider(provider);
+    let [switchboardFunction, functionState] = await loadSwitchboardFunctionEnv(switchboardProgram);
+    let attestationQueue = new AttestationQueueAccount(
+        switchboardProgram,
+        functionState.attestationQueue
+    );
+
+    // A batch takes up to MAX_BATCH_BETS bets, so the bucket is split over as
+    // many batches as it takes, each with its own request
+    for (let i = 0; i < bets.length; i += MAX_BATCH_BETS) {
+        const remainingAccounts = bets.slice(i, i + MAX_BATCH_BETS).flat();
+
+        // Create a new request account with a fresh keypair
+        const switchboardRequestKeypair = anchor.web3.Keypair.generate();
+        const switchboardRequestEscrowPubkey = anchor.utils.token.associatedAddress({
+            mint: switchboardProgram.mint.address,
+            owner: switchboardRequestKeypair.publicKey,
+        });
+        console.log(`REQUEST ACCOUNT: ${switchboardRequestKeypair.publicKey}`);
+
+        const [settlementBatchPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from(SETTLEMENT_BATCH_SEED), toLEBytesFromUInt64(GAME_ID), switchboardRequestKeypair.publicKey.toBuffer()],
+            program.programId
+        );
+        console.log(`BATCH: ${settlementBatchPubKey}`);
+
+        const tx = await program.methods
+            .requestBatchSettlement(new anchor.BN(bucket))
+            .accounts({
+                payer: payer.publicKey,
+                gameConfig: gameConfigPubKey,
+                settlementBatch: settlementBatchPubKey,
+                switchboard: switchboardProgram.attestationProgramId,
+                switchboardState: switchboardProgram.attestationProgramState.publicKey,
+                switchboardAttestationQueue: attestationQueue.publicKey,
+                switchboardFunction: switchboardFunction.publicKey,
+                switchboardRequest: switchboardRequestKeypair.publicKey,
+                switchboardRequestEscrow: switchboardRequestEscrowPubkey,
+                switchboardMint: switchboardProgram.mint.address,
+            })
+            .remainingAccounts(remainingAccounts)
+            .signers([switchboardRequestKeypair])
+            .rpc();
+        console.log(`[TX] request batch settlement: ${tx}`);
+    }
+})().then(() => console.log("Finished successfully")).catch(console.error)
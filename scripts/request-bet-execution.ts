@@ -4,7 +4,9 @@ import {
     BET_SEED,
     GAME_STATE_SEED,
     toLEBytesFromUInt64,
-    loadSwitchboardFunctionEnv, GAME_CONFIG_SEED
+    loadSwitchboardFunctionEnv,
+    GAME_CONFIG_SEED,
+    GAME_ID,
 } from './utils'
 import {
     AttestationQueueAccount, FunctionRequestAccount,
@@ -20,12 +22,12 @@ import {
     console.log(`[env] PAYER: ${payer.publicKey}`);
 
     const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from(GAME_CONFIG_SEED)], program.programId
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
     );
     console.log(`CONFIG: ${gameConfigPubKey}`);
 
     const [gameStatePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from(GAME_STATE_SEED)], program.programId
+        [Buffer.from(GAME_STATE_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
     );
     console.log(`STATE: ${gameStatePubKey}`);
 
@@ -34,7 +36,7 @@ import {
     console.log(`BET ID: ${betID}`);
 
     const [betPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from(BET_SEED), toLEBytesFromUInt64(betID)], program.programId
+        [Buffer.from(BET_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(betID)], program.programId
     );
     console.log(`BET: ${betPubKey}`);
 
@@ -1,6 +1,6 @@
 import * as anchor from "@coral-xyz/anchor";
 import {Game} from '../target/types/game';
-import {formatValue, GAME_CONFIG_SEED} from './utils'
+import {CONFIG_CHANGE_SEED, formatValue, GAME_CONFIG_SEED, GAME_ID, toLEBytesFromUInt64} from './utils'
 
 
 (async () => {
@@ -13,20 +13,35 @@ import {formatValue, GAME_CONFIG_SEED} from './utils'
     console.log(`PAYER: ${payer.publicKey}`);
 
     const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from(GAME_CONFIG_SEED)], program.programId
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
     );
     console.log(`CONFIG: ${gameConfigPubKey}`);
 
+    // Applied by execute-config-change.ts once the config change delay passed
+    const changeID: number = Number(process.env.CHANGE_ID ?? Date.now());
+    const [configChangePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(CONFIG_CHANGE_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(changeID)],
+        program.programId
+    );
+    console.log(`CONFIG CHANGE: ${configChangePubKey}`);
+
     const tx = await program.methods
-        .setAmounts(new anchor.BN(1_000_000), new anchor.BN(50_000_000), new anchor.BN(255_000_000))
+        .proposeConfigChange(new anchor.BN(changeID), {
+            setAmounts: {
+                minBet: new anchor.BN(1_000_000),
+                maxBet: new anchor.BN(50_000_000),
+                maxUtilizedLiquidity: new anchor.BN(255_000_000),
+            },
+        })
         .accounts({
             payer: payer.publicKey,
             gameConfig: gameConfigPubKey,
+            configChange: configChangePubKey,
         })
         .signers([payer])
         .rpc();
 
-    console.log(`[TX] migrate: ${tx}`);
-    const config = await program.account.gameConfig.fetch(gameConfigPubKey)
-    console.log("Game config:", formatValue(config));
+    console.log(`[TX] propose set amounts: ${tx}`);
+    const configChange = await program.account.configChange.fetch(configChangePubKey)
+    console.log("Config change:", formatValue(configChange));
 })().then(() => console.log("Finished successfully")).catch(console.error)
@@ -0,0 +1,47 @@
+import * as anchor from "@coral-xyz/anchor";
+import {Game} from '../target/types/game';
+import {CONFIG_CHANGE_SEED, formatValue, GAME_CONFIG_SEED, GAME_ID, toLEBytesFromUInt64} from './utils'
+
+
+(async () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+
+    const program: anchor.Program<Game> = anchor.workspace.Game;
+
+    const payer = (provider.wallet as anchor.Wallet).payer;
+    console.log(`PAYER: ${payer.publicKey}`);
+
+    const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`CONFIG: ${gameConfigPubKey}`);
+
+    // Applied by execute-config-change.ts once the config change delay passed
+    const changeID: number = Number(process.env.CHANGE_ID ?? Date.now());
+    const [configChangePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(CONFIG_CHANGE_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(changeID)],
+        program.programId
+    );
+    console.log(`CONFIG CHANGE: ${configChangePubKey}`);
+
+    // Winning payouts of at least the threshold are held for the window, zero turns holding off
+    const disputeThreshold = new anchor.BN(process.env.DISPUTE_THRESHOLD ?? 50_000_000);
+    const disputeWindow = Number(process.env.DISPUTE_WINDOW ?? 60 * 60);
+
+    const tx = await program.methods
+        .proposeConfigChange(new anchor.BN(changeID), {
+            setDisputeParams: {disputeThreshold, disputeWindow},
+        })
+        .accounts({
+            payer: payer.publicKey,
+            gameConfig: gameConfigPubKey,
+            configChange: configChangePubKey,
+        })
+        .signers([payer])
+        .rpc();
+
+    console.log(`[TX] propose set dispute params: ${tx}`);
+    const configChange = await program.account.configChange.fetch(configChangePubKey)
+    console.log("Config change:", formatValue(configChange));
+})().then(() => console.log("Finished successfully")).catch(console.error)
@@ -0,0 +1,100 @@
+import * as anchor from "@coral-xyz/anchor";
+import {Game} from '../target/types/game';
+import {
+    BET_SEED,
+    GAME_CONFIG_SEED,
+    GAME_STATE_SEED,
+    PAIR_SEED,
+    toLEBytesFromUInt64,
+    formatValue,
+    GAME_ID,
+} from './utils'
+import {getAssociatedTokenAddress} from "@solana/spl-token";
+
+(async () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+
+    const program: anchor.Program<Game> = anchor.workspace.Game;
+
+    const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`CONFIG: ${gameConfigPubKey}`);
+
+    const [gameStatePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_STATE_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`STATE: ${gameStatePubKey}`);
+
+    const betID: number = Number(process.env.BET_ID);
+    console.log(`BET ID: ${betID}`);
+
+    const [betPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(BET_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(betID)], program.programId
+    );
+    console.log(`BET: ${betPubKey}`);
+
+    const config = await program.account.gameConfig.fetch(gameConfigPubKey)
+    const bet = await program.account.bet.fetch(betPubKey)
+
+    const [acceptedPairPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(PAIR_SEED), toLEBytesFromUInt64(GAME_ID), Buffer.from(bet.pair)], program.programId
+    );
+    console.log(`PAIR: ${acceptedPairPubKey}`);
+
+    // Price update accounts posted through the Pyth receiver: the first updates published at or after the
+    // bet's start and end, i.e. whose previous publish time is before it
+    const openPriceUpdatePubKey = new anchor.web3.PublicKey(process.env.OPEN_PRICE_UPDATE)
+    const closePriceUpdatePubKey = new anchor.web3.PublicKey(process.env.CLOSE_PRICE_UPDATE)
+    console.log(`OPEN PRICE UPDATE: ${openPriceUpdatePubKey}`);
+    console.log(`CLOSE PRICE UPDATE: ${closePriceUpdatePubKey}`);
+
+    // Positions are paid to the bettor unless the token changed hands
+    const positionTokenAccountPubKey = bet.positionMint.equals(anchor.web3.PublicKey.default)
+        ? null
+        : await getAssociatedTokenAddress(bet.positionMint, bet.user, true);
+
+    // A lost bet rewards the referrer on the bettor's profile
+    const userProfile = bet.userProfile.equals(anchor.web3.PublicKey.default)
+        ? null
+        : await program.account.userProfile.fetch(bet.userProfile);
+    const referrerPubKey = userProfile && !userProfile.referrer.equals(anchor.web3.PublicKey.default)
+        ? userProfile.referrer
+        : null;
+
+    // A subscription round opens the next one at the next bet ID
+    const isRound = !bet.subscription.equals(anchor.web3.PublicKey.default)
+    const state = await program.account.gameState.fetch(gameStatePubKey)
+    const [nextBetPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(BET_SEED), toLEBytesFromUInt64(GAME_ID), toLEBytesFromUInt64(state.nextBetId.toNumber())],
+        program.programId
+    );
+
+    const tx = await program.methods
+        .settleBetWithPyth(new anchor.BN(betID))
+        .accounts({
+            settlement: {
+                bet: betPubKey,
+                gameState: gameStatePubKey,
+                gameConfig: gameConfigPubKey,
+                userTokenAccount: bet.userTokenAccount,
+                gameEscrow: config.gameEscrow,
+                subscription: bet.subscription.equals(anchor.web3.PublicKey.default) ? null : bet.subscription,
+                positionTokenAccount: positionTokenAccountPubKey,
+                tournamentEntry: bet.tournamentEntry.equals(anchor.web3.PublicKey.default) ? null : bet.tournamentEntry,
+                userProfile: bet.userProfile.equals(anchor.web3.PublicKey.default) ? null : bet.userProfile,
+                acceptedPair: acceptedPairPubKey,
+                referrer: referrerPubKey,
+                nextBet: isRound ? nextBetPubKey : null,
+                systemProgram: isRound ? anchor.web3.SystemProgram.programId : null,
+            },
+            openPriceUpdate: openPriceUpdatePubKey,
+            closePriceUpdate: closePriceUpdatePubKey,
+        })
+        .rpc();
+    console.log(`[TX] settle bet with pyth: ${tx}`);
+
+    const settledBet = await program.account.bet.fetch(betPubKey)
+    console.log("BET DATA:", formatValue(settledBet));
+})().then(() => console.log("Finished successfully")).catch(console.error)
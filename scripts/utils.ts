@@ -15,6 +15,22 @@ dotenv.config();
 export const GAME_CONFIG_SEED = "GAME_CONFIG";
 export const GAME_STATE_SEED = "GAME_STATE";
 export const BET_SEED = "BET";
+export const SUBSCRIPTION_SEED = "SUBSCRIPTION";
+export const POSITION_MINT_SEED = "POSITION_MINT";
+export const VOUCHER_SEED = "VOUCHER";
+export const USER_PROFILE_SEED = "USER_PROFILE";
+export const REFERRER_SEED = "REFERRER";
+export const TOURNAMENT_SEED = "TOURNAMENT";
+export const TOURNAMENT_ENTRY_SEED = "TOURNAMENT_ENTRY";
+export const SESSION_SEED = "SESSION";
+export const PAIR_SEED = "PAIR";
+export const CONFIG_CHANGE_SEED = "CONFIG_CHANGE";
+export const SETTLEMENT_BATCH_SEED = "SETTLEMENT_BATCH";
+export const SETTLEMENT_BUCKET = 5 * 60;
+export const MAX_BATCH_BETS = 3;
+
+// Every PDA of a game instance is seeded by its ID
+export const GAME_ID: number = Number(process.env.GAME_ID ?? 0);
 
 export const myMrEnclave: Uint8Array | undefined = process.env.MR_ENCLAVE
     ? parseRawMrEnclave(process.env.MR_ENCLAVE)
@@ -0,0 +1,61 @@
+import * as anchor from "@coral-xyz/anchor";
+import {Game} from '../target/types/game';
+import {formatValue, GAME_CONFIG_SEED, GAME_STATE_SEED, GAME_ID, toLEBytesFromUInt64} from './utils'
+import {getAssociatedTokenAddress} from "@solana/spl-token";
+
+
+(async () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+
+    const program: anchor.Program<Game> = anchor.workspace.Game;
+
+    const payer = (provider.wallet as anchor.Wallet).payer;
+    console.log(`PAYER: ${payer.publicKey}`);
+
+    const [gameConfigPubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_CONFIG_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`CONFIG: ${gameConfigPubKey}`);
+
+    const [gameStatePubKey] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from(GAME_STATE_SEED), toLEBytesFromUInt64(GAME_ID)], program.programId
+    );
+    console.log(`STATE: ${gameStatePubKey}`);
+
+    console.log(`TOKEN: ${process.env.TOKEN}`);
+    const token = new anchor.web3.PublicKey(process.env.TOKEN)
+
+    const gameEscrowPubKey = await getAssociatedTokenAddress(
+        token,
+        gameConfigPubKey,
+        true,
+    );
+    console.log(`ESCROW: ${gameEscrowPubKey}`);
+
+    // Paid to the treasurer's own token account
+    const destinationTokenAccountPubKey = await getAssociatedTokenAddress(
+        token,
+        payer.publicKey,
+        true,
+    );
+    console.log(`DESTINATION TOKEN ACCOUNT: ${destinationTokenAccountPubKey}`);
+
+    const amount = new anchor.BN(process.env.AMOUNT ?? 10_000_000);
+
+    const tx = await program.methods
+        .withdraw(amount)
+        .accounts({
+            payer: payer.publicKey,
+            gameConfig: gameConfigPubKey,
+            gameState: gameStatePubKey,
+            gameEscrow: gameEscrowPubKey,
+            destinationTokenAccount: destinationTokenAccountPubKey,
+        })
+        .signers([payer])
+        .rpc();
+
+    console.log(`[TX] withdraw: ${tx}`);
+    const state = await program.account.gameState.fetch(gameStatePubKey)
+    console.log("Game state:", formatValue(state));
+})().then(() => console.log("Finished successfully")).catch(console.error)
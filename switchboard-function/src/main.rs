@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::str::FromStr;
 
 pub use switchboard_solana::get_ixn_discriminator;
@@ -5,6 +6,7 @@ pub use switchboard_solana::prelude::*;
 
 mod params;
 pub use params::*;
+mod scan;
 use reqwest;
 use serde::Deserialize;
 use switchboard_solana::anchor_spl::token::spl_token;
@@ -14,44 +16,220 @@ pub async fn sb_function(
     runner: FunctionRunner,
     params: Vec<u8>,
 ) -> Result<Vec<Instruction>, SbFunctionError> {
-    // parse and validate user provided request params
-    let params: ContainerParams =
-        ContainerParams::decode(&params).map_err(|_| Error::ArgParseFail)?;
-
-    let (open_price, open_expo) = get_price(params.pair.clone(), params.start_time)
-        .await
-        .map_err(|_| Error::GetPriceFail)?;
-    let (close_price, close_expo) = get_price(params.pair, params.end_time)
-        .await
-        .map_err(|_| Error::GetPriceFail)?;
-
-    if open_expo != close_expo {
-        return Err(Error::InvalidPriceExpo.into());
+    // The settlement routine finds expired bets itself and settles as many per
+    // run as fit in its transaction. Otherwise parse and validate user provided
+    // request params; a batch request settles several bets, names the batch
+    // account the program checks them against and closes it afterwards
+    let (bets, batch_key, mut close_batch) = if runner.function_routine_key.is_some() {
+        let params = RoutineParams::decode(&params).map_err(|_| Error::ArgParseFail)?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|_| Error::RpcFail)?
+            .as_secs();
+        let candidates = scan::expired_bets(&params, now)
+            .await
+            .map_err(|_| Error::RpcFail)?;
+        (scan::rotate_bets(candidates, now), None, None)
+    } else if BatchParams::is_batch(&params) {
+        let batch = BatchParams::decode(&params).map_err(|_| Error::ArgParseFail)?;
+        // A bet settled or cancelled since the request was made would fail the
+        // run, so only the batch's close still covers it.
+        let active = scan::active_bets(&batch.bets)
+            .await
+            .map_err(|_| Error::RpcFail)?;
+        let close_batch = close_batch_ixn(&batch);
+        let bets = batch
+            .bets
+            .into_iter()
+            .zip(active)
+            .filter_map(|(bet, active)| active.then_some(bet))
+            .collect();
+        (bets, Some(batch.batch_key), Some(close_batch))
+    } else {
+        let params = ContainerParams::decode(&params).map_err(|_| Error::ArgParseFail)?;
+        (vec![params], None, None)
+    };
+
+    // Settling a subscription round opens the next one at the next bet ID, which
+    // the rounds settled in this run take in turn.
+    let mut next_bet_id = match bets.iter().find(|bet| bet.subscription_key.is_some()) {
+        Some(params) => Some(
+            scan::next_bet_id(params)
+                .await
+                .map_err(|_| Error::RpcFail)?,
+        ),
+        None => None,
+    };
+
+    // Bets in a batch often share a pair and times, so each price is fetched once.
+    let mut price_cache = PriceCache::new();
+    let mut bounds_cache = BoundsCache::new();
+    let mut ixns = Vec::with_capacity(bets.len());
+    for params in &bets {
+        // Hermes being unreachable fails the run so it is retried, instead of
+        // refunding bets that can still be priced.
+        let prices = get_prices(params, &mut price_cache)
+            .await
+            .map_err(|_| Error::PriceFetchFail)?;
+        // The program rejects prices outside its bounds, which would fail every
+        // other settlement of the run, so the bet is left to be cancelled. Its batch
+        // can't be closed until then, so the keeper closes it afterwards.
+        if let Ok((open, close)) = &prices {
+            let bounds = get_cached_bounds(&mut bounds_cache, params)
+                .await
+                .map_err(|_| Error::RpcFail)?;
+            if !bounds.allow(params, open, close) {
+                close_batch = None;
+                continue;
+            }
+        }
+        let referrer_key = scan::referrer(params)
+            .await
+            .map_err(|_| Error::RpcFail)?;
+        let next_bet_key = match (params.subscription_key, next_bet_id.as_mut()) {
+            (Some(_), Some(next_bet_id)) => {
+                let next_bet_key = scan::bet_pda(params, *next_bet_id);
+                *next_bet_id += 1;
+                Some(next_bet_key)
+            }
+            _ => None,
+        };
+        ixns.push(settlement_ixn(
+            &runner,
+            params,
+            prices,
+            referrer_key,
+            next_bet_key,
+            batch_key,
+        ));
+        if runner.function_routine_key.is_some() && !fits_in_tx(&runner.payer, &ixns) {
+            ixns.pop();
+            break;
+        }
     }
+    ixns.extend(close_batch);
+
+    Ok(ixns)
+}
+
+/// Largest transaction the cluster accepts, in bytes.
+const MAX_TX_SIZE: usize = 1232;
+
+/// Bytes the runner adds to the message of the returned instructions: the
+/// payer, enclave and verifier signatures, up to ten accounts of its verify and
+/// compute budget instructions the settlements don't already name, the 104 byte
+/// verify instruction and the 20 bytes of compute budget instructions.
+const RUNNER_TX_SIZE: usize = 1 + 3 * 64 + 10 * 32 + 104 + 20;
+
+/// Whether the runner can still send the instructions in a single transaction.
+fn fits_in_tx(payer: &Pubkey, ixns: &[Instruction]) -> bool {
+    let message = solana_program::message::Message::new(ixns, Some(payer));
+    message.serialize().len() + RUNNER_TX_SIZE <= MAX_TX_SIZE
+}
+
+/// Builds the instruction closing a batch once its bets are settled, which
+/// refunds the keeper that requested it.
+fn close_batch_ixn(batch: &BatchParams) -> Instruction {
+    let program_id = batch.bets[0].program_id;
 
-    let mut bet_id_bytes = params.bet_id.to_le_bytes().to_vec();
-    let mut open_price_bytes = open_price.to_le_bytes().to_vec();
-    let mut close_price_bytes = close_price.to_le_bytes().to_vec();
+    // ACCOUNTS:
+    // 1. Settlement Batch (mut)
+    // 2. Keeper (mut): refunded the batch's rent
+    // Remaining: every bet of the batch, in the order it was requested
+    let mut accounts = vec![
+        AccountMeta::new(batch.batch_key, false),
+        AccountMeta::new(batch.keeper_key, false),
+    ];
+    accounts.extend(
+        batch
+            .bets
+            .iter()
+            .map(|bet| AccountMeta::new_readonly(bet.bet_key, false)),
+    );
+
+    Instruction {
+        program_id,
+        data: get_ixn_discriminator("close_settlement_batch").to_vec(),
+        accounts,
+    }
+}
 
-    // IXN DATA:
-    // LEN: 12 bytes
-    // Anchor Ixn Discriminator
-    // Open price as u64
-    // Open price as u64
-    let mut ixn_data = get_ixn_discriminator("settle_bet").to_vec();
-    ixn_data.append(&mut bet_id_bytes);
-    ixn_data.append(&mut open_price_bytes);
-    ixn_data.append(&mut close_price_bytes);
+/// Builds the instruction settling a bet, or reporting why it could not be priced.
+fn settlement_ixn(
+    runner: &FunctionRunner,
+    params: &ContainerParams,
+    prices: Result<(Price, Price), SettlementFailure>,
+    referrer_key: Option<Pubkey>,
+    next_bet_key: Option<Pubkey>,
+    batch_key: Option<Pubkey>,
+) -> Instruction {
+    // A bet that can't be priced is reported as failed, which refunds the bettor
+    // right away instead of after the cancel buffer.
+    let ixn_data = match prices {
+        Ok((open, close)) => {
+            // IXN DATA:
+            // Anchor Ixn Discriminator
+            // Bet ID as u64
+            // Open price, confidence and publish time as u64
+            // Close price, confidence and publish time as u64
+            // Price exponent as i32
+            let mut ixn_data = get_ixn_discriminator("settle_bet").to_vec();
+            ixn_data.extend_from_slice(&params.bet_id.to_le_bytes());
+            for price in [&open, &close] {
+                ixn_data.extend_from_slice(&price.price.to_le_bytes());
+                ixn_data.extend_from_slice(&price.conf.to_le_bytes());
+                ixn_data.extend_from_slice(&price.publish_time.to_le_bytes());
+            }
+            ixn_data.extend_from_slice(&open.expo.to_le_bytes());
+            ixn_data
+        }
+        Err(reason) => {
+            // IXN DATA:
+            // Anchor Ixn Discriminator
+            // Bet ID as u64
+            // Failure reason as u8
+            let mut ixn_data = get_ixn_discriminator("report_settlement_failure").to_vec();
+            ixn_data.extend_from_slice(&params.bet_id.to_le_bytes());
+            ixn_data.push(reason as u8);
+            ixn_data
+        }
+    };
 
-    let (state_pda, _bump) = Pubkey::find_program_address(&[b"GAME_STATE"], &params.program_id);
-    let (config_pda, _bump) = Pubkey::find_program_address(&[b"GAME_CONFIG"], &params.program_id);
+    let game_id_bytes = params.game_id.to_le_bytes();
+    let (state_pda, _bump) =
+        Pubkey::find_program_address(&[b"GAME_STATE", &game_id_bytes], &params.program_id);
+    let (config_pda, _bump) =
+        Pubkey::find_program_address(&[b"GAME_CONFIG", &game_id_bytes], &params.program_id);
+    let (pair_pda, _bump) = Pubkey::find_program_address(
+        &[b"PAIR", &game_id_bytes, params.pair.as_bytes()],
+        &params.program_id,
+    );
+    let (event_authority_pda, _bump) =
+        Pubkey::find_program_address(&[b"__event_authority"], &params.program_id);
 
     // ACCOUNTS:
     // 1. Bet (mut)
-    // 2. Switchboard Function
-    // 3. Switchboard Function Request
-    // 4. Enclave Signer (signer): our Gramine generated keypair
-    Ok(vec![Instruction {
+    // 2. Game State (mut)
+    // 3. Game Config
+    // 4. User Token Account (mut)
+    // 5. Game Escrow (mut)
+    // 6. Token Program
+    // 7. Subscription (mut, optional): the program ID stands in for a bet without one
+    // 8. Position Token Account (optional): the bettor's position token account
+    // 9. Tournament Entry (mut, optional): the entry scored by a tournament bet
+    // 10. User Profile (mut, optional): releases the bettor's open bet slot
+    // 11. Accepted Pair: bounds the price move the settlement may report
+    // 12. Referrer (mut, optional): the referrer on the bettor's profile
+    // 13. Next Bet (mut, optional): the next round a subscription round opens
+    // 14. System Program (optional): creates the next round's bet
+    // 15. Switchboard Function
+    // 16. Switchboard Function Request (optional): absent when the routine settles
+    // 17. Enclave Signer (signer): our Gramine generated keypair
+    // 18. Settlement Batch (optional): the batch a batch request settles for
+    // 19. Switchboard Function Routine (optional): the game's settlement routine
+    // 20. Event Authority: signs the CPI the settlement event is emitted through
+    // 21. Game Program
+    Instruction {
         program_id: params.program_id,
         data: ixn_data,
         accounts: vec![
@@ -60,19 +238,70 @@ pub async fn sb_function(
             AccountMeta::new_readonly(config_pda, false),
             AccountMeta::new(params.user_token_account_key, false),
             AccountMeta::new(params.escrow_key, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            match params.subscription_key {
+                Some(subscription_key) => AccountMeta::new(subscription_key, false),
+                None => AccountMeta::new_readonly(params.program_id, false),
+            },
+            AccountMeta::new_readonly(
+                params
+                    .position_token_account_key
+                    .unwrap_or(params.program_id),
+                false,
+            ),
+            match params.tournament_entry_key {
+                Some(tournament_entry_key) => AccountMeta::new(tournament_entry_key, false),
+                None => AccountMeta::new_readonly(params.program_id, false),
+            },
+            match params.user_profile_key {
+                Some(user_profile_key) => AccountMeta::new(user_profile_key, false),
+                None => AccountMeta::new_readonly(params.program_id, false),
+            },
+            AccountMeta::new_readonly(pair_pda, false),
+            match referrer_key {
+                Some(referrer_key) => AccountMeta::new(referrer_key, false),
+                None => AccountMeta::new_readonly(params.program_id, false),
+            },
+            match next_bet_key {
+                Some(next_bet_key) => AccountMeta::new(next_bet_key, false),
+                None => AccountMeta::new_readonly(params.program_id, false),
+            },
+            AccountMeta::new_readonly(
+                match next_bet_key {
+                    Some(_) => solana_program::system_program::id(),
+                    None => params.program_id,
+                },
+                false,
+            ),
             AccountMeta::new_readonly(runner.function, false),
-            AccountMeta::new_readonly(runner.function_request_key.unwrap(), false),
+            AccountMeta::new_readonly(
+                runner.function_request_key.unwrap_or(params.program_id),
+                false,
+            ),
             AccountMeta::new_readonly(runner.signer, true),
-            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(batch_key.unwrap_or(params.program_id), false),
+            AccountMeta::new_readonly(
+                runner.function_routine_key.unwrap_or(params.program_id),
+                false,
+            ),
+            AccountMeta::new_readonly(event_authority_pda, false),
+            AccountMeta::new_readonly(params.program_id, false),
         ],
-    }])
+    }
 }
 
 #[sb_error]
 pub enum Error {
     ArgParseFail,
-    GetPriceFail,
-    InvalidPriceExpo
+    RpcFail,
+    PriceFetchFail,
+}
+
+/// Mirrors `SettlementFailure` in the game program.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SettlementFailure {
+    PriceUnavailable = 0,
+    PriceExpoMismatch = 1,
 }
 
 #[derive(Deserialize)]
@@ -83,50 +312,152 @@ struct ApiResponse {
 #[derive(Deserialize)]
 struct PriceDetails {
     price: String,
+    conf: String,
     expo: i32,
+    publish_time: u64,
 }
 
-async fn get_price(pair: String, timestamp: u64) -> Result<(u64, i32), Box<dyn std::error::Error>> {
-    let client = reqwest::Client::new();
+#[derive(Clone)]
+pub struct Price {
+    pub price: u64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: u64,
+}
 
-    let asset_id = if pair.starts_with("ETHUSD") {
-        "ff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace"
-    } else if pair.starts_with("BTCUSD") {
-        "e62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43"
-    } else {
-        return Err("Unsupported pair".into());
+/// Prices already fetched in this run, keyed by feed ID and timestamp. A price
+/// Hermes has no record of is cached too so it is not retried for every bet.
+type PriceCache = HashMap<(String, u64), Option<Price>>;
+
+/// Fetches the open and close prices of the bet. The inner error is a failure
+/// to report; the outer one a transient failure to fetch the prices.
+async fn get_prices(
+    params: &ContainerParams,
+    prices: &mut PriceCache,
+) -> Result<Result<(Price, Price), SettlementFailure>, Box<dyn std::error::Error>> {
+    let open = get_cached_price(prices, &params.feed_id, params.start_time).await?;
+    let close = get_cached_price(prices, &params.feed_id, params.end_time).await?;
+
+    let (Some(open), Some(close)) = (open, close) else {
+        return Ok(Err(SettlementFailure::PriceUnavailable));
     };
+    if open.expo != close.expo {
+        return Ok(Err(SettlementFailure::PriceExpoMismatch));
+    }
+
+    Ok(Ok((open, close)))
+}
+
+/// Bounds already read in this run, keyed by pair.
+type BoundsCache = HashMap<String, scan::PriceBounds>;
+
+async fn get_cached_bounds(
+    bounds: &mut BoundsCache,
+    params: &ContainerParams,
+) -> Result<scan::PriceBounds, Box<dyn std::error::Error>> {
+    if !bounds.contains_key(&params.pair) {
+        let price_bounds = scan::price_bounds(params).await?;
+        bounds.insert(params.pair.clone(), price_bounds);
+    }
+
+    Ok(bounds[&params.pair])
+}
+
+async fn get_cached_price(
+    prices: &mut PriceCache,
+    feed_id: &str,
+    timestamp: u64,
+) -> Result<Option<Price>, Box<dyn std::error::Error>> {
+    let key = (feed_id.to_string(), timestamp);
+    if !prices.contains_key(&key) {
+        let price = get_price(feed_id, timestamp).await?;
+        prices.insert(key.clone(), price);
+    }
+
+    Ok(prices[&key].clone())
+}
+
+/// Fetches the first price published at or after `timestamp`. Hermes answering
+/// 404 means it has no such price, e.g. the timestamp is past its retention, and
+/// is the only failure returned as `None`; any other error may be transient.
+async fn get_price(
+    feed_id: &str,
+    timestamp: u64,
+) -> Result<Option<Price>, Box<dyn std::error::Error>> {
+    let client = reqwest::Client::new();
 
     let url = format!(
         "https://hermes.pyth.network/api/get_price_feed?id={}&publish_time={}",
-        asset_id, timestamp
+        feed_id, timestamp
     );
-    let resp = client.get(&url).send().await?.json::<ApiResponse>().await?;
+    let resp = client.get(&url).send().await?;
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let resp = resp.error_for_status()?.json::<ApiResponse>().await?;
 
-    // Convert price from string to float and apply exponent
-    let price = resp.price.price.parse::<u64>()?;
+    // Prices and confidences are integers scaled by 10^expo. A negative price
+    // can't be settled on and won't change on a retry.
+    let (Ok(price), Ok(conf)) = (
+        resp.price.price.parse::<u64>(),
+        resp.price.conf.parse::<u64>(),
+    ) else {
+        return Ok(None);
+    };
 
-    Ok((price, resp.price.expo))
+    Ok(Some(Price {
+        price,
+        conf,
+        expo: resp.price.expo,
+        publish_time: resp.price.publish_time,
+    }))
 }
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_fits_in_tx() {
+        let payer = Pubkey::new_unique();
+        let ixn = |data_len: usize| Instruction {
+            program_id: Pubkey::new_unique(),
+            data: vec![0; data_len],
+            accounts: vec![],
+        };
+
+        // The header, two accounts, the blockhash and an instruction with 490 bytes
+        // of data leave exactly the runner's share of the transaction.
+        assert!(fits_in_tx(&payer, &[ixn(490)]));
+        assert!(!fits_in_tx(&payer, &[ixn(491)]));
+        assert!(fits_in_tx(&payer, &[ixn(100), ixn(100)]));
+        assert!(!fits_in_tx(&payer, &[ixn(300), ixn(300)]));
+    }
+
     #[tokio::test]
     async fn test_get_price() {
         // Use a timestamp known to have data
         let timestamp = 1712644200;
 
-        let eth_price = get_price("ETHUSD".to_string(), timestamp).await;
-        let btc_price = get_price("BTCUSD".to_string(), timestamp).await;
+        let eth_price = get_price(
+            "ff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace",
+            timestamp,
+        )
+        .await;
+        let btc_price = get_price(
+            "e62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43",
+            timestamp,
+        )
+        .await;
 
         assert!(eth_price.is_ok(), "Expected Ok result, got Err");
-        let (price, expo) = eth_price.unwrap();
-        assert_eq!(price, 367977968861);
-        assert_eq!(expo, -8);
+        let price = eth_price.unwrap().expect("Expected a price");
+        assert_eq!(price.price, 367977968861);
+        assert_eq!(price.expo, -8);
+        assert!(price.publish_time >= timestamp);
         assert!(btc_price.is_ok(), "Expected Ok result, got Err");
-        let (price, expo) = btc_price.unwrap();
-        assert_eq!(price, 7114704503000);
-        assert_eq!(expo, -8);
+        let price = btc_price.unwrap().expect("Expected a price");
+        assert_eq!(price.price, 7114704503000);
+        assert_eq!(price.expo, -8);
+        assert!(price.publish_time >= timestamp);
     }
 }
@@ -2,13 +2,20 @@ use crate::*;
 
 pub struct ContainerParams {
     pub program_id: Pubkey,
+    pub game_id: u64,
     pub bet_id: u64,
     pub pair: String,
+    pub feed_id: String,
+    pub placed_time: u64,
     pub start_time: u64,
     pub end_time: u64,
     pub bet_key: Pubkey,
     pub user_token_account_key: Pubkey,
     pub escrow_key: Pubkey,
+    pub subscription_key: Option<Pubkey>,
+    pub position_token_account_key: Option<Pubkey>,
+    pub tournament_entry_key: Option<Pubkey>,
+    pub user_profile_key: Option<Pubkey>,
 }
 
 impl ContainerParams {
@@ -16,26 +23,44 @@ impl ContainerParams {
         let params = String::from_utf8(container_params.clone()).unwrap();
 
         let mut program_id: Pubkey = Pubkey::default();
+        let mut game_id: u64 = 0;
         let mut bet_id: u64 = 0;
         let mut trading_pair: String = String::default();
+        let mut feed_id: String = String::default();
+        let mut placed_time: u64 = 0;
         let mut start_time: u64 = 0;
         let mut end_time: u64 = 0;
         let mut bet_key: Pubkey = Pubkey::default();
         let mut user_token_account_key: Pubkey = Pubkey::default();
         let mut escrow_key: Pubkey = Pubkey::default();
+        let mut subscription_key: Option<Pubkey> = None;
+        let mut position_token_account_key: Option<Pubkey> = None;
+        let mut tournament_entry_key: Option<Pubkey> = None;
+        let mut user_profile_key: Option<Pubkey> = None;
 
         for env_pair in params.split(',') {
             let pair: Vec<&str> = env_pair.splitn(2, '=').collect();
             if pair.len() == 2 {
                 match pair[0] {
                     "PID" => program_id = Pubkey::from_str(pair[1]).unwrap(),
+                    "GAME_ID" => game_id = pair[1].parse::<u64>().unwrap(),
                     "BET_ID" => bet_id = pair[1].parse::<u64>().unwrap(),
                     "PAIR" => trading_pair = String::from_str(pair[1]).unwrap(),
+                    "FEED_ID" => feed_id = String::from_str(pair[1]).unwrap(),
+                    "PLACED_TIME" => placed_time = pair[1].parse::<u64>().unwrap(),
                     "START_TIME" => start_time = pair[1].parse::<u64>().unwrap(),
                     "END_TIME" => end_time = pair[1].parse::<u64>().unwrap(),
                     "BET" => bet_key = Pubkey::from_str(pair[1]).unwrap(),
                     "USER_TOKEN" => user_token_account_key = Pubkey::from_str(pair[1]).unwrap(),
                     "ESCROW" => escrow_key = Pubkey::from_str(pair[1]).unwrap(),
+                    "SUBSCRIPTION" => subscription_key = Some(Pubkey::from_str(pair[1]).unwrap()),
+                    "POSITION" => {
+                        position_token_account_key = Some(Pubkey::from_str(pair[1]).unwrap())
+                    }
+                    "TOURNAMENT_ENTRY" => {
+                        tournament_entry_key = Some(Pubkey::from_str(pair[1]).unwrap())
+                    }
+                    "USER_PROFILE" => user_profile_key = Some(Pubkey::from_str(pair[1]).unwrap()),
                     _ => {}
                 }
             }
@@ -56,9 +81,24 @@ impl ContainerParams {
                 "END_TIME must be greater than 0".to_string(),
             ));
         }
+        if start_time <= placed_time {
+            return Err(SbError::CustomMessage(
+                "START_TIME must be greater than PLACED_TIME".to_string(),
+            ));
+        }
+        if end_time <= start_time {
+            return Err(SbError::CustomMessage(
+                "END_TIME must be greater than START_TIME".to_string(),
+            ));
+        }
         if trading_pair == "" {
             return Err(SbError::CustomMessage("PAIR cannot be empty".to_string()));
         }
+        if feed_id.len() != 64 || !feed_id.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(SbError::CustomMessage(
+                "FEED_ID must be a 32 byte hex string".to_string(),
+            ));
+        }
         if bet_key == Pubkey::default() {
             return Err(SbError::CustomMessage(
                 "BET cannot be undefined".to_string(),
@@ -67,13 +107,123 @@ impl ContainerParams {
 
         Ok(Self {
             program_id,
+            game_id,
             bet_id,
             pair: trading_pair,
+            feed_id,
+            placed_time,
             start_time,
             end_time,
             bet_key,
             user_token_account_key,
             escrow_key,
+            subscription_key,
+            position_token_account_key,
+            tournament_entry_key,
+            user_profile_key,
+        })
+    }
+}
+
+/// Params of the settlement routine, which finds the bets to settle itself.
+pub struct RoutineParams {
+    pub program_id: Pubkey,
+    pub game_id: u64,
+    pub escrow_key: Pubkey,
+}
+
+impl RoutineParams {
+    pub fn decode(container_params: &Vec<u8>) -> std::result::Result<Self, SbError> {
+        let params = String::from_utf8(container_params.clone()).unwrap();
+
+        let mut program_id: Pubkey = Pubkey::default();
+        let mut game_id: u64 = 0;
+        let mut escrow_key: Pubkey = Pubkey::default();
+
+        for env_pair in params.split(',') {
+            let pair: Vec<&str> = env_pair.splitn(2, '=').collect();
+            if pair.len() == 2 {
+                match pair[0] {
+                    "PID" => program_id = Pubkey::from_str(pair[1]).unwrap(),
+                    "GAME_ID" => game_id = pair[1].parse::<u64>().unwrap(),
+                    "ESCROW" => escrow_key = Pubkey::from_str(pair[1]).unwrap(),
+                    _ => {}
+                }
+            }
+        }
+
+        if program_id == Pubkey::default() {
+            return Err(SbError::CustomMessage(
+                "PID cannot be undefined".to_string(),
+            ));
+        }
+        if escrow_key == Pubkey::default() {
+            return Err(SbError::CustomMessage(
+                "ESCROW cannot be undefined".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            program_id,
+            game_id,
+            escrow_key,
+        })
+    }
+}
+
+/// Params of a batch request: the shared params, then each bet's params, all
+/// separated by ';'.
+pub struct BatchParams {
+    pub batch_key: Pubkey,
+    pub keeper_key: Pubkey,
+    pub bets: Vec<ContainerParams>,
+}
+
+impl BatchParams {
+    pub fn is_batch(container_params: &Vec<u8>) -> bool {
+        container_params.contains(&b';')
+    }
+
+    pub fn decode(container_params: &Vec<u8>) -> std::result::Result<Self, SbError> {
+        let params = String::from_utf8(container_params.clone()).unwrap();
+        let mut segments = params.split(';');
+        let shared = segments.next().unwrap_or_default();
+
+        let mut batch_key: Pubkey = Pubkey::default();
+        let mut keeper_key: Pubkey = Pubkey::default();
+        for env_pair in shared.split(',') {
+            let pair: Vec<&str> = env_pair.splitn(2, '=').collect();
+            if pair.len() == 2 {
+                match pair[0] {
+                    "BATCH" => batch_key = Pubkey::from_str(pair[1]).unwrap(),
+                    "KEEPER" => keeper_key = Pubkey::from_str(pair[1]).unwrap(),
+                    _ => {}
+                }
+            }
+        }
+
+        if batch_key == Pubkey::default() {
+            return Err(SbError::CustomMessage(
+                "BATCH cannot be undefined".to_string(),
+            ));
+        }
+        if keeper_key == Pubkey::default() {
+            return Err(SbError::CustomMessage(
+                "KEEPER cannot be undefined".to_string(),
+            ));
+        }
+
+        let bets = segments
+            .map(|bet| ContainerParams::decode(&format!("{},{}", shared, bet).into_bytes()))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        if bets.is_empty() {
+            return Err(SbError::CustomMessage("BATCH cannot be empty".to_string()));
+        }
+
+        Ok(Self {
+            batch_key,
+            keeper_key,
+            bets,
         })
     }
 }
@@ -82,30 +232,173 @@ impl ContainerParams {
 mod tests {
     use super::*;
 
+    const BTC_FEED_ID: &str = "e62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43";
+
     #[test]
     fn test_params_decode() {
         let request_params_string = format!(
-            "PID={},BET_ID={},PAIR={},START_TIME={},END_TIME={},BET={},USER_TOKEN={},ESCROW={}",
+            "PID={},GAME_ID={},BET_ID={},PAIR={},FEED_ID={},PLACED_TIME={},START_TIME={},END_TIME={},BET={},USER_TOKEN={},ESCROW={},POSITION={},USER_PROFILE={}",
             anchor_spl::token::ID,
+            3,
             0,
             "BTCUSDXX",
+            BTC_FEED_ID,
             1,
+            2,
             6,
             anchor_spl::token::ID,
             anchor_spl::token::ID,
             anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
         );
         let request_params_bytes = request_params_string.into_bytes();
 
         let params = ContainerParams::decode(&request_params_bytes).unwrap();
 
         assert_eq!(params.program_id, anchor_spl::token::ID);
+        assert_eq!(params.game_id, 3);
         assert_eq!(params.bet_id, 0);
         assert_eq!(params.pair, "BTCUSDXX");
-        assert_eq!(params.start_time, 1);
+        assert_eq!(params.feed_id, BTC_FEED_ID);
+        assert_eq!(params.placed_time, 1);
+        assert_eq!(params.start_time, 2);
         assert_eq!(params.end_time, 6);
         assert_eq!(params.bet_key, anchor_spl::token::ID);
         assert_eq!(params.user_token_account_key, anchor_spl::token::ID);
         assert_eq!(params.escrow_key, anchor_spl::token::ID);
+        assert_eq!(params.subscription_key, None);
+        assert_eq!(params.position_token_account_key, Some(anchor_spl::token::ID));
+        assert_eq!(params.tournament_entry_key, None);
+        assert_eq!(params.user_profile_key, Some(anchor_spl::token::ID));
+    }
+
+    #[test]
+    fn test_params_decode_subscription() {
+        let request_params_string = format!(
+            "PID={},BET_ID={},PAIR={},FEED_ID={},PLACED_TIME={},START_TIME={},END_TIME={},BET={},USER_TOKEN={},ESCROW={},SUBSCRIPTION={}",
+            anchor_spl::token::ID,
+            0,
+            "BTCUSDXX",
+            BTC_FEED_ID,
+            1,
+            2,
+            6,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+        );
+        let request_params_bytes = request_params_string.into_bytes();
+
+        let params = ContainerParams::decode(&request_params_bytes).unwrap();
+
+        assert_eq!(params.game_id, 0);
+        assert_eq!(params.subscription_key, Some(anchor_spl::token::ID));
+        assert_eq!(params.position_token_account_key, None);
+        assert_eq!(params.user_profile_key, None);
+    }
+
+    #[test]
+    fn test_params_decode_invalid_feed_id() {
+        let request_params_string = format!(
+            "PID={},BET_ID={},PAIR={},FEED_ID={},PLACED_TIME={},START_TIME={},END_TIME={},BET={},USER_TOKEN={},ESCROW={}",
+            anchor_spl::token::ID,
+            0,
+            "BTCUSDXX",
+            "e62df6c8",
+            1,
+            2,
+            6,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+        );
+        let request_params_bytes = request_params_string.into_bytes();
+
+        assert!(ContainerParams::decode(&request_params_bytes).is_err());
+    }
+
+    #[test]
+    fn test_params_decode_start_not_after_placement() {
+        let request_params_string = format!(
+            "PID={},BET_ID={},PAIR={},FEED_ID={},PLACED_TIME={},START_TIME={},END_TIME={},BET={},USER_TOKEN={},ESCROW={}",
+            anchor_spl::token::ID,
+            0,
+            "BTCUSDXX",
+            BTC_FEED_ID,
+            2,
+            2,
+            6,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+        );
+        let request_params_bytes = request_params_string.into_bytes();
+
+        assert!(ContainerParams::decode(&request_params_bytes).is_err());
+    }
+
+    #[test]
+    fn test_batch_params_decode() {
+        let request_params_string = format!(
+            "PID={},GAME_ID={},ESCROW={},BATCH={},KEEPER={};BET_ID={},PAIR={},FEED_ID={},START_TIME={},END_TIME={},BET={},USER_TOKEN={},POSITION={};BET_ID={},PAIR={},FEED_ID={},START_TIME={},END_TIME={},BET={},USER_TOKEN={},SUBSCRIPTION={}",
+            anchor_spl::token::ID,
+            3,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            4,
+            "BTCUSDXX",
+            BTC_FEED_ID,
+            2,
+            6,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            5,
+            "BTCUSDXX",
+            BTC_FEED_ID,
+            3,
+            7,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+        );
+        let request_params_bytes = request_params_string.into_bytes();
+
+        assert!(BatchParams::is_batch(&request_params_bytes));
+        let params = BatchParams::decode(&request_params_bytes).unwrap();
+
+        assert_eq!(params.batch_key, anchor_spl::token::ID);
+        assert_eq!(params.keeper_key, anchor_spl::token::ID);
+        assert_eq!(params.bets.len(), 2);
+        assert_eq!(params.bets[0].game_id, 3);
+        assert_eq!(params.bets[0].bet_id, 4);
+        assert_eq!(params.bets[0].escrow_key, anchor_spl::token::ID);
+        assert_eq!(params.bets[0].position_token_account_key, Some(anchor_spl::token::ID));
+        assert_eq!(params.bets[0].subscription_key, None);
+        assert_eq!(params.bets[1].bet_id, 5);
+        assert_eq!(params.bets[1].end_time, 7);
+        assert_eq!(params.bets[1].position_token_account_key, None);
+        assert_eq!(params.bets[1].subscription_key, Some(anchor_spl::token::ID));
+    }
+
+    #[test]
+    fn test_routine_params_decode() {
+        let request_params_string = format!(
+            "PID={},GAME_ID={},ESCROW={}",
+            anchor_spl::token::ID,
+            3,
+            anchor_spl::token::ID,
+        );
+        let request_params_bytes = request_params_string.into_bytes();
+
+        let params = RoutineParams::decode(&request_params_bytes).unwrap();
+
+        assert_eq!(params.program_id, anchor_spl::token::ID);
+        assert_eq!(params.game_id, 3);
+        assert_eq!(params.escrow_key, anchor_spl::token::ID);
+        assert!(RoutineParams::decode(&format!("PID={}", anchor_spl::token::ID).into_bytes()).is_err());
     }
 }
@@ -0,0 +1,633 @@
+use crate::*;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{json, Value};
+use switchboard_solana::anchor_spl::associated_token::get_associated_token_address;
+
+/// Oldest expired bets the routine takes turns settling.
+pub const MAX_ROUTINE_CANDIDATES: usize = 16;
+
+/// Basis points in a whole, as the game program bounds price moves in.
+const MAX_BPS: u128 = 10_000;
+
+// Offsets into the game program's zero copy accounts, past the 8 byte
+// discriminator. The structs are packed.
+const BET_LEN: usize = 8 + 426;
+const BET_BUMP: usize = 8;
+const BET_ID: usize = 9;
+const BET_START_TIME: usize = 33;
+const BET_END_TIME: usize = 41;
+const BET_USER: usize = 65;
+const BET_USER_TOKEN_ACCOUNT: usize = 97;
+const BET_PAIR: usize = 129;
+const BET_ACTIVE: usize = 138;
+const BET_SUBSCRIPTION: usize = 171;
+const BET_POSITION_MINT: usize = 203;
+const BET_TOURNAMENT_ENTRY: usize = 275;
+const BET_USER_PROFILE: usize = 307;
+const PAIR_FEED_ID: usize = 25;
+const PAIR_MAX_MOVE_BPS: usize = 57;
+const PAIR_PULL_ORACLE: usize = 65;
+const CONFIG_MAX_PUBLISH_DELAY: usize = 351;
+const USER_PROFILE_REFERRER: usize = 41;
+const GAME_STATE_NEXT_BET_ID: usize = 25;
+
+/// Finds active bets of the game that have ended and settle through this
+/// function, oldest first.
+pub async fn expired_bets(
+    params: &RoutineParams,
+    now: u64,
+) -> Result<Vec<ContainerParams>, Box<dyn std::error::Error>> {
+    let rpc_url = rpc_url();
+    let client = reqwest::Client::new();
+
+    let bets = get_program_accounts(
+        &client,
+        &rpc_url,
+        &params.program_id,
+        json!([
+            { "dataSize": BET_LEN },
+            { "memcmp": { "offset": 0, "bytes": STANDARD.encode(discriminator("Bet")), "encoding": "base64" } },
+            { "memcmp": { "offset": BET_ACTIVE, "bytes": STANDARD.encode([1u8]), "encoding": "base64" } },
+        ]),
+    )
+    .await?;
+
+    let mut pairs: Vec<[u8; 8]> = bets
+        .iter()
+        .filter_map(|(_, data)| read_pair(data))
+        .collect();
+    pairs.sort();
+    pairs.dedup();
+    let pair_keys: Vec<Pubkey> = pairs
+        .iter()
+        .map(|pair| pair_pda(params, pair))
+        .collect();
+    let pair_accounts = get_multiple_accounts(&client, &rpc_url, &pair_keys).await?;
+    let pair_accounts: HashMap<[u8; 8], Vec<u8>> = pairs
+        .into_iter()
+        .zip(pair_accounts)
+        .filter_map(|(pair, data)| Some((pair, data?)))
+        .collect();
+
+    Ok(select_bets(params, now, &bets, &pair_accounts))
+}
+
+/// Picks up to `MAX_ROUTINE_CANDIDATES` bets to settle, oldest first. A bet of
+/// another game, one still running, or one on a pull oracle market is skipped,
+/// as its settlement would fail.
+pub fn select_bets(
+    params: &RoutineParams,
+    now: u64,
+    bets: &[(Pubkey, Vec<u8>)],
+    pair_accounts: &HashMap<[u8; 8], Vec<u8>>,
+) -> Vec<ContainerParams> {
+    let mut selected: Vec<ContainerParams> = bets
+        .iter()
+        .filter_map(|(bet_key, data)| {
+            if data.len() != BET_LEN || data[BET_ACTIVE] != 1 {
+                return None;
+            }
+
+            let bet_id = read_u64(data, BET_ID);
+            let bet_pda = Pubkey::create_program_address(
+                &[
+                    b"BET",
+                    &params.game_id.to_le_bytes(),
+                    &bet_id.to_le_bytes(),
+                    &[data[BET_BUMP]],
+                ],
+                &params.program_id,
+            )
+            .ok()?;
+            let end_time = read_u64(data, BET_END_TIME);
+            if bet_pda != *bet_key || end_time >= now {
+                return None;
+            }
+
+            let pair = read_pair(data)?;
+            let pair_account = pair_accounts.get(&pair)?;
+            if pair_account.len() <= PAIR_PULL_ORACLE || pair_account[PAIR_PULL_ORACLE] != 0 {
+                return None;
+            }
+            let feed_id = pair_account[PAIR_FEED_ID..PAIR_FEED_ID + 32]
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+
+            let position_mint = read_key(data, BET_POSITION_MINT);
+            Some(ContainerParams {
+                program_id: params.program_id,
+                game_id: params.game_id,
+                bet_id,
+                pair: String::from_utf8(pair.to_vec()).ok()?,
+                feed_id,
+                placed_time: 0,
+                start_time: read_u64(data, BET_START_TIME),
+                end_time,
+                bet_key: *bet_key,
+                user_token_account_key: read_key(data, BET_USER_TOKEN_ACCOUNT),
+                escrow_key: params.escrow_key,
+                subscription_key: read_optional_key(data, BET_SUBSCRIPTION),
+                position_token_account_key: read_optional_key(data, BET_POSITION_MINT).map(|_| {
+                    get_associated_token_address(&read_key(data, BET_USER), &position_mint)
+                }),
+                tournament_entry_key: read_optional_key(data, BET_TOURNAMENT_ENTRY),
+                user_profile_key: read_optional_key(data, BET_USER_PROFILE),
+            })
+        })
+        .collect();
+
+    selected.sort_by_key(|bet| (bet.end_time, bet.bet_id));
+    selected.truncate(MAX_ROUTINE_CANDIDATES);
+    selected
+}
+
+/// Orders the candidates a routine run settles. Runs take turns over which bet
+/// comes first, so a bet whose settlement keeps failing only fails some runs
+/// instead of blocking every other bet of the game.
+pub fn rotate_bets(mut candidates: Vec<ContainerParams>, now: u64) -> Vec<ContainerParams> {
+    if !candidates.is_empty() {
+        let turn = (now % candidates.len() as u64) as usize;
+        candidates.rotate_left(turn);
+    }
+    candidates
+}
+
+/// Bounds the game program holds a bet's settlement prices to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PriceBounds {
+    pub max_move_bps: u32,
+    pub max_publish_delay: u32,
+}
+
+impl PriceBounds {
+    /// Mirrors `AcceptedPair::validate_prices` and
+    /// `GameConfig::validate_publish_times`; a settlement outside them fails.
+    pub fn allow(&self, params: &ContainerParams, open: &Price, close: &Price) -> bool {
+        if open.price == 0 || close.price == 0 || i8::try_from(open.expo).is_err() {
+            return false;
+        }
+
+        let price_move = open.price.abs_diff(close.price) as u128;
+        if self.max_move_bps != 0
+            && price_move * MAX_BPS > open.price as u128 * self.max_move_bps as u128
+        {
+            return false;
+        }
+
+        let max_publish_delay = self.max_publish_delay as u64;
+        open.publish_time.abs_diff(params.start_time) <= max_publish_delay
+            && close.publish_time.abs_diff(params.end_time) <= max_publish_delay
+    }
+}
+
+/// Reads the bounds of the bet's market and game.
+pub async fn price_bounds(
+    params: &ContainerParams,
+) -> Result<PriceBounds, Box<dyn std::error::Error>> {
+    let game_id_bytes = params.game_id.to_le_bytes();
+    let (config_key, _bump) =
+        Pubkey::find_program_address(&[b"GAME_CONFIG", &game_id_bytes], &params.program_id);
+    let (pair_key, _bump) = Pubkey::find_program_address(
+        &[b"PAIR", &game_id_bytes, params.pair.as_bytes()],
+        &params.program_id,
+    );
+
+    let client = reqwest::Client::new();
+    let accounts = get_multiple_accounts(&client, &rpc_url(), &[config_key, pair_key]).await?;
+    let (Some(Some(config)), Some(Some(pair))) = (accounts.first(), accounts.get(1)) else {
+        return Err("game config or pair not found".into());
+    };
+    if config.len() < CONFIG_MAX_PUBLISH_DELAY + 4 || pair.len() < PAIR_MAX_MOVE_BPS + 4 {
+        return Err("game config or pair too short".into());
+    }
+
+    Ok(PriceBounds {
+        max_move_bps: read_u32(pair, PAIR_MAX_MOVE_BPS),
+        max_publish_delay: read_u32(config, CONFIG_MAX_PUBLISH_DELAY),
+    })
+}
+
+/// Reads whether each bet is still active, i.e. not settled or cancelled yet.
+pub async fn active_bets(
+    bets: &[ContainerParams],
+) -> Result<Vec<bool>, Box<dyn std::error::Error>> {
+    let client = reqwest::Client::new();
+    let keys: Vec<Pubkey> = bets.iter().map(|bet| bet.bet_key).collect();
+    let accounts = get_multiple_accounts(&client, &rpc_url(), &keys).await?;
+
+    Ok(accounts
+        .iter()
+        .map(|data| matches!(data, Some(data) if data.len() > BET_ACTIVE && data[BET_ACTIVE] == 1))
+        .collect())
+}
+
+/// Reads the referrer on the bettor's profile, which a lost bet rewards.
+pub async fn referrer(
+    params: &ContainerParams,
+) -> Result<Option<Pubkey>, Box<dyn std::error::Error>> {
+    let Some(user_profile_key) = params.user_profile_key else {
+        return Ok(None);
+    };
+
+    let client = reqwest::Client::new();
+    let accounts = get_multiple_accounts(&client, &rpc_url(), &[user_profile_key]).await?;
+    let data = accounts
+        .into_iter()
+        .next()
+        .flatten()
+        .ok_or("user profile not found")?;
+    if data.len() < USER_PROFILE_REFERRER + 32 {
+        return Err("user profile too short".into());
+    }
+
+    Ok(read_optional_key(&data, USER_PROFILE_REFERRER))
+}
+
+/// Reads the ID the game gives its next bet.
+pub async fn next_bet_id(params: &ContainerParams) -> Result<u64, Box<dyn std::error::Error>> {
+    let (state_key, _bump) = Pubkey::find_program_address(
+        &[b"GAME_STATE", &params.game_id.to_le_bytes()],
+        &params.program_id,
+    );
+
+    let client = reqwest::Client::new();
+    let accounts = get_multiple_accounts(&client, &rpc_url(), &[state_key]).await?;
+    let data = accounts
+        .into_iter()
+        .next()
+        .flatten()
+        .ok_or("game state not found")?;
+    if data.len() < GAME_STATE_NEXT_BET_ID + 8 {
+        return Err("game state too short".into());
+    }
+
+    Ok(read_u64(&data, GAME_STATE_NEXT_BET_ID))
+}
+
+/// The address of the bet `bet_id` of the game.
+pub fn bet_pda(params: &ContainerParams, bet_id: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"BET", &params.game_id.to_le_bytes(), &bet_id.to_le_bytes()],
+        &params.program_id,
+    )
+    .0
+}
+
+fn rpc_url() -> String {
+    std::env::var("RPC_URL").unwrap_or("https://api.devnet.solana.com".to_string())
+}
+
+fn pair_pda(params: &RoutineParams, pair: &[u8; 8]) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"PAIR", &params.game_id.to_le_bytes(), pair],
+        &params.program_id,
+    )
+    .0
+}
+
+fn discriminator(account: &str) -> [u8; 8] {
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(
+        &solana_program::hash::hash(format!("account:{}", account).as_bytes()).to_bytes()[..8],
+    );
+    discriminator
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn read_key(data: &[u8], offset: usize) -> Pubkey {
+    Pubkey::new_from_array(data[offset..offset + 32].try_into().unwrap())
+}
+
+/// Optional bet fields are stored as the default pubkey when unset.
+fn read_optional_key(data: &[u8], offset: usize) -> Option<Pubkey> {
+    Some(read_key(data, offset)).filter(|key| *key != Pubkey::default())
+}
+
+fn read_pair(data: &[u8]) -> Option<[u8; 8]> {
+    data.get(BET_PAIR..BET_PAIR + 8)?.try_into().ok()
+}
+
+async fn rpc_call(
+    client: &reqwest::Client,
+    rpc_url: &str,
+    method: &str,
+    params: Value,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    let mut resp = client.post(rpc_url).json(&body).send().await?.json::<Value>().await?;
+    match resp.get_mut("result") {
+        Some(result) => Ok(result.take()),
+        None => Err(format!("{} failed: {}", method, resp["error"]).into()),
+    }
+}
+
+fn decode_data(account: &Value) -> Option<Vec<u8>> {
+    STANDARD.decode(account["data"][0].as_str()?).ok()
+}
+
+async fn get_program_accounts(
+    client: &reqwest::Client,
+    rpc_url: &str,
+    program_id: &Pubkey,
+    filters: Value,
+) -> Result<Vec<(Pubkey, Vec<u8>)>, Box<dyn std::error::Error>> {
+    let result = rpc_call(
+        client,
+        rpc_url,
+        "getProgramAccounts",
+        json!([program_id.to_string(), { "encoding": "base64", "filters": filters }]),
+    )
+    .await?;
+
+    let mut accounts = Vec::new();
+    for account in result.as_array().ok_or("getProgramAccounts returned no accounts")? {
+        let key = Pubkey::from_str(account["pubkey"].as_str().unwrap_or_default())?;
+        if let Some(data) = decode_data(&account["account"]) {
+            accounts.push((key, data));
+        }
+    }
+    Ok(accounts)
+}
+
+async fn get_multiple_accounts(
+    client: &reqwest::Client,
+    rpc_url: &str,
+    keys: &[Pubkey],
+) -> Result<Vec<Option<Vec<u8>>>, Box<dyn std::error::Error>> {
+    if keys.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+    let result = rpc_call(
+        client,
+        rpc_url,
+        "getMultipleAccounts",
+        json!([keys, { "encoding": "base64" }]),
+    )
+    .await?;
+
+    Ok(result["value"]
+        .as_array()
+        .ok_or("getMultipleAccounts returned no accounts")?
+        .iter()
+        .map(decode_data)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BTC_FEED_ID: &str = "e62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43";
+
+    fn bet_account(params: &RoutineParams, bet_id: u64, end_time: u64) -> (Pubkey, Vec<u8>) {
+        let (bet_key, bump) = Pubkey::find_program_address(
+            &[b"BET", &params.game_id.to_le_bytes(), &bet_id.to_le_bytes()],
+            &params.program_id,
+        );
+        let mut data = vec![0u8; BET_LEN];
+        data[..8].copy_from_slice(&discriminator("Bet"));
+        data[BET_BUMP] = bump;
+        data[BET_ID..BET_ID + 8].copy_from_slice(&bet_id.to_le_bytes());
+        data[BET_START_TIME..BET_START_TIME + 8].copy_from_slice(&(end_time - 60).to_le_bytes());
+        data[BET_END_TIME..BET_END_TIME + 8].copy_from_slice(&end_time.to_le_bytes());
+        data[BET_USER..BET_USER + 32].copy_from_slice(anchor_spl::token::ID.as_ref());
+        data[BET_PAIR..BET_PAIR + 8].copy_from_slice(b"BTCUSDXX");
+        data[BET_ACTIVE] = 1;
+        data[BET_POSITION_MINT..BET_POSITION_MINT + 32]
+            .copy_from_slice(anchor_spl::token::ID.as_ref());
+        (bet_key, data)
+    }
+
+    #[test]
+    fn test_select_bets() {
+        let params = RoutineParams {
+            program_id: anchor_spl::token::ID,
+            game_id: 3,
+            escrow_key: anchor_spl::token::ID,
+        };
+        let mut pair_account = vec![0u8; PAIR_PULL_ORACLE + 1];
+        for (i, byte) in pair_account[PAIR_FEED_ID..PAIR_FEED_ID + 32].iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&BTC_FEED_ID[i * 2..i * 2 + 2], 16).unwrap();
+        }
+        let pair_accounts = HashMap::from([(*b"BTCUSDXX", pair_account)]);
+
+        let mut inactive = bet_account(&params, 4, 90);
+        inactive.1[BET_ACTIVE] = 0;
+        let mut other_game = bet_account(&params, 5, 90);
+        other_game.0 = anchor_spl::token::ID;
+        let bets = vec![
+            bet_account(&params, 1, 95),
+            bet_account(&params, 2, 100),
+            bet_account(&params, 3, 80),
+            inactive,
+            other_game,
+            bet_account(&params, 6, 70),
+            bet_account(&params, 7, 85),
+        ];
+
+        let selected = select_bets(&params, 100, &bets, &pair_accounts);
+
+        let bet_ids: Vec<u64> = selected.iter().map(|bet| bet.bet_id).collect();
+        assert_eq!(bet_ids, vec![6, 3, 7, 1]);
+        assert_eq!(selected[0].feed_id, BTC_FEED_ID);
+        assert_eq!(selected[0].pair, "BTCUSDXX");
+        assert_eq!(selected[0].start_time, 10);
+        assert_eq!(selected[0].subscription_key, None);
+        assert!(selected[0].position_token_account_key.is_some());
+
+        let mut pull_oracle = pair_accounts.clone();
+        pull_oracle.get_mut(b"BTCUSDXX").unwrap()[PAIR_PULL_ORACLE] = 1;
+        assert!(select_bets(&params, 100, &bets, &pull_oracle).is_empty());
+    }
+
+    #[test]
+    fn test_rotate_bets() {
+        let params = RoutineParams {
+            program_id: anchor_spl::token::ID,
+            game_id: 3,
+            escrow_key: anchor_spl::token::ID,
+        };
+        let pair_accounts = HashMap::from([(*b"BTCUSDXX", vec![0u8; PAIR_PULL_ORACLE + 1])]);
+        let bets = vec![
+            bet_account(&params, 1, 70),
+            bet_account(&params, 2, 80),
+            bet_account(&params, 3, 90),
+        ];
+
+        // Consecutive runs start at different bets, so none can block the others.
+        let rotated: Vec<Vec<u64>> = (100..103)
+            .map(|now| {
+                let candidates = select_bets(&params, 100, &bets, &pair_accounts);
+                rotate_bets(candidates, now)
+                    .iter()
+                    .map(|bet| bet.bet_id)
+                    .collect()
+            })
+            .collect();
+        assert_eq!(rotated, vec![vec![2, 3, 1], vec![3, 1, 2], vec![1, 2, 3]]);
+        assert!(rotate_bets(Vec::new(), 100).is_empty());
+    }
+
+    #[test]
+    fn test_price_bounds() {
+        let params = RoutineParams {
+            program_id: anchor_spl::token::ID,
+            game_id: 3,
+            escrow_key: anchor_spl::token::ID,
+        };
+        let pair_accounts = HashMap::from([(*b"BTCUSDXX", vec![0u8; PAIR_PULL_ORACLE + 1])]);
+        let bet = select_bets(&params, 100, &[bet_account(&params, 1, 90)], &pair_accounts)
+            .remove(0);
+        let bounds = PriceBounds {
+            max_move_bps: 1_000,
+            max_publish_delay: 5,
+        };
+        let price = |price: u64, publish_time: u64| Price {
+            price,
+            conf: 0,
+            expo: -8,
+            publish_time,
+        };
+
+        assert!(bounds.allow(&bet, &price(100_000, 30), &price(110_000, 90)));
+        assert!(bounds.allow(&bet, &price(100_000, 35), &price(90_000, 85)));
+        // Moved too far, priced at zero or published too long before or after
+        assert!(!bounds.allow(&bet, &price(100_000, 30), &price(110_001, 90)));
+        assert!(!bounds.allow(&bet, &price(0, 30), &price(0, 90)));
+        assert!(!bounds.allow(&bet, &price(100_000, 36), &price(100_000, 90)));
+        assert!(!bounds.allow(&bet, &price(100_000, 30), &price(100_000, 84)));
+        // An unbounded market only rejects zero prices
+        let unbounded = PriceBounds {
+            max_move_bps: 0,
+            ..bounds
+        };
+        assert!(unbounded.allow(&bet, &price(100_000, 30), &price(1, 90)));
+    }
+
+    /// Field sizes of the game program's accounts, in declaration order.
+    const BET_FIELDS: &[(&str, usize)] = &[
+        ("bump", 1),
+        ("bet_id", 8),
+        ("amount", 8),
+        ("payout", 8),
+        ("start_time", 8),
+        ("end_time", 8),
+        ("open_price", 8),
+        ("close_price", 8),
+        ("user", 32),
+        ("user_token_account", 32),
+        ("pair", 8),
+        ("is_long", 1),
+        ("active", 1),
+        ("switchboard_request", 32),
+        ("subscription", 32),
+        ("position_mint", 32),
+        ("claimable", 8),
+        ("voucher", 32),
+        ("tournament_entry", 32),
+        ("user_profile", 32),
+        ("version", 1),
+        ("disputable_until", 8),
+        ("price_expo", 1),
+        ("open_conf", 8),
+        ("close_conf", 8),
+        ("open_publish_offset", 2),
+        ("close_publish_offset", 2),
+        ("position_redeemed", 1),
+        ("reserved", 64),
+    ];
+    const ACCEPTED_PAIR_FIELDS: &[(&str, usize)] = &[
+        ("bump", 1),
+        ("game_id", 8),
+        ("pair", 8),
+        ("feed_id", 32),
+        ("max_move_bps", 4),
+        ("max_conf_bps", 4),
+        ("pull_oracle", 1),
+        ("disabled", 1),
+    ];
+    const GAME_CONFIG_FIELDS: &[(&str, usize)] = &[
+        ("bump", 1),
+        ("game_id", 8),
+        ("authority", 32),
+        ("min_bet", 8),
+        ("max_bet", 8),
+        ("max_utilized_liquidity", 8),
+        ("cancel_buffer", 8),
+        ("max_interval", 4),
+        ("min_interval", 4),
+        ("min_entry_delay", 4),
+        ("max_entry_delay", 4),
+        ("bet_cooldown", 4),
+        ("max_open_bets", 4),
+        ("leverage", 2),
+        ("referral_share_bps", 2),
+        ("switchboard_function", 32),
+        ("token_mint", 32),
+        ("game_escrow", 32),
+        ("allowlist_root", 32),
+        ("version", 1),
+        ("config_change_delay", 4),
+        ("paused", 1),
+        ("pauser", 32),
+        ("risk_manager", 32),
+        ("treasurer", 32),
+        ("dispute_threshold", 8),
+        ("dispute_window", 4),
+        ("max_publish_delay", 4),
+    ];
+    const GAME_STATE_FIELDS: &[(&str, usize)] = &[
+        ("bump", 1),
+        ("game_id", 8),
+        ("locked_liquidity", 8),
+        ("next_bet_id", 8),
+    ];
+    const USER_PROFILE_FIELDS: &[(&str, usize)] = &[
+        ("bump", 1),
+        ("user", 32),
+        ("referrer", 32),
+    ];
+
+    /// The offset of `field` past the discriminator, as the packed struct lays it out.
+    fn offset_of(fields: &[(&str, usize)], field: &str) -> usize {
+        let index = fields.iter().position(|(name, _)| *name == field).unwrap();
+        8 + fields[..index].iter().map(|(_, size)| size).sum::<usize>()
+    }
+
+    #[test]
+    fn test_layout_offsets() {
+        assert_eq!(BET_LEN, 8 + BET_FIELDS.iter().map(|(_, size)| size).sum::<usize>());
+        assert_eq!(BET_BUMP, offset_of(BET_FIELDS, "bump"));
+        assert_eq!(BET_ID, offset_of(BET_FIELDS, "bet_id"));
+        assert_eq!(BET_START_TIME, offset_of(BET_FIELDS, "start_time"));
+        assert_eq!(BET_END_TIME, offset_of(BET_FIELDS, "end_time"));
+        assert_eq!(BET_USER, offset_of(BET_FIELDS, "user"));
+        assert_eq!(BET_USER_TOKEN_ACCOUNT, offset_of(BET_FIELDS, "user_token_account"));
+        assert_eq!(BET_PAIR, offset_of(BET_FIELDS, "pair"));
+        assert_eq!(BET_ACTIVE, offset_of(BET_FIELDS, "active"));
+        assert_eq!(BET_SUBSCRIPTION, offset_of(BET_FIELDS, "subscription"));
+        assert_eq!(BET_POSITION_MINT, offset_of(BET_FIELDS, "position_mint"));
+        assert_eq!(BET_TOURNAMENT_ENTRY, offset_of(BET_FIELDS, "tournament_entry"));
+        assert_eq!(BET_USER_PROFILE, offset_of(BET_FIELDS, "user_profile"));
+        assert_eq!(PAIR_FEED_ID, offset_of(ACCEPTED_PAIR_FIELDS, "feed_id"));
+        assert_eq!(PAIR_MAX_MOVE_BPS, offset_of(ACCEPTED_PAIR_FIELDS, "max_move_bps"));
+        assert_eq!(PAIR_PULL_ORACLE, offset_of(ACCEPTED_PAIR_FIELDS, "pull_oracle"));
+        assert_eq!(
+            CONFIG_MAX_PUBLISH_DELAY,
+            offset_of(GAME_CONFIG_FIELDS, "max_publish_delay")
+        );
+        assert_eq!(GAME_STATE_NEXT_BET_ID, offset_of(GAME_STATE_FIELDS, "next_bet_id"));
+        assert_eq!(USER_PROFILE_REFERRER, offset_of(USER_PROFILE_FIELDS, "referrer"));
+    }
+}
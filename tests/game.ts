@@ -1,16 +1,3456 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
-import { Game } from "../target/types/game";
+import { keccak_256 } from "@noble/hashes/sha3";
+import {
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  Signer,
+  SystemProgram,
+  Transaction,
+  TransactionInstruction,
+  VersionedTransaction,
+} from "@solana/web3.js";
+import {
+  AccountLayout,
+  MINT_SIZE,
+  MintLayout,
+  NATIVE_MINT,
+  TOKEN_PROGRAM_ID,
+  createAssociatedTokenAccountInstruction,
+  createCloseAccountInstruction,
+  createInitializeMint2Instruction,
+  createMintToInstruction,
+  createTransferInstruction,
+  getAssociatedTokenAddressSync,
+} from "@solana/spl-token";
+import { attestationTypes } from "@switchboard-xyz/solana.js";
+import { BankrunProvider } from "anchor-bankrun";
+import {
+  BanksTransactionMeta,
+  Clock,
+  ProgramTestContext,
+  startAnchor,
+} from "solana-bankrun";
+import { expect } from "chai";
+import { createHash } from "crypto";
+import { Game, IDL } from "../target/types/game";
+
+const PROGRAM_ID = new PublicKey("Hxoo6xf3yChvNbyyWAShypeQPEEmEUXWuWWCQu5BmTfi");
+const PYTH_RECEIVER = new PublicKey("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+const SWITCHBOARD_ATTESTATION_PROGRAM = new PublicKey(
+  "sbattyXrzedoNATfc4L31wC9Mhxsi1BmFhTiN8gDshx"
+);
+const BPF_LOADER = new PublicKey("BPFLoader2111111111111111111111111111111111");
+
+const GAME_ID = 0;
+const TOKEN = 1_000_000;
+const HOUSE_LIQUIDITY = 10_000 * TOKEN;
+const USER_TOKENS = 1_000 * TOKEN;
+const STAKE = 10 * TOKEN;
+const PAYOUT = 17 * TOKEN;
+const INTERVAL = 120;
+const MIN_ENTRY_DELAY = 5;
+const MAX_ENTRY_DELAY = 60 * 60;
+const ENTRY_DELAY_LIMIT = 24 * 60 * 60;
+const CONFIG_CHANGE_DELAY = 2 * 24 * 60 * 60;
+const CANCEL_BUFFER = 24 * 60 * 60;
+const SETTLEMENT_BUCKET = 5 * 60;
+
+const ticker = (name: string) => Array.from(Buffer.from(name));
+const feedId = (hex: string) => Array.from(Buffer.from(hex, "hex"));
+
+const BTC = ticker("BTCUSDXX");
+const BTC_FEED = feedId("e62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43");
+
+const discriminator = (name: string) =>
+  createHash("sha256").update(`account:${name}`).digest().subarray(0, 8);
+
+const u64 = (value: number | anchor.BN) => new anchor.BN(value).toArrayLike(Buffer, "le", 8);
+const pda = (...seeds: (Buffer | Uint8Array)[]) =>
+  PublicKey.findProgramAddressSync(seeds, PROGRAM_ID)[0];
+
+const gameConfigPda = (gameId = GAME_ID) => pda(Buffer.from("GAME_CONFIG"), u64(gameId));
+const gameStatePda = (gameId = GAME_ID) => pda(Buffer.from("GAME_STATE"), u64(gameId));
+const pairPda = (pair: number[], gameId = GAME_ID) =>
+  pda(Buffer.from("PAIR"), u64(gameId), Buffer.from(pair));
+const betPda = (betId: number, gameId = GAME_ID) =>
+  pda(Buffer.from("BET"), u64(gameId), u64(betId));
+const positionMintPda = (betId: number, gameId = GAME_ID) =>
+  pda(Buffer.from("POSITION_MINT"), u64(gameId), u64(betId));
+const userProfilePda = (user: PublicKey, gameId = GAME_ID) =>
+  pda(Buffer.from("USER_PROFILE"), u64(gameId), user.toBuffer());
+const configChangePda = (changeId: number, gameId = GAME_ID) =>
+  pda(Buffer.from("CONFIG_CHANGE"), u64(gameId), u64(changeId));
+const subscriptionPda = (user: PublicKey, subscriptionId: number, gameId = GAME_ID) =>
+  pda(Buffer.from("SUBSCRIPTION"), u64(gameId), user.toBuffer(), u64(subscriptionId));
+const voucherPda = (user: PublicKey, voucherId: number, gameId = GAME_ID) =>
+  pda(Buffer.from("VOUCHER"), u64(gameId), user.toBuffer(), u64(voucherId));
+const referrerPda = (wallet: PublicKey, gameId = GAME_ID) =>
+  pda(Buffer.from("REFERRER"), u64(gameId), wallet.toBuffer());
+const tournamentPda = (tournamentId: number, gameId = GAME_ID) =>
+  pda(Buffer.from("TOURNAMENT"), u64(gameId), u64(tournamentId));
+const tournamentEntryPda = (tournament: PublicKey, user: PublicKey) =>
+  pda(Buffer.from("TOURNAMENT_ENTRY"), tournament.toBuffer(), user.toBuffer());
+const sessionPda = (user: PublicKey, sessionKey: PublicKey, gameId = GAME_ID) =>
+  pda(Buffer.from("SESSION"), u64(gameId), user.toBuffer(), sessionKey.toBuffer());
+
+const settlementBatchPda = (switchboardRequest: PublicKey, gameId = GAME_ID) =>
+  pda(Buffer.from("SETTLEMENT_BATCH"), u64(gameId), switchboardRequest.toBuffer());
+
+const orNull = (key: PublicKey) => (key.equals(PublicKey.default) ? null : key);
+
+/**
+ * Processes every transaction in a slot of its own, so that repeating an
+ * instruction gets a fresh blockhash, and keeps the metadata of the last one.
+ */
+class GameProvider extends BankrunProvider {
+  lastMeta: BanksTransactionMeta | null = null;
+
+  async sendAndConfirm(
+    tx: Transaction | VersionedTransaction,
+    signers?: Signer[]
+  ): Promise<string> {
+    // Warping resets the clock, which the tests move by hand.
+    const clock = await this.context.banksClient.getClock();
+    const slot = clock.slot + BigInt(1);
+    this.context.warpToSlot(slot);
+    this.context.setClock(
+      new Clock(
+        slot,
+        clock.epochStartTimestamp,
+        clock.epoch,
+        clock.leaderScheduleEpoch,
+        clock.unixTimestamp
+      )
+    );
+
+    const legacyTx = tx as Transaction;
+    legacyTx.feePayer = legacyTx.feePayer ?? this.wallet.publicKey;
+    legacyTx.recentBlockhash = (await this.context.banksClient.getLatestBlockhash())[0];
+    (signers ?? []).forEach((signer) => legacyTx.partialSign(signer));
+    // Transactions paid for by another wallet don't need the payer's signature.
+    if (legacyTx.feePayer.equals(this.wallet.publicKey)) {
+      await this.wallet.signTransaction(legacyTx);
+    }
+
+    this.lastMeta = await this.context.banksClient.processTransaction(legacyTx);
+    return anchor.utils.bytes.bs58.encode(legacyTx.signature);
+  }
+}
+
+let context: ProgramTestContext;
+let provider: GameProvider;
+let program: Program<Game>;
+let payer: Keypair;
+let mint: PublicKey;
+let gameEscrow: PublicKey;
+let switchboardFunction: PublicKey;
+
+const tokenAccount = (owner: PublicKey) => getAssociatedTokenAddressSync(mint, owner, true);
+
+async function send(instructions: TransactionInstruction[], signers: Keypair[] = []) {
+  await provider.sendAndConfirm(new Transaction().add(...instructions), signers);
+}
+
+async function now(): Promise<number> {
+  return Number((await context.banksClient.getClock()).unixTimestamp);
+}
+
+async function warp(seconds: number) {
+  const clock = await context.banksClient.getClock();
+  context.setClock(
+    new Clock(
+      clock.slot,
+      clock.epochStartTimestamp,
+      clock.epoch,
+      clock.leaderScheduleEpoch,
+      clock.unixTimestamp + BigInt(seconds)
+    )
+  );
+}
+
+async function balance(address: PublicKey): Promise<number> {
+  const account = await context.banksClient.getAccount(address);
+  return account ? Number(AccountLayout.decode(account.data).amount) : 0;
+}
+
+async function lamports(address: PublicKey): Promise<number> {
+  const account = await context.banksClient.getAccount(address);
+  return account ? Number(account.lamports) : 0;
+}
+
+async function accountExists(address: PublicKey): Promise<boolean> {
+  return (await context.banksClient.getAccount(address)) !== null;
+}
+
+/** A 6 decimal mint, minted by the payer. */
+async function newMint(): Promise<PublicKey> {
+  const mintKeypair = Keypair.generate();
+  const rent = await context.banksClient.getRent();
+  await send(
+    [
+      SystemProgram.createAccount({
+        fromPubkey: payer.publicKey,
+        newAccountPubkey: mintKeypair.publicKey,
+        space: MINT_SIZE,
+        lamports: Number(rent.minimumBalance(BigInt(MINT_SIZE))),
+        programId: TOKEN_PROGRAM_ID,
+      }),
+      createInitializeMint2Instruction(mintKeypair.publicKey, 6, payer.publicKey, null),
+    ],
+    [mintKeypair]
+  );
+  return mintKeypair.publicKey;
+}
+
+/** A wallet with SOL for fees and rent, and `tokens` in its token account. */
+async function newUser(tokens = USER_TOKENS): Promise<Keypair> {
+  const user = Keypair.generate();
+  await send([
+    SystemProgram.transfer({
+      fromPubkey: payer.publicKey,
+      toPubkey: user.publicKey,
+      lamports: 10 * LAMPORTS_PER_SOL,
+    }),
+    createAssociatedTokenAccountInstruction(
+      payer.publicKey,
+      tokenAccount(user.publicKey),
+      user.publicKey,
+      mint
+    ),
+    createMintToInstruction(mint, tokenAccount(user.publicKey), payer.publicKey, tokens),
+  ]);
+  return user;
+}
+
+/** Expects `promise` to fail with the program or framework error `name`. */
+async function expectError(promise: Promise<unknown>, name: string) {
+  const code =
+    program.idl.errors.find((error) => error.name === name)?.code ?? anchor.LangErrorCode[name];
+  expect(code, `unknown error ${name}`).to.not.be.undefined;
+
+  try {
+    await promise;
+  } catch (err) {
+    const match = /custom program error: (0x[0-9a-f]+)/i.exec(String(err));
+    const actual = err.error?.errorCode?.number ?? err.code ?? (match && parseInt(match[1], 16));
+    expect(actual, String(err)).to.equal(code);
+    return;
+  }
+  expect.fail(`expected ${name}`);
+}
+
+// Prefixes the self-invocations carrying `emit_cpi!` events
+const EVENT_IX_TAG = Buffer.from("e445a52e51cb9a1d", "hex");
+
+/** Decodes the CPI events emitted by the last transaction, in order. */
+function lastEvents(): anchor.Event[] {
+  return provider.lastMeta.innerInstructions
+    .flat()
+    .map((inner) => Buffer.from(inner.instruction.data))
+    .filter((data) => data.subarray(0, 8).equals(EVENT_IX_TAG))
+    .map((data) => program.coder.events.decode(data.subarray(8).toString("base64")));
+}
+
+let nextChangeId = 0;
+
+async function proposeConfigChange(change: any, proposer: Keypair = payer): Promise<number> {
+  const changeId = nextChangeId++;
+  await program.methods
+    .proposeConfigChange(new anchor.BN(changeId), change)
+    .accounts({
+      payer: proposer.publicKey,
+      gameConfig: gameConfigPda(),
+      configChange: configChangePda(changeId),
+    })
+    .signers([proposer])
+    .rpc();
+  return changeId;
+}
+
+/**
+ * Pair changes pass the pair account to create, disable or update, as in
+ * execute-config-change.ts.
+ */
+async function executeConfigChange(changeId: number, accounts: any = {}) {
+  const configChange = await program.account.configChange.fetch(configChangePda(changeId));
+  const change: any = configChange.change;
+  const pair =
+    change.addPair?.pair ??
+    change.deletePair?.pair ??
+    change.setPriceBounds?.pair ??
+    change.setOracle?.pair;
+  const pairPubKey = pair ? pairPda(pair) : null;
+  const pairExists = pairPubKey !== null && (await accountExists(pairPubKey));
+
+  await program.methods
+    .executeConfigChange()
+    .accounts({
+      payer: payer.publicKey,
+      gameConfig: gameConfigPda(),
+      configChange: configChangePda(changeId),
+      gameState: gameStatePda(),
+      authority: payer.publicKey,
+      newPair: change.addPair && !pairExists ? pairPubKey : null,
+      removedPair: change.deletePair ? pairPubKey : null,
+      updatedPair:
+        (change.addPair && pairExists) || change.setPriceBounds || change.setOracle
+          ? pairPubKey
+          : null,
+      ...accounts,
+    })
+    .rpc();
+}
+
+/** Proposes a change and executes it once the timelock has passed. */
+async function applyConfigChange(change: any) {
+  const changeId = await proposeConfigChange(change);
+  await warp(CONFIG_CHANGE_DELAY);
+  await executeConfigChange(changeId);
+}
+
+/** Registers `pair` unless a previous test did, settling through Pyth when `pullOracle` is set. */
+async function ensurePair(pair: number[], feed: number[], pullOracle: boolean) {
+  if (await accountExists(pairPda(pair))) {
+    return;
+  }
+
+  await applyConfigChange({ addPair: { pair, feedId: feed, maxMoveBps: 0, maxConfBps: 0 } });
+  if (pullOracle) {
+    await applyConfigChange({ setOracle: { pair, pullOracle: true } });
+  }
+}
+
+interface BetOptions {
+  pair?: number[];
+  amount?: number;
+  interval?: number;
+  isLong?: boolean;
+  startTime?: number;
+  proof?: number[][];
+  // A session key betting for the user
+  payer?: Keypair;
+  session?: PublicKey;
+  voucher?: PublicKey;
+  referrer?: PublicKey;
+  tournament?: PublicKey;
+  tournamentEntry?: PublicKey;
+}
+
+/** Places a bet for `user` and returns its ID. */
+async function placeBet(user: Keypair, options: BetOptions = {}): Promise<number> {
+  const state = await program.account.gameState.fetch(gameStatePda());
+  const betId = state.nextBetId.toNumber();
+  const pair = options.pair ?? BTC;
+  const betPayer = options.payer ?? user;
+  const positionMint = positionMintPda(betId);
+
+  await program.methods
+    .placeBet(
+      new anchor.BN(options.amount ?? STAKE),
+      pair,
+      options.interval ?? INTERVAL,
+      options.isLong ?? true,
+      new anchor.BN(options.startTime ?? 0),
+      options.proof ?? []
+    )
+    .accounts({
+      payer: betPayer.publicKey,
+      gameConfig: gameConfigPda(),
+      gameState: gameStatePda(),
+      acceptedPair: pairPda(pair),
+      bet: betPda(betId),
+      user: user.publicKey,
+      session: options.session ?? null,
+      userTokenAccount: tokenAccount(user.publicKey),
+      gameEscrow,
+      positionMint,
+      positionTokenAccount: getAssociatedTokenAddressSync(positionMint, user.publicKey),
+      voucher: options.voucher ?? null,
+      userProfile: userProfilePda(user.publicKey),
+      referrer: options.referrer ?? null,
+      tournament: options.tournament ?? null,
+      tournamentEntry: options.tournamentEntry ?? null,
+    })
+    .signers([betPayer])
+    .rpc();
+  return betId;
+}
+
+/** Subscribes `user` to compounding long rounds on BTC. */
+async function createSubscription(
+  user: Keypair,
+  subscriptionId: number,
+  deposit: number,
+  stake: number,
+  maxRounds: number,
+  proof: number[][] = []
+) {
+  const subscription = subscriptionPda(user.publicKey, subscriptionId);
+  await program.methods
+    .createSubscription(
+      new anchor.BN(subscriptionId),
+      new anchor.BN(deposit),
+      new anchor.BN(stake),
+      BTC,
+      INTERVAL,
+      true,
+      maxRounds,
+      true,
+      proof
+    )
+    .accounts({
+      payer: user.publicKey,
+      gameConfig: gameConfigPda(),
+      acceptedPair: pairPda(BTC),
+      subscription,
+      subscriptionEscrow: tokenAccount(subscription),
+      userTokenAccount: tokenAccount(user.publicKey),
+      tokenMint: mint,
+    })
+    .signers([user])
+    .rpc();
+}
+
+/** Opens the next round of `subscription` and returns the ID of its bet. */
+async function openSubscriptionRound(subscription: PublicKey): Promise<number> {
+  const state = await program.account.gameState.fetch(gameStatePda());
+  const betId = state.nextBetId.toNumber();
+  const account = await program.account.subscription.fetch(subscription);
+
+  await program.methods
+    .openSubscriptionRound()
+    .accounts({
+      payer: payer.publicKey,
+      gameConfig: gameConfigPda(),
+      gameState: gameStatePda(),
+      subscription,
+      subscriptionEscrow: account.subscriptionEscrow,
+      acceptedPair: pairPda(account.pair),
+      userProfile: userProfilePda(account.user),
+      bet: betPda(betId),
+      gameEscrow,
+    })
+    .rpc();
+  return betId;
+}
+
+/** Cancels `betId` once its cancel buffer has passed, refunding `holder`. */
+async function cancelBet(betId: number, holder: Keypair) {
+  const bet = await program.account.bet.fetch(betPda(betId));
+  const wait = bet.endTime.toNumber() + CANCEL_BUFFER + 1 - (await now());
+  if (wait > 0) {
+    await warp(wait);
+  }
+
+  // Subscription rounds have no position and refund the subscription escrow
+  const positionMint = orNull(bet.positionMint);
+  const userTokenAccount =
+    !positionMint && holder.publicKey.equals(bet.user)
+      ? bet.userTokenAccount
+      : tokenAccount(holder.publicKey);
+
+  await program.methods
+    .cancelBet(new anchor.BN(betId))
+    .accounts({
+      payer: holder.publicKey,
+      gameState: gameStatePda(),
+      gameConfig: gameConfigPda(),
+      bet: betPda(betId),
+      userTokenAccount,
+      gameEscrow,
+      subscription: orNull(bet.subscription),
+      positionMint,
+      positionTokenAccount: positionMint
+        ? getAssociatedTokenAddressSync(positionMint, holder.publicKey, true)
+        : null,
+      tournamentEntry: orNull(bet.tournamentEntry),
+      userProfile: orNull(bet.userProfile),
+    })
+    .signers([holder])
+    .rpc();
+}
+
+/** Claims the payout of `betId` held by the position of `holder`. */
+async function claimPosition(betId: number, holder: Keypair) {
+  const bet = await program.account.bet.fetch(betPda(betId));
+  await program.methods
+    .claimPosition(new anchor.BN(betId))
+    .accounts({
+      payer: holder.publicKey,
+      gameConfig: gameConfigPda(),
+      gameState: gameStatePda(),
+      bet: betPda(betId),
+      positionMint: bet.positionMint,
+      positionTokenAccount: getAssociatedTokenAddressSync(bet.positionMint, holder.publicKey),
+      userTokenAccount: tokenAccount(holder.publicKey),
+      gameEscrow,
+      subscription: orNull(bet.subscription),
+      tournamentEntry: orNull(bet.tournamentEntry),
+    })
+    .signers([holder])
+    .rpc();
+}
+
+/** Claims the held payout of `betId`, which has no position, for its bettor. */
+async function claimPayout(betId: number) {
+  const bet = await program.account.bet.fetch(betPda(betId));
+  await program.methods
+    .claimPayout(new anchor.BN(betId))
+    .accounts({
+      payer: payer.publicKey,
+      gameConfig: gameConfigPda(),
+      gameState: gameStatePda(),
+      bet: betPda(betId),
+      userTokenAccount: bet.userTokenAccount,
+      gameEscrow,
+      subscription: orNull(bet.subscription),
+      tournamentEntry: orNull(bet.tournamentEntry),
+    })
+    .rpc();
+}
+
+/** The accounts settling `betId`, as in settle-bet-with-pyth.ts. */
+async function settlementAccounts(betId: number) {
+  const bet = await program.account.bet.fetch(betPda(betId));
+  const userProfile = orNull(bet.userProfile)
+    ? await program.account.userProfile.fetch(bet.userProfile)
+    : null;
+  // A subscription round opens the next one at the next bet ID.
+  const nextBet = orNull(bet.subscription)
+    ? betPda((await program.account.gameState.fetch(gameStatePda())).nextBetId.toNumber())
+    : null;
+
+  return {
+    bet: betPda(betId),
+    gameState: gameStatePda(),
+    gameConfig: gameConfigPda(),
+    userTokenAccount: bet.userTokenAccount,
+    gameEscrow,
+    subscription: orNull(bet.subscription),
+    positionTokenAccount: orNull(bet.positionMint)
+      ? getAssociatedTokenAddressSync(bet.positionMint, bet.user, true)
+      : null,
+    tournamentEntry: orNull(bet.tournamentEntry),
+    userProfile: orNull(bet.userProfile),
+    acceptedPair: pairPda(bet.pair),
+    referrer: userProfile ? orNull(userProfile.referrer) : null,
+    nextBet,
+    systemProgram: nextBet ? SystemProgram.programId : null,
+  };
+}
+
+interface PriceUpdateOptions {
+  conf?: number;
+  expo?: number;
+  prevPublishTime?: number;
+  partial?: boolean;
+  owner?: PublicKey;
+}
+
+/** Injects a `PriceUpdateV2` account as posted by the Pyth receiver. */
+function postPriceUpdate(
+  feed: number[],
+  price: number,
+  publishTime: number,
+  options: PriceUpdateOptions = {}
+): PublicKey {
+  const message = Buffer.alloc(84);
+  Buffer.from(feed).copy(message, 0);
+  message.writeBigInt64LE(BigInt(price), 32);
+  message.writeBigUInt64LE(BigInt(options.conf ?? 0), 40);
+  message.writeInt32LE(options.expo ?? -2, 48);
+  message.writeBigInt64LE(BigInt(publishTime), 52);
+  message.writeBigInt64LE(BigInt(options.prevPublishTime ?? publishTime - 1), 60);
+  message.writeBigInt64LE(BigInt(price), 68);
+  message.writeBigUInt64LE(BigInt(options.conf ?? 0), 76);
+
+  // Partial { num_signatures } or Full
+  const verificationLevel = options.partial ? Buffer.from([0, 3]) : Buffer.from([1]);
+  const address = Keypair.generate().publicKey;
+  context.setAccount(address, {
+    lamports: LAMPORTS_PER_SOL,
+    data: Buffer.concat([
+      discriminator("PriceUpdateV2"),
+      Buffer.alloc(32),
+      verificationLevel,
+      message,
+      Buffer.alloc(8),
+    ]),
+    owner: options.owner ?? PYTH_RECEIVER,
+    executable: false,
+  });
+  return address;
+}
+
+/** Injects a zeroed Switchboard account of type `name`, as if it was never set up. */
+function switchboardAccount(
+  name: string,
+  span: number,
+  address = Keypair.generate().publicKey
+): PublicKey {
+  const data = Buffer.alloc(8 + span);
+  discriminator(name).copy(data);
+  context.setAccount(address, {
+    lamports: LAMPORTS_PER_SOL,
+    data,
+    owner: SWITCHBOARD_ATTESTATION_PROGRAM,
+    executable: false,
+  });
+  return address;
+}
+
+/** Injects a request of the game's function whose pending round `enclaveSigner` signs. */
+function switchboardRequest(enclaveSigner: PublicKey): PublicKey {
+  // Borsh encoded: the trigger and status flags, the authority, payer, function,
+  // escrow and queue, then the active round's status, four slots and verifier
+  // before its enclave signer. The zeroed queue matches the function's.
+  const data = Buffer.alloc(8 + 1024);
+  discriminator("FunctionRequestAccountData").copy(data);
+  data.writeUInt8(1, 9);
+  switchboardFunction.toBuffer().copy(data, 8 + 2 + 2 * 32);
+  data.writeUInt8(1, 8 + 2 + 5 * 32);
+  enclaveSigner.toBuffer().copy(data, 8 + 2 + 5 * 32 + 1 + 4 * 8 + 32);
+
+  const address = Keypair.generate().publicKey;
+  context.setAccount(address, {
+    lamports: LAMPORTS_PER_SOL,
+    data,
+    owner: SWITCHBOARD_ATTESTATION_PROGRAM,
+    executable: false,
+  });
+  return address;
+}
+
+/** Injects a routine of the game's function whose runs `enclaveSigner` signs. */
+function switchboardRoutine(enclaveSigner: PublicKey): PublicKey {
+  // Borsh encoded: the name, metadata, creation and update times, the disabled
+  // and status flags and the error status before the enclave signer. The zeroed
+  // queue matches the function's.
+  const data = Buffer.alloc(8 + attestationTypes.FunctionRoutineAccountData.layout.span);
+  discriminator("FunctionRoutineAccountData").copy(data);
+  enclaveSigner.toBuffer().copy(data, 8 + 64 + 256 + 2 * 8 + 3);
+
+  const address = Keypair.generate().publicKey;
+  context.setAccount(address, {
+    lamports: LAMPORTS_PER_SOL,
+    data,
+    owner: SWITCHBOARD_ATTESTATION_PROGRAM,
+    executable: false,
+  });
+  return address;
+}
+
+/** Injects the batch `switchboardRequest` settles `betIds` of `bucket` with, as requested. */
+async function settlementBatch(
+  switchboardRequest: PublicKey,
+  bucket: number,
+  betIds: number[],
+  keeper = payer.publicKey
+) {
+  const [address, bump] = PublicKey.findProgramAddressSync(
+    [Buffer.from("SETTLEMENT_BATCH"), u64(GAME_ID), switchboardRequest.toBuffer()],
+    PROGRAM_ID
+  );
+  const data = await program.coder.accounts.encode("SettlementBatch", {
+    bump,
+    gameId: new anchor.BN(GAME_ID),
+    bucket: new anchor.BN(bucket),
+    switchboardRequest,
+    keeper,
+    numBets: betIds.length,
+    betIds: [0, 1, 2].map((i) => new anchor.BN(betIds[i] ?? 0)),
+  });
+  context.setAccount(address, {
+    lamports: LAMPORTS_PER_SOL,
+    data,
+    owner: PROGRAM_ID,
+    executable: false,
+  });
+  return address;
+}
+
+interface SwitchboardOptions {
+  switchboardFunction?: PublicKey;
+  switchboardRequest?: PublicKey;
+  settlementBatch?: PublicKey;
+  switchboardRoutine?: PublicKey;
+}
+
+/** Reports that the enclave signed by `enclaveSigner` couldn't price `betId`. */
+async function reportSettlementFailure(
+  betId: number,
+  enclaveSigner: Keypair,
+  options: SwitchboardOptions = {}
+) {
+  await program.methods
+    .reportSettlementFailure(new anchor.BN(betId), { priceUnavailable: {} })
+    .accounts({
+      settlement: await settlementAccounts(betId),
+      switchboardFunction: options.switchboardFunction ?? switchboardFunction,
+      switchboardRequest: options.switchboardRequest ?? null,
+      enclaveSigner: enclaveSigner.publicKey,
+      settlementBatch: options.settlementBatch ?? null,
+      switchboardRoutine: options.switchboardRoutine ?? null,
+    })
+    .signers([enclaveSigner])
+    .rpc();
+}
+
+/** Settles `betId` at the prices the enclave signed by `enclaveSigner` read. */
+async function settleBet(
+  betId: number,
+  openPrice: number,
+  closePrice: number,
+  enclaveSigner: Keypair,
+  options: SwitchboardOptions = {}
+) {
+  const bet = await program.account.bet.fetch(betPda(betId));
+  const price = (value: number, publishTime: anchor.BN) => ({
+    price: new anchor.BN(value),
+    conf: new anchor.BN(0),
+    publishTime,
+  });
+  await program.methods
+    .settleBet(
+      new anchor.BN(betId),
+      price(openPrice, bet.startTime),
+      price(closePrice, bet.endTime),
+      -8
+    )
+    .accounts({
+      settlement: await settlementAccounts(betId),
+      switchboardFunction: options.switchboardFunction ?? switchboardFunction,
+      switchboardRequest: options.switchboardRequest ?? null,
+      enclaveSigner: enclaveSigner.publicKey,
+      settlementBatch: options.settlementBatch ?? null,
+      switchboardRoutine: options.switchboardRoutine ?? null,
+    })
+    .signers([enclaveSigner])
+    .rpc();
+}
+
+interface SettleOptions {
+  conf?: number;
+  openConf?: number;
+  closeConf?: number;
+  expo?: number;
+  closeExpo?: number;
+  // Seconds from the bet's start and end time to the publish times
+  openDelay?: number;
+  closeDelay?: number;
+  openPrevPublishTime?: number;
+  closePrevPublishTime?: number;
+  feedId?: number[];
+  partial?: boolean;
+  owner?: PublicKey;
+  // Pays for the settlement instead of the payer
+  settler?: Keypair;
+}
+
+/** Settles `betId` from the first updates published at its start and end time. */
+async function settleWithPyth(
+  betId: number,
+  openPrice: number,
+  closePrice: number,
+  options: SettleOptions = {}
+) {
+  const bet = await program.account.bet.fetch(betPda(betId));
+  const pair = await program.account.acceptedPair.fetch(pairPda(bet.pair));
+  const feed = options.feedId ?? pair.feedId;
+  const startTime = bet.startTime.toNumber();
+  const endTime = bet.endTime.toNumber();
+  const closePublishTime = endTime + (options.closeDelay ?? 0);
+
+  const openPriceUpdate = postPriceUpdate(feed, openPrice, startTime + (options.openDelay ?? 0), {
+    conf: options.openConf ?? options.conf,
+    expo: options.expo,
+    prevPublishTime: options.openPrevPublishTime ?? startTime - 1,
+    partial: options.partial,
+    owner: options.owner,
+  });
+  const closePriceUpdate = postPriceUpdate(feed, closePrice, closePublishTime, {
+    conf: options.closeConf ?? options.conf,
+    expo: options.closeExpo ?? options.expo,
+    prevPublishTime: options.closePrevPublishTime ?? endTime - 1,
+    partial: options.partial,
+    owner: options.owner,
+  });
+
+  const wait = closePublishTime + 1 - (await now());
+  if (wait > 0) {
+    await warp(wait);
+  }
+
+  const tx = await program.methods
+    .settleBetWithPyth(new anchor.BN(betId))
+    .accounts({
+      settlement: await settlementAccounts(betId),
+      openPriceUpdate,
+      closePriceUpdate,
+    })
+    .transaction();
+  if (options.settler) {
+    tx.feePayer = options.settler.publicKey;
+  }
+  await provider.sendAndConfirm(tx, options.settler ? [options.settler] : []);
+}
 
 describe("game", () => {
-  // Configure the client to use the local cluster.
-  anchor.setProvider(anchor.AnchorProvider.env());
+  before(async () => {
+    // A Switchboard function account that takes requests, standing in for the
+    // one the game is initialized with.
+    switchboardFunction = Keypair.generate().publicKey;
+    const functionData = Buffer.alloc(8 + attestationTypes.FunctionAccountData.layout.span);
+    discriminator("FunctionAccountData").copy(functionData);
+
+    context = await startAnchor(
+      ".",
+      [],
+      [
+        {
+          address: switchboardFunction,
+          info: {
+            lamports: LAMPORTS_PER_SOL,
+            data: functionData,
+            owner: SWITCHBOARD_ATTESTATION_PROGRAM,
+            executable: false,
+          },
+        },
+      ]
+    );
+    provider = new GameProvider(context);
+    anchor.setProvider(provider);
+    program = new Program<Game>(IDL, PROGRAM_ID, provider);
+    payer = context.payer;
 
-  const program = anchor.workspace.Game as Program<Game>;
+    mint = await newMint();
+    gameEscrow = tokenAccount(gameConfigPda());
+  });
 
   it("Is initialized!", async () => {
-    // Add your test here.
-    const tx = await program.methods.initialize().rpc();
+    const tx = await program.methods
+      .initialize(new anchor.BN(GAME_ID), PublicKey.default)
+      .accounts({
+        payer: payer.publicKey,
+        gameConfig: gameConfigPda(),
+        gameState: gameStatePda(),
+        gameEscrow,
+        tokenMint: mint,
+        authority: payer.publicKey,
+        switchboardFunction,
+      })
+      .rpc();
     console.log("Your transaction signature", tx);
+
+    // The house liquidity backing the payouts
+    await send([createMintToInstruction(mint, gameEscrow, payer.publicKey, HOUSE_LIQUIDITY)]);
+
+    const config = await program.account.gameConfig.fetch(gameConfigPda());
+    expect(config.authority.equals(payer.publicKey)).to.be.true;
+    expect(config.gameEscrow.equals(gameEscrow)).to.be.true;
+    expect(config.minEntryDelay).to.equal(MIN_ENTRY_DELAY);
+    expect(config.maxEntryDelay).to.equal(MAX_ENTRY_DELAY);
+
+    const state = await program.account.gameState.fetch(gameStatePda());
+    expect(state.nextBetId.toNumber()).to.equal(0);
+    expect(state.lockedLiquidity.toNumber()).to.equal(0);
+  });
+
+  describe("[user-026] deferred bet starts", () => {
+    let user: Keypair;
+
+    before(async () => {
+      await ensurePair(BTC, BTC_FEED, true);
+      user = await newUser();
+    });
+
+    it("opens the bet at the requested start time", async () => {
+      const startTime = (await now()) + 60;
+      const betId = await placeBet(user, { startTime });
+
+      const bet = await program.account.bet.fetch(betPda(betId));
+      expect(bet.startTime.toNumber()).to.equal(startTime);
+      expect(bet.endTime.toNumber()).to.equal(startTime + INTERVAL);
+
+      await settleWithPyth(betId, 100_000, 101_000);
+    });
+
+    it("opens a zero start time after the minimum entry delay", async () => {
+      const betId = await placeBet(user);
+
+      const bet = await program.account.bet.fetch(betPda(betId));
+      expect(bet.startTime.toNumber()).to.equal((await now()) + MIN_ENTRY_DELAY);
+
+      await settleWithPyth(betId, 100_000, 101_000);
+    });
+
+    it("rejects start times outside the entry delays", async () => {
+      const currentTime = await now();
+      await expectError(
+        placeBet(user, { startTime: currentTime + MIN_ENTRY_DELAY - 1 }),
+        "InvalidStartTime"
+      );
+      await expectError(
+        placeBet(user, { startTime: currentTime + MAX_ENTRY_DELAY + 1 }),
+        "InvalidStartTime"
+      );
+    });
+
+    it("rejects entry delays out of bounds", async () => {
+      await expectError(
+        proposeConfigChange({ setEntryDelays: { minEntryDelay: 0, maxEntryDelay: 60 } }),
+        "InvalidEntryDelay"
+      );
+      await expectError(
+        proposeConfigChange({ setEntryDelays: { minEntryDelay: 120, maxEntryDelay: 60 } }),
+        "InvalidEntryDelay"
+      );
+      await expectError(
+        proposeConfigChange({
+          setEntryDelays: { minEntryDelay: MIN_ENTRY_DELAY, maxEntryDelay: ENTRY_DELAY_LIMIT + 1 },
+        }),
+        "InvalidEntryDelay"
+      );
+    });
+
+    it("changes the entry delays through a config change", async () => {
+      await applyConfigChange({
+        setEntryDelays: { minEntryDelay: 30, maxEntryDelay: 2 * 60 * 60 },
+      });
+
+      const config = await program.account.gameConfig.fetch(gameConfigPda());
+      expect(config.minEntryDelay).to.equal(30);
+      expect(config.maxEntryDelay).to.equal(2 * 60 * 60);
+
+      await expectError(placeBet(user, { startTime: (await now()) + 10 }), "InvalidStartTime");
+      const betId = await placeBet(user, { startTime: (await now()) + 90 * 60 });
+      await settleWithPyth(betId, 100_000, 101_000);
+
+      await applyConfigChange({
+        setEntryDelays: { minEntryDelay: MIN_ENTRY_DELAY, maxEntryDelay: MAX_ENTRY_DELAY },
+      });
+    });
+  });
+
+  describe("[user-027] subscriptions", () => {
+    let user: Keypair;
+    let subscription: PublicKey;
+    let subscriptionEscrow: PublicKey;
+
+    before(async () => {
+      await ensurePair(BTC, BTC_FEED, true);
+      user = await newUser();
+      subscription = subscriptionPda(user.publicKey, 1);
+      subscriptionEscrow = tokenAccount(subscription);
+    });
+
+    it("plays compounding rounds from the subscription escrow", async () => {
+      await createSubscription(user, 1, 4 * STAKE, STAKE, 2);
+      expect(await balance(subscriptionEscrow)).to.equal(4 * STAKE);
+
+      const firstBetId = await openSubscriptionRound(subscription);
+      const firstBet = await program.account.bet.fetch(betPda(firstBetId));
+      expect(firstBet.subscription.equals(subscription)).to.be.true;
+      expect(firstBet.userTokenAccount.equals(subscriptionEscrow)).to.be.true;
+      expect(firstBet.amount.toNumber()).to.equal(STAKE);
+      expect(await balance(subscriptionEscrow)).to.equal(3 * STAKE);
+
+      await expectError(openSubscriptionRound(subscription), "ActiveSubscriptionRound");
+
+      // A win pays into the escrow and is staked on the next round, which the
+      // settlement opens from the prefunded rent
+      const rent = await lamports(subscription);
+      const secondBetId = firstBetId + 1;
+      await settleWithPyth(firstBetId, 100_000, 101_000);
+      const placed = lastEvents().find((event) => event.name === "BetPlaced");
+      expect(placed.data.betId.toNumber()).to.equal(secondBetId);
+      expect(await balance(subscriptionEscrow)).to.equal(3 * STAKE);
+      let account = await program.account.subscription.fetch(subscription);
+      expect(account.hasActiveBet).to.be.true;
+      expect(account.activeBetId.toNumber()).to.equal(secondBetId);
+      expect(account.nextStake.toNumber()).to.equal(PAYOUT);
+      const betRent = await lamports(betPda(secondBetId));
+      expect(await lamports(subscription)).to.equal(rent - betRent);
+
+      const secondBet = await program.account.bet.fetch(betPda(secondBetId));
+      expect(secondBet.amount.toNumber()).to.equal(PAYOUT);
+      expect(secondBet.subscription.equals(subscription)).to.be.true;
+      expect(secondBet.userProfile.equals(userProfilePda(user.publicKey))).to.be.true;
+      expect(secondBet.active).to.be.true;
+      await expectError(openSubscriptionRound(subscription), "ActiveSubscriptionRound");
+
+      // A loss resets the stake, and the last round opens no other
+      await settleWithPyth(secondBetId, 101_000, 100_000);
+      expect(lastEvents().some((event) => event.name === "BetPlaced")).to.be.false;
+      account = await program.account.subscription.fetch(subscription);
+      expect(account.hasActiveBet).to.be.false;
+      expect(account.roundsPlayed).to.equal(2);
+      expect(account.nextStake.toNumber()).to.equal(STAKE);
+
+      await expectError(openSubscriptionRound(subscription), "InvalidRounds");
+    });
+
+    it("refunds the escrow when cancelled", async () => {
+      const escrowBalance = await balance(subscriptionEscrow);
+      const userBalance = await balance(tokenAccount(user.publicKey));
+
+      await program.methods
+        .cancelSubscription()
+        .accounts({
+          payer: user.publicKey,
+          subscription,
+          subscriptionEscrow,
+          userTokenAccount: tokenAccount(user.publicKey),
+        })
+        .signers([user])
+        .rpc();
+
+      expect(await balance(tokenAccount(user.publicKey))).to.equal(userBalance + escrowBalance);
+      expect(await accountExists(subscription)).to.be.false;
+      expect(await accountExists(subscriptionEscrow)).to.be.false;
+    });
+
+    it("rejects deposits below the stake and zero rounds", async () => {
+      await expectError(
+        createSubscription(user, 2, STAKE / 2, STAKE, 2),
+        "InsufficientSubscriptionBalance"
+      );
+      await expectError(createSubscription(user, 3, 4 * STAKE, STAKE, 0), "InvalidRounds");
+    });
+  });
+
+  describe("[user-028] transferable positions", () => {
+    let bettor: Keypair;
+    let buyer: Keypair;
+
+    /** Sells the position of `betId` from `seller` to the buyer. */
+    async function sellPosition(betId: number, seller: Keypair) {
+      const { positionMint } = await program.account.bet.fetch(betPda(betId));
+      const destination = getAssociatedTokenAddressSync(positionMint, buyer.publicKey);
+      await send(
+        [
+          createAssociatedTokenAccountInstruction(
+            payer.publicKey,
+            destination,
+            buyer.publicKey,
+            positionMint
+          ),
+          createTransferInstruction(
+            getAssociatedTokenAddressSync(positionMint, seller.publicKey),
+            destination,
+            seller.publicKey,
+            1
+          ),
+        ],
+        [seller]
+      );
+    }
+
+    before(async () => {
+      await ensurePair(BTC, BTC_FEED, true);
+      bettor = await newUser();
+      buyer = await newUser();
+    });
+
+    it("mints a fixed 1-of-1 position and pays a bettor who holds it", async () => {
+      const betId = await placeBet(bettor);
+      const bet = await program.account.bet.fetch(betPda(betId));
+      const position = getAssociatedTokenAddressSync(bet.positionMint, bettor.publicKey);
+      expect(await balance(position)).to.equal(1);
+
+      const positionMint = MintLayout.decode(
+        (await context.banksClient.getAccount(bet.positionMint)).data
+      );
+      expect(Number(positionMint.supply)).to.equal(1);
+      expect(positionMint.mintAuthorityOption).to.equal(0);
+
+      const before = await balance(tokenAccount(bettor.publicKey));
+      await settleWithPyth(betId, 100_000, 101_000);
+      expect(await balance(tokenAccount(bettor.publicKey))).to.equal(before + PAYOUT);
+
+      const settled = await program.account.bet.fetch(betPda(betId));
+      expect(settled.positionRedeemed).to.be.true;
+      expect(settled.claimable.toNumber()).to.equal(0);
+    });
+
+    it("leaves the payout of a sold position to its holder", async () => {
+      const betId = await placeBet(bettor);
+      await sellPosition(betId, bettor);
+
+      const before = await balance(tokenAccount(bettor.publicKey));
+      await settleWithPyth(betId, 100_000, 101_000);
+      expect(await balance(tokenAccount(bettor.publicKey))).to.equal(before);
+      const settled = await program.account.bet.fetch(betPda(betId));
+      expect(settled.claimable.toNumber()).to.equal(PAYOUT);
+
+      const buyerBalance = await balance(tokenAccount(buyer.publicKey));
+      await claimPosition(betId, buyer);
+      expect(await balance(tokenAccount(buyer.publicKey))).to.equal(buyerBalance + PAYOUT);
+
+      const claimed = await program.account.bet.fetch(betPda(betId));
+      expect(claimed.positionRedeemed).to.be.true;
+      const position = getAssociatedTokenAddressSync(claimed.positionMint, buyer.publicKey);
+      expect(await balance(position)).to.equal(0);
+
+      await expectError(claimPosition(betId, buyer), "NothingToClaim");
+    });
+
+    it("settles after the bettor closed their token account", async () => {
+      const seller = await newUser(STAKE);
+      const betId = await placeBet(seller);
+      await sellPosition(betId, seller);
+      await send(
+        [
+          createCloseAccountInstruction(
+            tokenAccount(seller.publicKey),
+            seller.publicKey,
+            seller.publicKey
+          ),
+        ],
+        [seller]
+      );
+
+      await settleWithPyth(betId, 100_000, 101_000);
+      const settled = await program.account.bet.fetch(betPda(betId));
+      expect(settled.claimable.toNumber()).to.equal(PAYOUT);
+
+      const buyerBalance = await balance(tokenAccount(buyer.publicKey));
+      await claimPosition(betId, buyer);
+      expect(await balance(tokenAccount(buyer.publicKey))).to.equal(buyerBalance + PAYOUT);
+    });
+
+    it("refunds a cancelled bet to the position holder", async () => {
+      const betId = await placeBet(bettor);
+      await sellPosition(betId, bettor);
+
+      await expectError(cancelBet(betId, bettor), "InvalidPosition");
+
+      const buyerBalance = await balance(tokenAccount(buyer.publicKey));
+      await cancelBet(betId, buyer);
+      expect(await balance(tokenAccount(buyer.publicKey))).to.equal(buyerBalance + STAKE);
+      const profile = await program.account.userProfile.fetch(userProfilePda(bettor.publicKey));
+      expect(profile.openBets).to.equal(0);
+    });
+
+    it("lets subscribers cancel a stuck round into the subscription escrow", async () => {
+      const subscriber = await newUser();
+      const subscription = subscriptionPda(subscriber.publicKey, 1);
+      await createSubscription(subscriber, 1, 2 * STAKE, STAKE, 1);
+      const betId = await openSubscriptionRound(subscription);
+
+      await expectError(cancelBet(betId, bettor), "Unauthorized");
+
+      await cancelBet(betId, subscriber);
+      expect(await balance(tokenAccount(subscription))).to.equal(2 * STAKE);
+      expect((await program.account.subscription.fetch(subscription)).hasActiveBet).to.be.false;
+    });
+  });
+
+  describe("[user-029] vouchers", () => {
+    let user: Keypair;
+    let nextVoucherId = 0;
+
+    async function issueVoucher(
+      recipient: PublicKey,
+      options: { amount?: number; expiresAt?: number; maxUses?: number; pairs?: number[][] } = {},
+      issuer: Keypair = payer
+    ): Promise<PublicKey> {
+      const voucherId = nextVoucherId++;
+      const voucher = voucherPda(recipient, voucherId);
+      await program.methods
+        .issueVoucher(
+          new anchor.BN(voucherId),
+          new anchor.BN(options.amount ?? STAKE),
+          new anchor.BN(options.expiresAt ?? (await now()) + 3600),
+          options.maxUses ?? 1,
+          options.pairs ?? []
+        )
+        .accounts({
+          payer: issuer.publicKey,
+          gameConfig: gameConfigPda(),
+          voucher,
+          user: recipient,
+        })
+        .signers([issuer])
+        .rpc();
+      return voucher;
+    }
+
+    before(async () => {
+      await ensurePair(BTC, BTC_FEED, true);
+      user = await newUser();
+    });
+
+    it("stakes house money and pays only the profit", async () => {
+      const voucher = await issueVoucher(user.publicKey, { pairs: [BTC] });
+      const before = await balance(tokenAccount(user.publicKey));
+
+      const betId = await placeBet(user, { voucher });
+      expect(await balance(tokenAccount(user.publicKey))).to.equal(before);
+      expect((await program.account.voucher.fetch(voucher)).uses).to.equal(1);
+
+      const bet = await program.account.bet.fetch(betPda(betId));
+      expect(bet.voucher.equals(voucher)).to.be.true;
+      expect(bet.payout.toNumber()).to.equal(PAYOUT - STAKE);
+
+      await settleWithPyth(betId, 100_000, 101_000);
+      expect(await balance(tokenAccount(user.publicKey))).to.equal(before + PAYOUT - STAKE);
+
+      await expectError(placeBet(user, { voucher }), "InvalidVoucher");
+    });
+
+    it("refunds nothing for a lost or cancelled voucher bet", async () => {
+      const voucher = await issueVoucher(user.publicKey, { maxUses: 2 });
+      const before = await balance(tokenAccount(user.publicKey));
+
+      const lostBetId = await placeBet(user, { voucher });
+      await settleWithPyth(lostBetId, 101_000, 100_000);
+      expect(await balance(tokenAccount(user.publicKey))).to.equal(before);
+
+      const cancelledBetId = await placeBet(user, { voucher });
+      await cancelBet(cancelledBetId, user);
+      expect(await balance(tokenAccount(user.publicKey))).to.equal(before);
+    });
+
+    it("rejects vouchers used outside their terms", async () => {
+      const voucher = await issueVoucher(user.publicKey);
+      await expectError(placeBet(user, { voucher, amount: 2 * STAKE }), "InvalidAmount");
+
+      const ethOnly = await issueVoucher(user.publicKey, { pairs: [ticker("ETHUSDXX")] });
+      await expectError(placeBet(user, { voucher: ethOnly }), "InvalidPair");
+
+      const expired = await issueVoucher(user.publicKey, { expiresAt: (await now()) + 10 });
+      await warp(10);
+      await expectError(placeBet(user, { voucher: expired }), "InvalidVoucher");
+
+      const other = await newUser();
+      await expectError(placeBet(other, { voucher }), "InvalidVoucher");
+    });
+
+    it("lets only the authority issue and revoke vouchers", async () => {
+      const outsider = await newUser();
+      await expectError(issueVoucher(user.publicKey, {}, outsider), "ConstraintRaw");
+      await expectError(issueVoucher(user.publicKey, { amount: 0 }), "InvalidVoucher");
+      await expectError(issueVoucher(user.publicKey, { maxUses: 0 }), "InvalidVoucher");
+
+      const voucher = await issueVoucher(user.publicKey);
+      const revoke = (revoker: Keypair) =>
+        program.methods
+          .revokeVoucher()
+          .accounts({ payer: revoker.publicKey, gameConfig: gameConfigPda(), voucher })
+          .signers([revoker])
+          .rpc();
+
+      await expectError(revoke(outsider), "ConstraintRaw");
+      await revoke(payer);
+      expect(await accountExists(voucher)).to.be.false;
+    });
+  });
+
+  describe("[user-030] referrals", () => {
+    // 20% of the house edge of a lost bet: (STAKE - PAYOUT / 2) * 2000 / 10000
+    const REWARD = 300_000;
+
+    let wallet: Keypair;
+    let referrer: PublicKey;
+    let user: Keypair;
+
+    async function claimReferralRewards() {
+      await program.methods
+        .claimReferralRewards()
+        .accounts({
+          payer: wallet.publicKey,
+          gameConfig: gameConfigPda(),
+          gameState: gameStatePda(),
+          referrer,
+          userTokenAccount: tokenAccount(wallet.publicKey),
+          gameEscrow,
+        })
+        .signers([wallet])
+        .rpc();
+    }
+
+    before(async () => {
+      await ensurePair(BTC, BTC_FEED, true);
+      wallet = await newUser();
+      user = await newUser();
+      referrer = referrerPda(wallet.publicKey);
+
+      await program.methods
+        .registerReferrer()
+        .accounts({ payer: wallet.publicKey, gameConfig: gameConfigPda(), referrer })
+        .signers([wallet])
+        .rpc();
+    });
+
+    it("binds the first referrer and shares the edge of lost bets", async () => {
+      const lostBetId = await placeBet(user, { referrer });
+      const profile = await program.account.userProfile.fetch(userProfilePda(user.publicKey));
+      expect(profile.referrer.equals(referrer)).to.be.true;
+      let account = await program.account.referrer.fetch(referrer);
+      expect(account.referredVolume.toNumber()).to.equal(STAKE);
+
+      const unclaimedRewards = async () =>
+        (await program.account.gameState.fetch(gameStatePda())).unclaimedReferralRewards.toNumber();
+      const unclaimed = await unclaimedRewards();
+      await settleWithPyth(lostBetId, 101_000, 100_000);
+      account = await program.account.referrer.fetch(referrer);
+      expect(account.claimable.toNumber()).to.equal(REWARD);
+      expect(account.totalEarned.toNumber()).to.equal(REWARD);
+      expect(await unclaimedRewards()).to.equal(unclaimed + REWARD);
+
+      const wonBetId = await placeBet(user, { referrer });
+      await settleWithPyth(wonBetId, 100_000, 101_000);
+
+      account = await program.account.referrer.fetch(referrer);
+      expect(account.claimable.toNumber()).to.equal(REWARD);
+      expect(account.referredVolume.toNumber()).to.equal(2 * STAKE);
+    });
+
+    it("keeps the bound referrer for later bets", async () => {
+      await expectError(placeBet(user), "InvalidReferrer");
+
+      const other = await newUser();
+      const otherReferrer = referrerPda(other.publicKey);
+      await program.methods
+        .registerReferrer()
+        .accounts({ payer: other.publicKey, gameConfig: gameConfigPda(), referrer: otherReferrer })
+        .signers([other])
+        .rpc();
+      await expectError(placeBet(user, { referrer: otherReferrer }), "InvalidReferrer");
+    });
+
+    it("rejects self-referrals", async () => {
+      await expectError(placeBet(wallet, { referrer }), "InvalidReferrer");
+    });
+
+    it("pays out the claimable rewards", async () => {
+      const before = await balance(tokenAccount(wallet.publicKey));
+      await claimReferralRewards();
+      expect(await balance(tokenAccount(wallet.publicKey))).to.equal(before + REWARD);
+      expect((await program.account.referrer.fetch(referrer)).claimable.toNumber()).to.equal(0);
+
+      await expectError(claimReferralRewards(), "NothingToClaim");
+    });
+  });
+
+  describe("[user-031] tournaments", () => {
+    const ENTRY_FEE = STAKE;
+    const PRIZE_BPS = [6000, 3000];
+
+    let nextTournamentId = 0;
+    let tournament: PublicKey;
+    let tournamentEscrow: PublicKey;
+    let first: Keypair;
+    let second: Keypair;
+    let last: Keypair;
+
+    async function createTournament(
+      options: {
+        startTime?: number;
+        endTime?: number;
+        finalizeTime?: number;
+        pairs?: number[][];
+        prizeBps?: number[];
+      } = {},
+      creator: Keypair = payer
+    ): Promise<PublicKey> {
+      const tournamentId = nextTournamentId;
+      const tournament = tournamentPda(tournamentId);
+      const startTime = options.startTime ?? (await now());
+      const endTime = options.endTime ?? startTime + 600;
+      await program.methods
+        .createTournament(
+          new anchor.BN(tournamentId),
+          new anchor.BN(ENTRY_FEE),
+          new anchor.BN(startTime),
+          new anchor.BN(endTime),
+          new anchor.BN(options.finalizeTime ?? endTime + 600),
+          options.pairs ?? [BTC],
+          options.prizeBps ?? PRIZE_BPS
+        )
+        .accounts({
+          payer: creator.publicKey,
+          gameConfig: gameConfigPda(),
+          tournament,
+          tournamentEscrow: tokenAccount(tournament),
+          tokenMint: mint,
+        })
+        .signers([creator])
+        .rpc();
+      nextTournamentId++;
+      return tournament;
+    }
+
+    async function joinTournament(user: Keypair, tournament: PublicKey) {
+      await program.methods
+        .joinTournament()
+        .accounts({
+          payer: user.publicKey,
+          gameConfig: gameConfigPda(),
+          tournament,
+          tournamentEntry: tournamentEntryPda(tournament, user.publicKey),
+          tournamentEscrow: tokenAccount(tournament),
+          userTokenAccount: tokenAccount(user.publicKey),
+        })
+        .signers([user])
+        .rpc();
+    }
+
+    async function rankEntry(user: Keypair) {
+      await program.methods
+        .rankTournamentEntry()
+        .accounts({ tournament, tournamentEntry: tournamentEntryPda(tournament, user.publicKey) })
+        .rpc();
+    }
+
+    async function finalizeTournament(entrants: Keypair[]) {
+      await program.methods
+        .finalizeTournament()
+        .accounts({
+          payer: payer.publicKey,
+          gameConfig: gameConfigPda(),
+          tournament,
+          tournamentEscrow,
+          gameEscrow,
+        })
+        .remainingAccounts(
+          entrants.map((entrant) => ({
+            pubkey: tournamentEntryPda(tournament, entrant.publicKey),
+            isSigner: false,
+            isWritable: true,
+          }))
+        )
+        .rpc();
+    }
+
+    async function claimPrize(user: Keypair) {
+      await program.methods
+        .claimTournamentPrize()
+        .accounts({
+          payer: user.publicKey,
+          gameConfig: gameConfigPda(),
+          tournament,
+          tournamentEntry: tournamentEntryPda(tournament, user.publicKey),
+          tournamentEscrow,
+          userTokenAccount: tokenAccount(user.publicKey),
+        })
+        .signers([user])
+        .rpc();
+    }
+
+    const tournamentBet = (user: Keypair, options: BetOptions = {}) =>
+      placeBet(user, {
+        tournament,
+        tournamentEntry: tournamentEntryPda(tournament, user.publicKey),
+        ...options,
+      });
+
+    before(async () => {
+      await ensurePair(BTC, BTC_FEED, true);
+      first = await newUser();
+      second = await newUser();
+      last = await newUser();
+    });
+
+    it("rejects invalid tournaments", async () => {
+      const start = await now();
+      await expectError(createTournament({ startTime: start, endTime: start }), "InvalidTimestamp");
+      await expectError(
+        createTournament({ startTime: start, endTime: start + 600, finalizeTime: start + 600 }),
+        "InvalidTimestamp"
+      );
+      await expectError(createTournament({ prizeBps: [] }), "InvalidPrizeShares");
+      await expectError(createTournament({ prizeBps: [6000, 5000] }), "InvalidPrizeShares");
+      await expectError(createTournament({}, await newUser()), "ConstraintRaw");
+    });
+
+    it("collects entry fees into the prize pool", async () => {
+      tournament = await createTournament();
+      tournamentEscrow = tokenAccount(tournament);
+
+      for (const entrant of [first, second, last]) {
+        await joinTournament(entrant, tournament);
+      }
+      expect(await balance(tournamentEscrow)).to.equal(3 * ENTRY_FEE);
+      expect((await program.account.tournament.fetch(tournament)).numEntrants).to.equal(3);
+    });
+
+    it("only counts bets within the tournament", async () => {
+      await expectError(
+        placeBet(first, { tournamentEntry: tournamentEntryPda(tournament, first.publicKey) }),
+        "InvalidTournament"
+      );
+      await expectError(tournamentBet(first, { interval: 3600 }), "InvalidTournament");
+      await expectError(
+        tournamentBet(first, { tournamentEntry: tournamentEntryPda(tournament, second.publicKey) }),
+        "InvalidTournament"
+      );
+
+      const ethOnly = await createTournament({ pairs: [ticker("ETHUSDXX")] });
+      await joinTournament(first, ethOnly);
+      await expectError(
+        placeBet(first, {
+          tournament: ethOnly,
+          tournamentEntry: tournamentEntryPda(ethOnly, first.publicKey),
+        }),
+        "InvalidPair"
+      );
+    });
+
+    it("scores settled bets and ranks entries once the tournament ends", async () => {
+      const firstBetId = await tournamentBet(first, { amount: 2 * STAKE });
+      const secondBetId = await tournamentBet(second);
+      const lastBetId = await tournamentBet(last);
+      const firstEntry = tournamentEntryPda(tournament, first.publicKey);
+      expect((await program.account.tournamentEntry.fetch(firstEntry)).openBets).to.equal(1);
+
+      await expectError(rankEntry(first), "InvalidTimestamp");
+
+      await settleWithPyth(firstBetId, 100_000, 101_000);
+      await settleWithPyth(secondBetId, 100_000, 101_000);
+      await settleWithPyth(lastBetId, 101_000, 100_000);
+
+      const entry = await program.account.tournamentEntry.fetch(firstEntry);
+      expect(entry.pnl.toNumber()).to.equal(2 * (PAYOUT - STAKE));
+      expect(entry.openBets).to.equal(0);
+
+      const { endTime } = await program.account.tournament.fetch(tournament);
+      await warp(endTime.toNumber() - (await now()));
+
+      for (const entrant of [last, second, first]) {
+        await rankEntry(entrant);
+      }
+      await expectError(rankEntry(first), "InvalidTournament");
+
+      const account = await program.account.tournament.fetch(tournament);
+      expect(account.numRanked).to.equal(3);
+      expect(account.rankedUsers.slice(0, 3).map((user) => user.toBase58())).to.deep.equal(
+        [first, second, last].map((entrant) => entrant.publicKey.toBase58())
+      );
+    });
+
+    it("awards the prize pool to the leaderboard", async () => {
+      await expectError(finalizeTournament([first, second, last]), "InvalidTimestamp");
+
+      const { finalizeTime } = await program.account.tournament.fetch(tournament);
+      await warp(finalizeTime.toNumber() - (await now()));
+
+      await expectError(finalizeTournament([first, second]), "InvalidTournament");
+      await expectError(finalizeTournament([second, first, last]), "InvalidTournament");
+
+      const escrowBalance = await balance(gameEscrow);
+      await finalizeTournament([first, second, last]);
+
+      // The unawarded 10% of the pool goes to the house
+      const pool = 3 * ENTRY_FEE;
+      expect(await balance(gameEscrow)).to.equal(escrowBalance + pool / 10);
+      expect((await program.account.tournament.fetch(tournament)).finalized).to.be.true;
+      await expectError(finalizeTournament([first, second, last]), "InvalidTournament");
+
+      for (const [i, winner] of [first, second].entries()) {
+        const before = await balance(tokenAccount(winner.publicKey));
+        await claimPrize(winner);
+        expect(await balance(tokenAccount(winner.publicKey))).to.equal(
+          before + (pool * PRIZE_BPS[i]) / 10_000
+        );
+      }
+
+      await expectError(claimPrize(last), "NothingToClaim");
+      await expectError(claimPrize(first), "NothingToClaim");
+      expect(await balance(tournamentEscrow)).to.equal(0);
+    });
+
+    it("closes entries once the tournament ends", async () => {
+      await expectError(joinTournament(await newUser(), tournament), "InvalidTimestamp");
+    });
+  });
+
+  describe("[user-032] allowlist", () => {
+    let allowed: Keypair[];
+    let outsider: Keypair;
+    let root: Buffer;
+    let proofs: number[][][];
+
+    // Hashes the sorted pair of nodes, as validate_allowlist does
+    const hashPair = (a: Buffer, b: Buffer) =>
+      Buffer.from(keccak_256(Buffer.concat(Buffer.compare(a, b) <= 0 ? [a, b] : [b, a])));
+
+    before(async () => {
+      await ensurePair(BTC, BTC_FEED, true);
+      allowed = [await newUser(), await newUser()];
+      outsider = await newUser();
+
+      const leaves = [
+        ...allowed.map((user) => user.publicKey),
+        Keypair.generate().publicKey,
+        Keypair.generate().publicKey,
+      ].map((key) => Buffer.from(keccak_256(key.toBuffer())));
+      const branches = [hashPair(leaves[0], leaves[1]), hashPair(leaves[2], leaves[3])];
+      root = hashPair(branches[0], branches[1]);
+      proofs = [
+        [leaves[1], branches[1]],
+        [leaves[0], branches[1]],
+      ].map((proof) => proof.map((node) => Array.from(node)));
+    });
+
+    it("lets only the authority set the allowlist root", async () => {
+      await expectError(
+        proposeConfigChange({ setAllowlistRoot: { allowlistRoot: Array.from(root) } }, outsider),
+        "Unauthorized"
+      );
+
+      await applyConfigChange({ setAllowlistRoot: { allowlistRoot: Array.from(root) } });
+      const config = await program.account.gameConfig.fetch(gameConfigPda());
+      expect(Buffer.from(config.allowlistRoot).equals(root)).to.be.true;
+    });
+
+    it("only takes bets from allowlisted wallets", async () => {
+      await expectError(placeBet(outsider), "NotAllowlisted");
+      await expectError(placeBet(outsider, { proof: proofs[0] }), "NotAllowlisted");
+      await expectError(placeBet(allowed[0]), "NotAllowlisted");
+      await expectError(placeBet(allowed[0], { proof: proofs[1] }), "NotAllowlisted");
+
+      for (const [i, user] of allowed.entries()) {
+        const betId = await placeBet(user, { proof: proofs[i] });
+        await settleWithPyth(betId, 100_000, 101_000);
+      }
+    });
+
+    it("only opens subscriptions for allowlisted wallets", async () => {
+      await expectError(createSubscription(outsider, 1, 2 * STAKE, STAKE, 1), "NotAllowlisted");
+      await createSubscription(allowed[0], 1, 2 * STAKE, STAKE, 1, proofs[0]);
+    });
+
+    it("opens the game to everyone again with a zero root", async () => {
+      await applyConfigChange({ setAllowlistRoot: { allowlistRoot: Array(32).fill(0) } });
+
+      const betId = await placeBet(outsider);
+      await settleWithPyth(betId, 100_000, 101_000);
+    });
+  });
+
+  describe("[user-033] per-wallet limits", () => {
+    const COOLDOWN = 60;
+
+    let user: Keypair;
+
+    const openBets = async () =>
+      (await program.account.userProfile.fetch(userProfilePda(user.publicKey))).openBets;
+
+    before(async () => {
+      await ensurePair(BTC, BTC_FEED, true);
+      user = await newUser();
+      await applyConfigChange({ setUserLimits: { betCooldown: COOLDOWN, maxOpenBets: 2 } });
+    });
+
+    after(async () => {
+      await applyConfigChange({ setUserLimits: { betCooldown: 5, maxOpenBets: 10 } });
+    });
+
+    it("applies the limits through a config change", async () => {
+      const config = await program.account.gameConfig.fetch(gameConfigPda());
+      expect(config.betCooldown).to.equal(COOLDOWN);
+      expect(config.maxOpenBets).to.equal(2);
+    });
+
+    it("rate limits each wallet and caps its open bets", async () => {
+      const firstBetId = await placeBet(user);
+      await expectError(placeBet(user), "BetCooldown");
+
+      // Other wallets are not held up
+      const other = await newUser();
+      const otherBetId = await placeBet(other);
+
+      await warp(COOLDOWN);
+      const secondBetId = await placeBet(user);
+      expect(await openBets()).to.equal(2);
+
+      await warp(COOLDOWN);
+      await expectError(placeBet(user), "TooManyOpenBets");
+
+      // Settling a bet frees its slot
+      await settleWithPyth(firstBetId, 100_000, 101_000);
+      const thirdBetId = await placeBet(user);
+
+      for (const betId of [otherBetId, secondBetId, thirdBetId]) {
+        await settleWithPyth(betId, 100_000, 101_000);
+      }
+      expect(await openBets()).to.equal(0);
+    });
+
+    it("counts subscription rounds against the subscriber's limits", async () => {
+      const subscriber = await newUser();
+      const subscription = subscriptionPda(subscriber.publicKey, 1);
+      const userProfile = userProfilePda(subscriber.publicKey);
+      await createSubscription(subscriber, 1, 2 * STAKE, STAKE, 1);
+
+      const betId = await placeBet(subscriber);
+      await expectError(openSubscriptionRound(subscription), "BetCooldown");
+
+      await warp(COOLDOWN);
+      const roundBetId = await openSubscriptionRound(subscription);
+      const round = await program.account.bet.fetch(betPda(roundBetId));
+      expect(round.userProfile.equals(userProfile)).to.be.true;
+      expect((await program.account.userProfile.fetch(userProfile)).openBets).to.equal(2);
+
+      await warp(COOLDOWN);
+      await expectError(placeBet(subscriber), "TooManyOpenBets");
+
+      // Settling the round frees its slot
+      await settleWithPyth(roundBetId, 100_000, 101_000);
+      expect((await program.account.userProfile.fetch(userProfile)).openBets).to.equal(1);
+      await settleWithPyth(betId, 100_000, 101_000);
+      expect((await program.account.userProfile.fetch(userProfile)).openBets).to.equal(0);
+    });
+  });
+
+  describe("[user-034] session keys", () => {
+    const CAP = 2 * STAKE;
+
+    let user: Keypair;
+    let sessionKey: Keypair;
+
+    async function createSession(
+      owner: Keypair,
+      key: PublicKey,
+      options: { expiresAt?: number; cap?: number; pairs?: number[][] } = {}
+    ): Promise<PublicKey> {
+      const session = sessionPda(owner.publicKey, key);
+      await program.methods
+        .createSession(
+          key,
+          new anchor.BN(options.expiresAt ?? (await now()) + 3600),
+          new anchor.BN(options.cap ?? CAP),
+          options.pairs ?? [BTC]
+        )
+        .accounts({
+          payer: owner.publicKey,
+          gameConfig: gameConfigPda(),
+          session,
+          userTokenAccount: tokenAccount(owner.publicKey),
+        })
+        .signers([owner])
+        .rpc();
+      return session;
+    }
+
+    async function revokeSession(owner: Keypair, session: PublicKey) {
+      await program.methods
+        .revokeSession()
+        .accounts({
+          payer: owner.publicKey,
+          session,
+          userTokenAccount: tokenAccount(owner.publicKey),
+        })
+        .signers([owner])
+        .rpc();
+    }
+
+    const delegation = async (owner: Keypair) => {
+      const account = await context.banksClient.getAccount(tokenAccount(owner.publicKey));
+      return AccountLayout.decode(account.data);
+    };
+
+    before(async () => {
+      await ensurePair(BTC, BTC_FEED, true);
+      user = await newUser();
+      // Session keys only need SOL to pay for the bet accounts
+      sessionKey = await newUser(0);
+    });
+
+    it("rejects sessions that can't be used", async () => {
+      await expectError(
+        createSession(user, sessionKey.publicKey, { expiresAt: await now() }),
+        "InvalidSession"
+      );
+      await expectError(createSession(user, sessionKey.publicKey, { cap: 0 }), "InvalidSession");
+    });
+
+    it("bets for the user within the spending cap", async () => {
+      const session = await createSession(user, sessionKey.publicKey);
+      let account = await delegation(user);
+      expect(new PublicKey(account.delegate).equals(gameConfigPda())).to.be.true;
+      expect(Number(account.delegatedAmount)).to.equal(CAP);
+
+      await expectError(placeBet(user, { payer: sessionKey }), "Unauthorized");
+      const impostor = await newUser(0);
+      await expectError(placeBet(user, { payer: impostor, session }), "InvalidSession");
+
+      const before = await balance(tokenAccount(user.publicKey));
+      const betIds = [await placeBet(user, { payer: sessionKey, session })];
+      await warp(5);
+      betIds.push(await placeBet(user, { payer: sessionKey, session }));
+      expect(await balance(tokenAccount(user.publicKey))).to.equal(before - 2 * STAKE);
+
+      const bet = await program.account.bet.fetch(betPda(betIds[0]));
+      expect(bet.user.equals(user.publicKey)).to.be.true;
+      expect((await program.account.session.fetch(session)).spent.toNumber()).to.equal(CAP);
+
+      await warp(5);
+      await expectError(placeBet(user, { payer: sessionKey, session }), "SessionCapExceeded");
+
+      // The user still bets directly
+      betIds.push(await placeBet(user));
+
+      for (const betId of betIds) {
+        await settleWithPyth(betId, 100_000, 101_000);
+      }
+      account = await delegation(user);
+      expect(Number(account.delegatedAmount)).to.equal(0);
+
+      // A used up session no longer holds the delegation
+      const next = await createSession(user, Keypair.generate().publicKey);
+      await revokeSession(user, next);
+      await revokeSession(user, session);
+    });
+
+    it("backs one session per token account", async () => {
+      const session = await createSession(user, Keypair.generate().publicKey);
+      await expectError(createSession(user, Keypair.generate().publicKey), "SessionActive");
+
+      await expectError(revokeSession(await newUser(), session), "Unauthorized");
+      await revokeSession(user, session);
+      expect(await accountExists(session)).to.be.false;
+      expect((await delegation(user)).delegateOption).to.equal(0);
+
+      const replacement = await createSession(user, sessionKey.publicKey);
+      await revokeSession(user, replacement);
+    });
+
+    it("limits sessions to their pairs and lifetime", async () => {
+      const session = await createSession(user, sessionKey.publicKey, {
+        pairs: [ticker("ETHUSDXX")],
+      });
+      await expectError(placeBet(user, { payer: sessionKey, session }), "InvalidPair");
+      await revokeSession(user, session);
+
+      const expiring = await createSession(user, sessionKey.publicKey, {
+        expiresAt: (await now()) + 10,
+      });
+      await warp(10);
+      await expectError(placeBet(user, { payer: sessionKey, session: expiring }), "InvalidSession");
+      await revokeSession(user, expiring);
+    });
+  });
+
+  describe("[user-035] game instances", () => {
+    const PARTNER_GAME_ID = 1;
+
+    let partner: Keypair;
+    let partnerMint: PublicKey;
+    let partnerEscrow: PublicKey;
+
+    const proposeOnPartnerGame = (proposer: Keypair, changeId: number) =>
+      program.methods
+        .proposeConfigChange(new anchor.BN(changeId), {
+          addPair: { pair: BTC, feedId: BTC_FEED, maxMoveBps: 0, maxConfBps: 0 },
+        })
+        .accounts({
+          payer: proposer.publicKey,
+          gameConfig: gameConfigPda(PARTNER_GAME_ID),
+          configChange: configChangePda(changeId, PARTNER_GAME_ID),
+        })
+        .signers([proposer])
+        .rpc();
+
+    before(async () => {
+      await ensurePair(BTC, BTC_FEED, true);
+      partner = await newUser(0);
+      partnerMint = await newMint();
+      partnerEscrow = getAssociatedTokenAddressSync(
+        partnerMint,
+        gameConfigPda(PARTNER_GAME_ID),
+        true
+      );
+    });
+
+    it("initializes a game with its own authority and mint", async () => {
+      const { nextBetId } = await program.account.gameState.fetch(gameStatePda());
+
+      await program.methods
+        .initialize(new anchor.BN(PARTNER_GAME_ID), PublicKey.default)
+        .accounts({
+          payer: payer.publicKey,
+          gameConfig: gameConfigPda(PARTNER_GAME_ID),
+          gameState: gameStatePda(PARTNER_GAME_ID),
+          gameEscrow: partnerEscrow,
+          tokenMint: partnerMint,
+          authority: partner.publicKey,
+          switchboardFunction,
+        })
+        .rpc();
+
+      const config = await program.account.gameConfig.fetch(gameConfigPda(PARTNER_GAME_ID));
+      expect(config.gameId.toNumber()).to.equal(PARTNER_GAME_ID);
+      expect(config.authority.equals(partner.publicKey)).to.be.true;
+      expect(config.tokenMint.equals(partnerMint)).to.be.true;
+      expect(config.gameEscrow.equals(partnerEscrow)).to.be.true;
+
+      const state = await program.account.gameState.fetch(gameStatePda(PARTNER_GAME_ID));
+      expect(state.gameId.toNumber()).to.equal(PARTNER_GAME_ID);
+      expect(state.nextBetId.toNumber()).to.equal(0);
+
+      // The first game is left as it was
+      const gameConfig = await program.account.gameConfig.fetch(gameConfigPda());
+      expect(gameConfig.authority.equals(payer.publicKey)).to.be.true;
+      expect(gameConfig.tokenMint.equals(mint)).to.be.true;
+      const gameState = await program.account.gameState.fetch(gameStatePda());
+      expect(gameState.nextBetId.toNumber()).to.equal(nextBetId.toNumber());
+    });
+
+    it("keeps authorities to their own game", async () => {
+      await expectError(proposeOnPartnerGame(payer, 0), "Unauthorized");
+      await expectError(
+        proposeConfigChange({ setUserLimits: { betCooldown: 0, maxOpenBets: 0 } }, partner),
+        "Unauthorized"
+      );
+
+      await proposeOnPartnerGame(partner, 0);
+      const configChange = await program.account.configChange.fetch(
+        configChangePda(0, PARTNER_GAME_ID)
+      );
+      expect(configChange.change).to.have.property("addPair");
+    });
+
+    it("rejects pairs registered for another game", async () => {
+      const user = Keypair.generate();
+      const userTokenAccount = getAssociatedTokenAddressSync(partnerMint, user.publicKey);
+      await send([
+        createAssociatedTokenAccountInstruction(
+          payer.publicKey,
+          userTokenAccount,
+          user.publicKey,
+          partnerMint
+        ),
+        createMintToInstruction(partnerMint, userTokenAccount, payer.publicKey, STAKE),
+      ]);
+
+      const positionMint = positionMintPda(0, PARTNER_GAME_ID);
+      await expectError(
+        program.methods
+          .placeBet(new anchor.BN(STAKE), BTC, INTERVAL, true, new anchor.BN(0), [])
+          .accounts({
+            payer: payer.publicKey,
+            gameConfig: gameConfigPda(PARTNER_GAME_ID),
+            gameState: gameStatePda(PARTNER_GAME_ID),
+            acceptedPair: pairPda(BTC),
+            bet: betPda(0, PARTNER_GAME_ID),
+            user: user.publicKey,
+            session: null,
+            userTokenAccount,
+            gameEscrow: partnerEscrow,
+            positionMint,
+            positionTokenAccount: getAssociatedTokenAddressSync(positionMint, user.publicKey),
+            voucher: null,
+            userProfile: userProfilePda(user.publicKey, PARTNER_GAME_ID),
+            referrer: null,
+            tournament: null,
+            tournamentEntry: null,
+          })
+          .rpc(),
+        "ConstraintSeeds"
+      );
+    });
+  });
+
+  describe("[user-036] pair registry", () => {
+    // More pairs than the old fixed-size registry could hold
+    const PAIRS = Array.from({ length: 11 }, (_, i) => ticker(`TEST${String(i).padStart(4, "0")}`));
+
+    const addPair = (pair: number[], feed = BTC_FEED) => ({
+      addPair: { pair, feedId: feed, maxMoveBps: 0, maxConfBps: 0 },
+    });
+
+    it("registers a pair account for each pair", async () => {
+      const changeIds: number[] = [];
+      for (const pair of PAIRS) {
+        changeIds.push(await proposeConfigChange(addPair(pair)));
+      }
+      await warp(CONFIG_CHANGE_DELAY);
+      for (const changeId of changeIds) {
+        await executeConfigChange(changeId);
+      }
+
+      for (const pair of PAIRS) {
+        const account = await program.account.acceptedPair.fetch(pairPda(pair));
+        expect(account.pair).to.deep.equal(pair);
+        expect(account.gameId.toNumber()).to.equal(GAME_ID);
+        expect(account.disabled).to.be.false;
+      }
+
+      const user = await newUser();
+      const betId = await placeBet(user, { pair: PAIRS[PAIRS.length - 1] });
+      expect((await program.account.bet.fetch(betPda(betId))).pair).to.deep.equal(
+        PAIRS[PAIRS.length - 1]
+      );
+      await cancelBet(betId, user);
+    });
+
+    it("rejects duplicate pairs", async () => {
+      const changeId = await proposeConfigChange(addPair(PAIRS[0]));
+      await warp(CONFIG_CHANGE_DELAY);
+      await expectError(executeConfigChange(changeId), "InvalidConfigChange");
+    });
+
+    it("validates tickers and feed IDs", async () => {
+      await expectError(proposeConfigChange(addPair(ticker("test0000"))), "InvalidTicker");
+      await expectError(proposeConfigChange(addPair(ticker("BTC-USDX"))), "InvalidTicker");
+      await expectError(proposeConfigChange(addPair(ticker("BTCUSD  "))), "InvalidTicker");
+      await expectError(
+        proposeConfigChange(addPair(ticker("NOFEED00"), Array(32).fill(0))),
+        "InvalidFeedId"
+      );
+    });
+
+    it("rejects bets on unregistered pairs", async () => {
+      await expectError(
+        placeBet(await newUser(), { pair: ticker("UNLISTED") }),
+        "AccountNotInitialized"
+      );
+    });
+  });
+
+  describe("[user-037] per-pair feed IDs", () => {
+    const ETH = ticker("ETHUSDXX");
+    const ETH_FEED = feedId("ff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace");
+
+    let user: Keypair;
+
+    before(async () => {
+      await ensurePair(BTC, BTC_FEED, true);
+      await ensurePair(ETH, ETH_FEED, true);
+      user = await newUser();
+    });
+
+    it("stores the oracle feed ID of each pair", async () => {
+      expect((await program.account.acceptedPair.fetch(pairPda(BTC))).feedId).to.deep.equal(
+        BTC_FEED
+      );
+      expect((await program.account.acceptedPair.fetch(pairPda(ETH))).feedId).to.deep.equal(
+        ETH_FEED
+      );
+    });
+
+    it("settles each pair against its own feed", async () => {
+      const betId = await placeBet(user, { pair: ETH });
+
+      await expectError(
+        settleWithPyth(betId, 100_000, 101_000, { feedId: BTC_FEED }),
+        "InvalidPriceUpdate"
+      );
+
+      const before = await balance(tokenAccount(user.publicKey));
+      await settleWithPyth(betId, 100_000, 101_000);
+      expect(await balance(tokenAccount(user.publicKey))).to.equal(before + PAYOUT);
+    });
+  });
+
+  describe("[user-038] account versions", () => {
+    // The switchboard routine and reserved space version 5 grew the config by
+    const CONFIG_GROWTH = 32 + 64;
+
+    /** Rewrites an account in place, as an older program version would have left it. */
+    async function rewriteAccount(
+      address: PublicKey,
+      name: string,
+      update: (account: any) => void,
+      truncate = 0
+    ) {
+      const info = await context.banksClient.getAccount(address);
+      const account = program.coder.accounts.decode(name, Buffer.from(info.data));
+      update(account);
+      const data = await program.coder.accounts.encode(name, account);
+      context.setAccount(address, { ...info, data: data.subarray(0, data.length - truncate) });
+    }
+
+    const migrateConfig = (migrator: Keypair = payer) =>
+      program.methods
+        .migrateConfig()
+        .accounts({ payer: migrator.publicKey, gameConfig: gameConfigPda() })
+        .signers([migrator])
+        .rpc();
+
+    const migrateState = (migrator: Keypair = payer) =>
+      program.methods
+        .migrateState()
+        .accounts({
+          payer: migrator.publicKey,
+          gameConfig: gameConfigPda(),
+          gameState: gameStatePda(),
+        })
+        .signers([migrator])
+        .rpc();
+
+    const migrateBet = (betId: number) =>
+      program.methods
+        .migrateBet(new anchor.BN(betId))
+        .accounts({ payer: payer.publicKey, gameConfig: gameConfigPda(), bet: betPda(betId) })
+        .rpc();
+
+    before(async () => {
+      await ensurePair(BTC, BTC_FEED, true);
+    });
+
+    it("creates accounts at the current version", async () => {
+      expect((await program.account.gameConfig.fetch(gameConfigPda())).version).to.equal(5);
+      expect((await program.account.gameState.fetch(gameStatePda())).version).to.equal(2);
+
+      const user = await newUser();
+      const betId = await placeBet(user);
+      expect((await program.account.bet.fetch(betPda(betId))).version).to.equal(3);
+      await settleWithPyth(betId, 100_000, 101_000);
+    });
+
+    it("grows and upgrades an old config in place", async () => {
+      const original = Buffer.from((await context.banksClient.getAccount(gameConfigPda())).data);
+
+      await rewriteAccount(
+        gameConfigPda(),
+        "GameConfig",
+        (config) => {
+          config.version = 1;
+          config.configChangeDelay = 0;
+          config.pauser = PublicKey.default;
+          config.riskManager = PublicKey.default;
+          config.treasurer = PublicKey.default;
+          config.maxPublishDelay = 0;
+        },
+        CONFIG_GROWTH
+      );
+
+      await expectError(migrateConfig(await newUser()), "Unauthorized");
+      await migrateConfig();
+
+      // Every field the old versions lacked is back to its default
+      const migrated = Buffer.from((await context.banksClient.getAccount(gameConfigPda())).data);
+      expect(migrated.equals(original)).to.be.true;
+    });
+
+    it("rejects configs it can't migrate", async () => {
+      await rewriteAccount(gameConfigPda(), "GameConfig", (config) => (config.version = 0));
+      await expectError(migrateConfig(), "InvalidAccountVersion");
+
+      await rewriteAccount(gameConfigPda(), "GameConfig", (config) => (config.version = 6));
+      await expectError(migrateConfig(), "InvalidAccountVersion");
+
+      await rewriteAccount(gameConfigPda(), "GameConfig", (config) => (config.version = 5));
+      await migrateConfig();
+    });
+
+    it("upgrades the game state", async () => {
+      await rewriteAccount(gameStatePda(), "GameState", (state) => (state.version = 1));
+
+      await expectError(migrateState(await newUser()), "ConstraintRaw");
+      await migrateState();
+      expect((await program.account.gameState.fetch(gameStatePda())).version).to.equal(2);
+
+      await rewriteAccount(gameStatePda(), "GameState", (state) => (state.version = 0));
+      await expectError(migrateState(), "InvalidAccountVersion");
+      await rewriteAccount(gameStatePda(), "GameState", (state) => (state.version = 2));
+    });
+
+    it("upgrades bets", async () => {
+      const user = await newUser();
+      const betId = await placeBet(user);
+
+      // Version 3 grew the reserved space
+      await rewriteAccount(betPda(betId), "Bet", (bet) => (bet.version = 2), 62);
+      await migrateBet(betId);
+      expect((await program.account.bet.fetch(betPda(betId))).version).to.equal(3);
+
+      await rewriteAccount(betPda(betId), "Bet", (bet) => (bet.version = 0));
+      await expectError(migrateBet(betId), "InvalidAccountVersion");
+      await rewriteAccount(betPda(betId), "Bet", (bet) => (bet.version = 3));
+
+      await settleWithPyth(betId, 100_000, 101_000);
+    });
+
+    it("moves the game and its bets from before game IDs", async () => {
+      const LEGACY_GAME_ID = 38;
+      const LEGACY_BET_ID = 7;
+      const legacyConfig = pda(Buffer.from("GAME_CONFIG"));
+      const legacyState = pda(Buffer.from("GAME_STATE"));
+      const legacyBet = pda(Buffer.from("BET"), u64(LEGACY_BET_ID));
+      const legacyEscrow = tokenAccount(legacyConfig);
+      const user = await newUser();
+
+      const u32 = (value: number) => Buffer.from(new Uint32Array([value]).buffer);
+      const u16 = (value: number) => Buffer.from(new Uint16Array([value]).buffer);
+      const legacyAccount = (name: string, fields: Buffer[]) => ({
+        lamports: LAMPORTS_PER_SOL,
+        data: Buffer.concat([discriminator(name), ...fields]),
+        owner: PROGRAM_ID,
+        executable: false,
+      });
+
+      // The accounts as laid out before game IDs and versioning
+      await send([
+        createAssociatedTokenAccountInstruction(payer.publicKey, legacyEscrow, legacyConfig, mint),
+        createMintToInstruction(mint, legacyEscrow, payer.publicKey, 100 * STAKE),
+      ]);
+      context.setAccount(
+        legacyConfig,
+        legacyAccount("GameConfig", [
+          Buffer.from([255]),
+          payer.publicKey.toBuffer(),
+          u64(STAKE / 2),
+          u64(5 * STAKE),
+          u64(50 * STAKE),
+          u64(CANCEL_BUFFER),
+          u32(2 * INTERVAL),
+          u32(INTERVAL),
+          u16(1700),
+          switchboardFunction.toBuffer(),
+          mint.toBuffer(),
+          legacyEscrow.toBuffer(),
+          u32(1),
+          Buffer.concat([Buffer.from(BTC), Buffer.alloc(9 * 8)]),
+        ])
+      );
+      context.setAccount(
+        legacyState,
+        legacyAccount("GameState", [Buffer.from([255]), u64(PAYOUT), u64(LEGACY_BET_ID + 1)])
+      );
+      context.setAccount(
+        legacyBet,
+        legacyAccount("Bet", [
+          Buffer.from([255]),
+          u64(LEGACY_BET_ID),
+          u64(STAKE),
+          u64(PAYOUT),
+          u64(1_000),
+          u64(1_000 + INTERVAL),
+          u64(0),
+          u64(0),
+          user.publicKey.toBuffer(),
+          tokenAccount(user.publicKey).toBuffer(),
+          Buffer.from(BTC),
+          Buffer.from([1, 1]),
+          PublicKey.default.toBuffer(),
+        ])
+      );
+
+      const migrateLegacyGame = (migrator: Keypair) =>
+        program.methods
+          .migrateLegacyGame(new anchor.BN(LEGACY_GAME_ID))
+          .accounts({
+            payer: migrator.publicKey,
+            legacyConfig,
+            legacyState,
+            legacyEscrow,
+            gameConfig: gameConfigPda(LEGACY_GAME_ID),
+            gameState: gameStatePda(LEGACY_GAME_ID),
+            gameEscrow: tokenAccount(gameConfigPda(LEGACY_GAME_ID)),
+            tokenMint: mint,
+          })
+          .signers([migrator])
+          .rpc();
+      const migrateLegacyBet = (gameId: number) =>
+        program.methods
+          .migrateLegacyBet(new anchor.BN(LEGACY_BET_ID))
+          .accounts({
+            payer: payer.publicKey,
+            gameConfig: gameConfigPda(gameId),
+            legacyBet,
+            bet: betPda(LEGACY_BET_ID, gameId),
+          })
+          .rpc();
+
+      await expectError(migrateLegacyGame(user), "Unauthorized");
+      // Only the game moved from before game IDs takes its bets
+      await expectError(migrateLegacyBet(GAME_ID), "InvalidAccountVersion");
+      await migrateLegacyGame(payer);
+
+      // The old limits are kept and everything else is as on initialize
+      const config = await program.account.gameConfig.fetch(gameConfigPda(LEGACY_GAME_ID));
+      expect(config.version).to.equal(5);
+      expect(config.gameId.toNumber()).to.equal(LEGACY_GAME_ID);
+      expect(config.legacy).to.be.true;
+      expect(config.authority.equals(payer.publicKey)).to.be.true;
+      expect(config.pauser.equals(payer.publicKey)).to.be.true;
+      expect(config.minBet.toNumber()).to.equal(STAKE / 2);
+      expect(config.maxBet.toNumber()).to.equal(5 * STAKE);
+      expect(config.maxUtilizedLiquidity.toNumber()).to.equal(50 * STAKE);
+      expect(config.minInterval).to.equal(INTERVAL);
+      expect(config.leverage).to.equal(1700);
+      expect(config.minEntryDelay).to.equal(MIN_ENTRY_DELAY);
+      expect(config.configChangeDelay).to.equal(CONFIG_CHANGE_DELAY);
+      expect(config.switchboardFunction.equals(switchboardFunction)).to.be.true;
+      expect(config.gameEscrow.equals(tokenAccount(gameConfigPda(LEGACY_GAME_ID)))).to.be.true;
+
+      const state = await program.account.gameState.fetch(gameStatePda(LEGACY_GAME_ID));
+      expect(state.version).to.equal(2);
+      expect(state.lockedLiquidity.toNumber()).to.equal(PAYOUT);
+      expect(state.nextBetId.toNumber()).to.equal(LEGACY_BET_ID + 1);
+
+      expect(await balance(config.gameEscrow)).to.equal(100 * STAKE);
+      expect(await accountExists(legacyConfig)).to.be.false;
+      expect(await accountExists(legacyState)).to.be.false;
+      expect(await accountExists(legacyEscrow)).to.be.false;
+
+      await migrateLegacyBet(LEGACY_GAME_ID);
+      const bet = await program.account.bet.fetch(betPda(LEGACY_BET_ID, LEGACY_GAME_ID));
+      expect(bet.version).to.equal(3);
+      expect(bet.betId.toNumber()).to.equal(LEGACY_BET_ID);
+      expect(bet.amount.toNumber()).to.equal(STAKE);
+      expect(bet.payout.toNumber()).to.equal(PAYOUT);
+      expect(bet.endTime.toNumber()).to.equal(1_000 + INTERVAL);
+      expect(bet.user.equals(user.publicKey)).to.be.true;
+      expect(bet.userTokenAccount.equals(tokenAccount(user.publicKey))).to.be.true;
+      expect(bet.pair).to.deep.equal(BTC);
+      expect(bet.isLong).to.be.true;
+      expect(bet.active).to.be.true;
+      expect(bet.positionMint.equals(PublicKey.default)).to.be.true;
+      expect(await accountExists(legacyBet)).to.be.false;
+    });
+  });
+
+  describe("[user-039] timelocked config changes", () => {
+    const DEFAULT_LIMITS = { setUserLimits: { betCooldown: 5, maxOpenBets: 10 } };
+
+    const cancelConfigChange = (changeId: number, canceller: Keypair = payer) =>
+      program.methods
+        .cancelConfigChange()
+        .accounts({
+          payer: canceller.publicKey,
+          gameConfig: gameConfigPda(),
+          configChange: configChangePda(changeId),
+        })
+        .signers([canceller])
+        .rpc();
+
+    it("applies a change only once its delay has passed", async () => {
+      const proposedAt = await now();
+      const changeId = await proposeConfigChange(DEFAULT_LIMITS);
+      const configChange = await program.account.configChange.fetch(configChangePda(changeId));
+      expect(configChange.executeAfter.toNumber()).to.equal(proposedAt + CONFIG_CHANGE_DELAY);
+
+      await expectError(executeConfigChange(changeId), "ConfigChangeNotReady");
+      await warp(CONFIG_CHANGE_DELAY - 1);
+      await expectError(executeConfigChange(changeId), "ConfigChangeNotReady");
+
+      await warp(1);
+      await executeConfigChange(changeId);
+      expect(await accountExists(configChangePda(changeId))).to.be.false;
+      await expectError(executeConfigChange(changeId), "AccountNotInitialized");
+    });
+
+    it("drops cancelled changes", async () => {
+      const changeId = await proposeConfigChange(DEFAULT_LIMITS);
+
+      await expectError(cancelConfigChange(changeId, await newUser()), "Unauthorized");
+      await cancelConfigChange(changeId);
+      expect(await accountExists(configChangePda(changeId))).to.be.false;
+
+      await warp(CONFIG_CHANGE_DELAY);
+      await expectError(executeConfigChange(changeId), "AccountNotInitialized");
+    });
+
+    it("validates changes when they are proposed", async () => {
+      const amounts = (minBet: number, maxBet: number) => ({
+        setAmounts: {
+          minBet: new anchor.BN(minBet),
+          maxBet: new anchor.BN(maxBet),
+          maxUtilizedLiquidity: new anchor.BN(255 * TOKEN),
+        },
+      });
+      await expectError(proposeConfigChange(amounts(0, 50 * TOKEN)), "InvalidAmount");
+      await expectError(proposeConfigChange(amounts(50 * TOKEN, 5 * TOKEN)), "InvalidAmount");
+
+      await expectError(
+        proposeConfigChange({
+          setDisputeParams: { disputeThreshold: new anchor.BN(0), disputeWindow: 24 * 60 * 60 + 1 },
+        }),
+        "InvalidDisputeWindow"
+      );
+      await expectError(
+        proposeConfigChange({ setMaxPublishDelay: { maxPublishDelay: 0 } }),
+        "InvalidPublishDelay"
+      );
+      await expectError(
+        proposeConfigChange({ setMaxPublishDelay: { maxPublishDelay: 32768 } }),
+        "InvalidPublishDelay"
+      );
+      await expectError(
+        proposeConfigChange({ setReferralShare: { referralShareBps: 10_001 } }),
+        "InvalidReferralShare"
+      );
+    });
+
+    it("only takes changes from the right role", async () => {
+      const outsider = await newUser();
+      await expectError(proposeConfigChange(DEFAULT_LIMITS, outsider), "Unauthorized");
+      await expectError(
+        proposeConfigChange({ setConfigChangeDelay: { delay: 0 } }, outsider),
+        "Unauthorized"
+      );
+      await expectError(
+        proposeConfigChange({ setReferralShare: { referralShareBps: 0 } }, outsider),
+        "Unauthorized"
+      );
+    });
+
+    it("rejects pair accounts passed with other changes", async () => {
+      const changeId = await proposeConfigChange(DEFAULT_LIMITS);
+      await warp(CONFIG_CHANGE_DELAY);
+      await expectError(
+        executeConfigChange(changeId, { newPair: pairPda(Array(8).fill(0)) }),
+        "InvalidConfigChange"
+      );
+
+      const deleteId = await proposeConfigChange({ deletePair: { pair: BTC } });
+      await warp(CONFIG_CHANGE_DELAY);
+      await expectError(
+        executeConfigChange(deleteId, { removedPair: null }),
+        "InvalidConfigChange"
+      );
+      await cancelConfigChange(deleteId);
+
+      await executeConfigChange(changeId);
+    });
+
+    it("timelocks the delay itself", async () => {
+      await applyConfigChange({ setConfigChangeDelay: { delay: 60 } });
+      expect((await program.account.gameConfig.fetch(gameConfigPda())).configChangeDelay).to.equal(
+        60
+      );
+
+      const changeId = await proposeConfigChange({
+        setConfigChangeDelay: { delay: CONFIG_CHANGE_DELAY },
+      });
+      await expectError(executeConfigChange(changeId), "ConfigChangeNotReady");
+      await warp(60);
+      await executeConfigChange(changeId);
+      expect((await program.account.gameConfig.fetch(gameConfigPda())).configChangeDelay).to.equal(
+        CONFIG_CHANGE_DELAY
+      );
+    });
+  });
+
+  describe("[user-040] admin roles", () => {
+    const USER_LIMITS = { setUserLimits: { betCooldown: 5, maxOpenBets: 10 } };
+
+    let pauser: Keypair;
+    let riskManager: Keypair;
+    let treasurer: Keypair;
+
+    const rolesChange = (roles: PublicKey[]) => ({
+      setRoles: { pauser: roles[0], riskManager: roles[1], treasurer: roles[2] },
+    });
+
+    const pause = (caller: Keypair) =>
+      program.methods
+        .pause()
+        .accounts({ payer: caller.publicKey, gameConfig: gameConfigPda() })
+        .signers([caller])
+        .rpc();
+
+    const unpause = (caller: Keypair) =>
+      program.methods
+        .unpause()
+        .accounts({ payer: caller.publicKey, gameConfig: gameConfigPda() })
+        .signers([caller])
+        .rpc();
+
+    const withdraw = (caller: Keypair, amount: number) =>
+      program.methods
+        .withdraw(new anchor.BN(amount))
+        .accounts({
+          payer: caller.publicKey,
+          gameConfig: gameConfigPda(),
+          gameState: gameStatePda(),
+          gameEscrow,
+          destinationTokenAccount: tokenAccount(caller.publicKey),
+        })
+        .signers([caller])
+        .rpc();
+
+    const cancelConfigChange = (changeId: number) =>
+      program.methods
+        .cancelConfigChange()
+        .accounts({
+          payer: riskManager.publicKey,
+          gameConfig: gameConfigPda(),
+          configChange: configChangePda(changeId),
+        })
+        .signers([riskManager])
+        .rpc();
+
+    before(async () => {
+      await ensurePair(BTC, BTC_FEED, true);
+      pauser = await newUser(0);
+      riskManager = await newUser(0);
+      treasurer = await newUser(0);
+    });
+
+    after(async () => {
+      await applyConfigChange(rolesChange([payer.publicKey, payer.publicKey, payer.publicKey]));
+    });
+
+    it("lets only the authority hand out roles, behind the timelock", async () => {
+      const roles = [pauser, riskManager, treasurer].map((role) => role.publicKey);
+      await expectError(proposeConfigChange(rolesChange(roles), pauser), "Unauthorized");
+      await expectError(proposeConfigChange(rolesChange(roles), riskManager), "Unauthorized");
+
+      const changeId = await proposeConfigChange(rolesChange(roles));
+      await expectError(executeConfigChange(changeId), "ConfigChangeNotReady");
+      let config = await program.account.gameConfig.fetch(gameConfigPda());
+      expect(config.pauser.equals(payer.publicKey)).to.be.true;
+
+      await warp(CONFIG_CHANGE_DELAY);
+      await executeConfigChange(changeId);
+      config = await program.account.gameConfig.fetch(gameConfigPda());
+      expect(config.pauser.equals(pauser.publicKey)).to.be.true;
+      expect(config.riskManager.equals(riskManager.publicKey)).to.be.true;
+      expect(config.treasurer.equals(treasurer.publicKey)).to.be.true;
+    });
+
+    it("lets the pauser pause and the authority unpause", async () => {
+      await expectError(pause(riskManager), "Unauthorized");
+      await pause(pauser);
+      expect((await program.account.gameConfig.fetch(gameConfigPda())).paused).to.be.true;
+
+      const user = await newUser();
+      await expectError(placeBet(user), "GamePaused");
+      await expectError(createSubscription(user, 1, 2 * STAKE, STAKE, 1), "GamePaused");
+
+      await expectError(unpause(pauser), "ConstraintRaw");
+      await unpause(payer);
+      expect((await program.account.gameConfig.fetch(gameConfigPda())).paused).to.be.false;
+
+      // The authority keeps every role
+      await pause(payer);
+      await unpause(payer);
+    });
+
+    it("lets the risk manager queue limit and pair changes", async () => {
+      await expectError(proposeConfigChange(USER_LIMITS, pauser), "Unauthorized");
+      await expectError(proposeConfigChange(USER_LIMITS, treasurer), "Unauthorized");
+      await cancelConfigChange(await proposeConfigChange(USER_LIMITS, riskManager));
+
+      await expectError(
+        proposeConfigChange({ setConfigChangeDelay: { delay: 60 } }, riskManager),
+        "Unauthorized"
+      );
+      await expectError(
+        proposeConfigChange({ setReferralShare: { referralShareBps: 1000 } }, riskManager),
+        "Unauthorized"
+      );
+    });
+
+    it("lets the treasurer set the referral share and withdraw", async () => {
+      await cancelConfigChange(
+        await proposeConfigChange({ setReferralShare: { referralShareBps: 1000 } }, treasurer)
+      );
+
+      await expectError(withdraw(pauser, TOKEN), "Unauthorized");
+      await expectError(withdraw(riskManager, TOKEN), "Unauthorized");
+
+      const escrowBalance = await balance(gameEscrow);
+      await withdraw(treasurer, TOKEN);
+      expect(await balance(tokenAccount(treasurer.publicKey))).to.equal(TOKEN);
+      expect(await balance(gameEscrow)).to.equal(escrowBalance - TOKEN);
+    });
+  });
+
+  describe("[user-041] liquidity accounting", () => {
+    const amounts = (maxUtilizedLiquidity: number) => ({
+      setAmounts: {
+        minBet: new anchor.BN(5 * TOKEN),
+        maxBet: new anchor.BN(50 * TOKEN),
+        maxUtilizedLiquidity: new anchor.BN(maxUtilizedLiquidity),
+      },
+    });
+
+    const lockedLiquidity = async () =>
+      (await program.account.gameState.fetch(gameStatePda())).lockedLiquidity.toNumber();
+
+    before(async () => {
+      await ensurePair(BTC, BTC_FEED, true);
+    });
+
+    after(async () => {
+      await applyConfigChange(amounts(255 * TOKEN));
+    });
+
+    it("locks the payout of open bets until they close", async () => {
+      const locked = await lockedLiquidity();
+      const user = await newUser();
+
+      const wonBetId = await placeBet(user);
+      expect(await lockedLiquidity()).to.equal(locked + PAYOUT);
+      await settleWithPyth(wonBetId, 100_000, 101_000);
+      expect(await lockedLiquidity()).to.equal(locked);
+
+      const cancelledBetId = await placeBet(user);
+      await cancelBet(cancelledBetId, user);
+      expect(await lockedLiquidity()).to.equal(locked);
+    });
+
+    it("won't lower the liquidity cap below the locked liquidity", async () => {
+      const user = await newUser();
+      const betId = await placeBet(user);
+
+      const changeId = await proposeConfigChange(amounts(PAYOUT - 1));
+      await warp(CONFIG_CHANGE_DELAY);
+      await expectError(executeConfigChange(changeId), "LiquidityBelowLocked");
+
+      await cancelBet(betId, user);
+      await executeConfigChange(changeId);
+      expect(
+        (await program.account.gameConfig.fetch(gameConfigPda())).maxUtilizedLiquidity.toNumber()
+      ).to.equal(PAYOUT - 1);
+    });
+
+    it("rejects bets beyond the liquidity cap", async () => {
+      await applyConfigChange(amounts(PAYOUT + STAKE));
+
+      const betId = await placeBet(await newUser());
+      await expectError(placeBet(await newUser()), "InsufficientLiquidity");
+      await settleWithPyth(betId, 100_000, 101_000);
+    });
+  });
+
+  describe("[user-042] solvency", () => {
+    let user: Keypair;
+    let betId: number;
+
+    const checkSolvency = async () => {
+      await program.methods
+        .checkSolvency()
+        .accounts({ gameConfig: gameConfigPda(), gameState: gameStatePda(), gameEscrow })
+        .rpc();
+      const returnData = provider.lastMeta.returnData;
+      return program.coder.types.decode("Solvency", Buffer.from(returnData.data));
+    };
+
+    /** Overwrites the escrow balance, as a bug draining the escrow would. */
+    async function setEscrowBalance(amount: number) {
+      const info = await context.banksClient.getAccount(gameEscrow);
+      const account = AccountLayout.decode(info.data);
+      account.amount = BigInt(amount);
+      const data = Buffer.alloc(AccountLayout.span);
+      AccountLayout.encode(account, data);
+      context.setAccount(gameEscrow, { ...info, data });
+    }
+
+    before(async () => {
+      await ensurePair(BTC, BTC_FEED, true);
+      user = await newUser();
+      betId = await placeBet(user);
+    });
+
+    after(async () => {
+      await settleWithPyth(betId, 100_000, 101_000);
+    });
+
+    it("reports the escrow against what it owes", async () => {
+      const state = await program.account.gameState.fetch(gameStatePda());
+      const owed =
+        state.lockedLiquidity.toNumber() +
+        state.unclaimedReferralRewards.toNumber() +
+        state.unclaimedPayouts.toNumber();
+      const escrowBalance = await balance(gameEscrow);
+
+      const solvency = await checkSolvency();
+      expect(solvency.escrowBalance.toNumber()).to.equal(escrowBalance);
+      expect(solvency.lockedLiquidity.toNumber()).to.equal(state.lockedLiquidity.toNumber());
+      expect(solvency.owed.toNumber()).to.equal(owed);
+      expect(solvency.freeLiquidity.toNumber()).to.equal(escrowBalance - owed);
+    });
+
+    it("fails once the escrow can't cover what it owes", async () => {
+      const escrowBalance = await balance(gameEscrow);
+      const { owed } = await checkSolvency();
+
+      await setEscrowBalance(owed.toNumber() - 1);
+      await expectError(checkSolvency(), "Insolvent");
+      await expectError(placeBet(await newUser()), "Insolvent");
+
+      await setEscrowBalance(escrowBalance);
+      await checkSolvency();
+    });
+
+    it("only withdraws the free liquidity", async () => {
+      const { freeLiquidity } = await checkSolvency();
+      const destination = await newUser(0);
+      await expectError(
+        program.methods
+          .withdraw(freeLiquidity.addn(1))
+          .accounts({
+            payer: payer.publicKey,
+            gameConfig: gameConfigPda(),
+            gameState: gameStatePda(),
+            gameEscrow,
+            destinationTokenAccount: tokenAccount(destination.publicKey),
+          })
+          .rpc(),
+        "InsufficientLiquidity"
+      );
+    });
+  });
+
+  describe("[user-043] price bounds and disputes", () => {
+    const SOL = ticker("SOLUSDXX");
+    const SOL_FEED = feedId("ef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d");
+    const DISPUTE_WINDOW = 60 * 60;
+
+    let user: Keypair;
+
+    const disputeSettlement = async (betId: number, disputer: Keypair = payer) => {
+      const bet = await program.account.bet.fetch(betPda(betId));
+      await program.methods
+        .disputeSettlement(new anchor.BN(betId))
+        .accounts({
+          payer: disputer.publicKey,
+          gameConfig: gameConfigPda(),
+          gameState: gameStatePda(),
+          bet: betPda(betId),
+          subscription: orNull(bet.subscription),
+          tournamentEntry: orNull(bet.tournamentEntry),
+        })
+        .signers([disputer])
+        .rpc();
+    };
+
+    before(async () => {
+      await ensurePair(BTC, BTC_FEED, true);
+      await ensurePair(SOL, SOL_FEED, true);
+      user = await newUser();
+    });
+
+    it("rejects settlements moving further than the market allows", async () => {
+      await applyConfigChange({ setPriceBounds: { pair: SOL, maxMoveBps: 1000, maxConfBps: 0 } });
+      expect((await program.account.acceptedPair.fetch(pairPda(SOL))).maxMoveBps).to.equal(1000);
+
+      const betId = await placeBet(user, { pair: SOL });
+      await expectError(settleWithPyth(betId, 100_000, 110_001), "PriceMoveTooLarge");
+      await expectError(settleWithPyth(betId, 100_000, 89_999), "PriceMoveTooLarge");
+      await expectError(settleWithPyth(betId, 0, 100_000), "InvalidPrice");
+
+      const before = await balance(tokenAccount(user.publicKey));
+      await settleWithPyth(betId, 100_000, 110_000);
+      expect(await balance(tokenAccount(user.publicKey))).to.equal(before + PAYOUT);
+    });
+
+    describe("dispute window", () => {
+      before(async () => {
+        await applyConfigChange({
+          setDisputeParams: {
+            disputeThreshold: new anchor.BN(PAYOUT),
+            disputeWindow: DISPUTE_WINDOW,
+          },
+        });
+      });
+
+      after(async () => {
+        await applyConfigChange({
+          setDisputeParams: { disputeThreshold: new anchor.BN(0), disputeWindow: 0 },
+        });
+      });
+
+      it("pays payouts below the threshold right away", async () => {
+        const before = await balance(tokenAccount(user.publicKey));
+        const betId = await placeBet(user, { amount: STAKE / 2 });
+        await settleWithPyth(betId, 100_000, 101_000);
+        expect(await balance(tokenAccount(user.publicKey))).to.equal(
+          before + (PAYOUT - STAKE) / 2
+        );
+      });
+
+      it("holds large payouts and refunds disputed ones", async () => {
+        const before = await balance(tokenAccount(user.publicKey));
+        const betId = await placeBet(user);
+        await settleWithPyth(betId, 100_000, 101_000);
+        const settledAt = await now();
+
+        const bet = await program.account.bet.fetch(betPda(betId));
+        expect(bet.claimable.toNumber()).to.equal(PAYOUT);
+        expect(bet.disputableUntil.toNumber()).to.equal(settledAt + DISPUTE_WINDOW);
+        expect(await balance(tokenAccount(user.publicKey))).to.equal(before - STAKE);
+
+        await expectError(claimPosition(betId, user), "SettlementDisputable");
+        await expectError(disputeSettlement(betId, user), "Unauthorized");
+
+        await disputeSettlement(betId);
+        const disputed = await program.account.bet.fetch(betPda(betId));
+        expect(disputed.claimable.toNumber()).to.equal(STAKE);
+        expect(disputed.disputableUntil.toNumber()).to.equal(0);
+
+        await claimPosition(betId, user);
+        expect(await balance(tokenAccount(user.publicKey))).to.equal(before);
+      });
+
+      it("releases held payouts once the window has passed", async () => {
+        const before = await balance(tokenAccount(user.publicKey));
+        const betId = await placeBet(user);
+        await settleWithPyth(betId, 100_000, 101_000);
+
+        await warp(DISPUTE_WINDOW);
+        await expectError(disputeSettlement(betId), "NotDisputable");
+
+        await claimPosition(betId, user);
+        expect(await balance(tokenAccount(user.publicKey))).to.equal(before - STAKE + PAYOUT);
+      });
+
+      it("holds subscription payouts until they are claimed or disputed", async () => {
+        const subscriber = await newUser();
+        const subscription = subscriptionPda(subscriber.publicKey, 1);
+        const subscriptionEscrow = tokenAccount(subscription);
+        await createSubscription(subscriber, 1, 4 * STAKE, STAKE, 3);
+
+        // A held win keeps the round open, so the settlement opens no next one
+        const firstBetId = await openSubscriptionRound(subscription);
+        await settleWithPyth(firstBetId, 100_000, 101_000);
+        expect(lastEvents().some((event) => event.name === "BetPlaced")).to.be.false;
+        const held = await program.account.bet.fetch(betPda(firstBetId));
+        expect(held.claimable.toNumber()).to.equal(PAYOUT);
+        let account = await program.account.subscription.fetch(subscription);
+        expect(account.hasActiveBet).to.be.true;
+        expect(await balance(subscriptionEscrow)).to.equal(3 * STAKE);
+        await expectError(claimPayout(firstBetId), "SettlementDisputable");
+
+        // A disputed round refunds the stake, which is not compounded
+        await disputeSettlement(firstBetId);
+        account = await program.account.subscription.fetch(subscription);
+        expect(account.hasActiveBet).to.be.false;
+        expect(account.nextStake.toNumber()).to.equal(STAKE);
+        await claimPayout(firstBetId);
+        expect(await balance(subscriptionEscrow)).to.equal(4 * STAKE);
+        await expectError(claimPayout(firstBetId), "NothingToClaim");
+
+        // An undisputed one is compounded once claimed
+        const secondBetId = await openSubscriptionRound(subscription);
+        await settleWithPyth(secondBetId, 100_000, 101_000);
+        await warp(DISPUTE_WINDOW);
+        await claimPayout(secondBetId);
+        const claimed = lastEvents().find((event) => event.name === "PayoutClaimed");
+        expect(claimed.data.payout.toNumber()).to.equal(PAYOUT);
+        account = await program.account.subscription.fetch(subscription);
+        expect(account.hasActiveBet).to.be.false;
+        expect(account.nextStake.toNumber()).to.equal(PAYOUT);
+        expect(await balance(subscriptionEscrow)).to.equal(3 * STAKE + PAYOUT);
+      });
+    });
+
+    it("stops new bets on a deleted pair while open ones settle", async () => {
+      const betId = await placeBet(user, { pair: SOL });
+      await applyConfigChange({ deletePair: { pair: SOL } });
+
+      const pair = await program.account.acceptedPair.fetch(pairPda(SOL));
+      expect(pair.disabled).to.be.true;
+      await expectError(placeBet(await newUser(), { pair: SOL }), "PairDisabled");
+
+      const before = await balance(tokenAccount(user.publicKey));
+      await settleWithPyth(betId, 100_000, 101_000);
+      expect(await balance(tokenAccount(user.publicKey))).to.equal(before + PAYOUT);
+    });
+
+    it("adds a deleted pair back without its old oracle settings", async () => {
+      await applyConfigChange({
+        addPair: { pair: SOL, feedId: SOL_FEED, maxMoveBps: 0, maxConfBps: 0 },
+      });
+
+      const pair = await program.account.acceptedPair.fetch(pairPda(SOL));
+      expect(pair.disabled).to.be.false;
+      expect(pair.maxMoveBps).to.equal(0);
+      expect(pair.pullOracle).to.be.false;
+
+      await applyConfigChange({ setOracle: { pair: SOL, pullOracle: true } });
+      const betId = await placeBet(user, { pair: SOL });
+      await settleWithPyth(betId, 100_000, 101_000);
+    });
+  });
+
+  describe("[user-044] settlement price details", () => {
+    let user: Keypair;
+
+    before(async () => {
+      await ensurePair(BTC, BTC_FEED, true);
+      user = await newUser();
+    });
+
+    it("keeps the bet layout within its reserved space", async () => {
+      const betId = await placeBet(user);
+      expect((await context.banksClient.getAccount(betPda(betId))).data.length).to.equal(434);
+      await settleWithPyth(betId, 100_000, 101_000);
+    });
+
+    it("stores the exponent, confidence and publish times", async () => {
+      const betId = await placeBet(user);
+      await settleWithPyth(betId, 100_000, 101_000, {
+        expo: -5,
+        openConf: 10,
+        closeConf: 20,
+        openDelay: 3,
+        closeDelay: 7,
+      });
+
+      const bet = await program.account.bet.fetch(betPda(betId));
+      expect(bet.openPrice.toNumber()).to.equal(100_000);
+      expect(bet.closePrice.toNumber()).to.equal(101_000);
+      expect(bet.priceExpo).to.equal(-5);
+      expect(bet.openConf.toNumber()).to.equal(10);
+      expect(bet.closeConf.toNumber()).to.equal(20);
+      expect(bet.openPublishOffset).to.equal(3);
+      expect(bet.closePublishOffset).to.equal(7);
+    });
+
+    it("rejects prices published too far from the bet times", async () => {
+      const betId = await placeBet(user);
+      await expectError(settleWithPyth(betId, 100_000, 101_000, { openDelay: 11 }), "StalePrice");
+      await expectError(settleWithPyth(betId, 100_000, 101_000, { closeDelay: 11 }), "StalePrice");
+      await expectError(settleWithPyth(betId, 100_000, 101_000, { openDelay: -1 }), "StalePrice");
+
+      // The delay is a config change, up to PUBLISH_DELAY_LIMIT
+      await applyConfigChange({ setMaxPublishDelay: { maxPublishDelay: 30 } });
+      await settleWithPyth(betId, 100_000, 101_000, { closeDelay: 30 });
+      await applyConfigChange({ setMaxPublishDelay: { maxPublishDelay: 10 } });
+      expect((await program.account.bet.fetch(betPda(betId))).closePublishOffset).to.equal(30);
+    });
+
+    it("rejects exponents a bet can't store", async () => {
+      const betId = await placeBet(user);
+      await expectError(settleWithPyth(betId, 100_000, 101_000, { expo: -200 }), "InvalidPrice");
+      await settleWithPyth(betId, 100_000, 101_000);
+    });
+  });
+
+  describe("[user-045] confidence refunds", () => {
+    const XRP = ticker("XRPUSDXX");
+    const XRP_FEED = feedId("ec5d399846a9209f3fe5881d70aae9268c94339ff9817e8d18ff19fa05eea1c8");
+
+    let user: Keypair;
+
+    before(async () => {
+      await ensurePair(XRP, XRP_FEED, true);
+      // Confidence may be at most 1% of the price
+      await applyConfigChange({ setPriceBounds: { pair: XRP, maxMoveBps: 0, maxConfBps: 100 } });
+      user = await newUser();
+    });
+
+    it("refunds bets when a price is too uncertain", async () => {
+      const before = await balance(tokenAccount(user.publicKey));
+      const betId = await placeBet(user, { pair: XRP });
+      await settleWithPyth(betId, 100_000, 110_000, { closeConf: 1_101 });
+
+      const bet = await program.account.bet.fetch(betPda(betId));
+      expect(bet.active).to.be.false;
+      expect(bet.payout.toNumber()).to.equal(STAKE);
+      expect(await balance(tokenAccount(user.publicKey))).to.equal(before);
+    });
+
+    it("refunds bets moving within the confidence band", async () => {
+      const before = await balance(tokenAccount(user.publicKey));
+      const betId = await placeBet(user, { pair: XRP, isLong: false });
+      await settleWithPyth(betId, 100_000, 100_500, { conf: 300 });
+      expect(await balance(tokenAccount(user.publicKey))).to.equal(before);
+    });
+
+    it("settles clear results as usual", async () => {
+      const before = await balance(tokenAccount(user.publicKey));
+      const wonBetId = await placeBet(user, { pair: XRP });
+      await settleWithPyth(wonBetId, 100_000, 101_000, { conf: 100 });
+      expect(await balance(tokenAccount(user.publicKey))).to.equal(before - STAKE + PAYOUT);
+
+      const lostBetId = await placeBet(user, { pair: XRP });
+      await settleWithPyth(lostBetId, 101_000, 100_000, { conf: 100 });
+      expect(await balance(tokenAccount(user.publicKey))).to.equal(before - 2 * STAKE + PAYOUT);
+    });
+  });
+
+  describe("[user-046] Pyth settlement", () => {
+    const ADA = ticker("ADAUSDXX");
+    const ADA_FEED = feedId("2a01deaec9e51a579277b34b122399984d0bbf57e2458a7e42fecd2829867a0d");
+
+    let user: Keypair;
+
+    before(async () => {
+      await ensurePair(BTC, BTC_FEED, true);
+      await ensurePair(ADA, ADA_FEED, false);
+      user = await newUser();
+    });
+
+    it("lets anyone settle a bet from Pyth updates", async () => {
+      const settler = await newUser(0);
+      const before = await balance(tokenAccount(user.publicKey));
+      const betId = await placeBet(user);
+      await settleWithPyth(betId, 100_000, 101_000, { settler });
+
+      expect((await program.account.bet.fetch(betPda(betId))).active).to.be.false;
+      expect(await balance(tokenAccount(user.publicKey))).to.equal(before - STAKE + PAYOUT);
+      expect(await balance(tokenAccount(settler.publicKey))).to.equal(0);
+
+      await expectError(settleWithPyth(betId, 100_000, 101_000), "InactiveBet");
+    });
+
+    it("rejects updates that aren't fully verified Pyth prices", async () => {
+      const betId = await placeBet(user);
+      await expectError(
+        settleWithPyth(betId, 100_000, 101_000, { owner: SystemProgram.programId }),
+        "InvalidPriceUpdate"
+      );
+      await expectError(
+        settleWithPyth(betId, 100_000, 101_000, { partial: true }),
+        "InvalidPriceUpdate"
+      );
+      await expectError(
+        settleWithPyth(betId, 100_000, 101_000, { expo: -2, closeExpo: -3 }),
+        "InvalidPriceUpdate"
+      );
+      await expectError(settleWithPyth(betId, -100_000, 101_000), "InvalidPrice");
+      await settleWithPyth(betId, 100_000, 101_000);
+    });
+
+    it("only takes the first update published at each bet time", async () => {
+      const betId = await placeBet(user);
+      const bet = await program.account.bet.fetch(betPda(betId));
+      await expectError(
+        settleWithPyth(betId, 100_000, 101_000, {
+          openDelay: 2,
+          openPrevPublishTime: bet.startTime.toNumber(),
+        }),
+        "NotFirstPriceUpdate"
+      );
+      await expectError(
+        settleWithPyth(betId, 100_000, 101_000, {
+          closeDelay: 2,
+          closePrevPublishTime: bet.endTime.toNumber() + 1,
+        }),
+        "NotFirstPriceUpdate"
+      );
+      await settleWithPyth(betId, 100_000, 101_000, { openDelay: 2, closeDelay: 2 });
+    });
+
+    it("leaves markets without the pull oracle to Switchboard", async () => {
+      const before = await balance(tokenAccount(user.publicKey));
+      const betId = await placeBet(user, { pair: ADA });
+      await expectError(settleWithPyth(betId, 100_000, 101_000), "WrongOracle");
+
+      await cancelBet(betId, user);
+      expect(await balance(tokenAccount(user.publicKey))).to.equal(before);
+    });
+  });
+
+  describe("[user-047] settlement failure reports", () => {
+    const DOGE = ticker("DOGEUSDX");
+    const DOGE_FEED = feedId("dcef50dd0a4cd2dcc17e45df1676dcb336a11a61c69df7a0299b0150c672d25c");
+
+    let user: Keypair;
+    let enclaveSigner: Keypair;
+
+    before(async () => {
+      await ensurePair(BTC, BTC_FEED, true);
+      await ensurePair(DOGE, DOGE_FEED, false);
+      user = await newUser();
+      enclaveSigner = Keypair.generate();
+    });
+
+    it("leaves pull oracle markets to Pyth", async () => {
+      const betId = await placeBet(user);
+      await expectError(reportSettlementFailure(betId, enclaveSigner), "WrongOracle");
+      await settleWithPyth(betId, 100_000, 101_000);
+    });
+
+    it("only takes reports from the game's Switchboard function", async () => {
+      const before = await balance(tokenAccount(user.publicKey));
+      const betId = await placeBet(user, { pair: DOGE });
+
+      const otherFunction = switchboardAccount(
+        "FunctionAccountData",
+        attestationTypes.FunctionAccountData.layout.span
+      );
+      await expectError(
+        reportSettlementFailure(betId, enclaveSigner, { switchboardFunction: otherFunction }),
+        "ConstraintAddress"
+      );
+      // Without the bet's request or the game's routine nothing vouches for the signer
+      await expectError(reportSettlementFailure(betId, enclaveSigner), "Unauthorized");
+
+      const bet = await program.account.bet.fetch(betPda(betId));
+      expect(bet.active).to.be.true;
+
+      await cancelBet(betId, user);
+      expect(await balance(tokenAccount(user.publicKey))).to.equal(before);
+    });
+
+    it("refunds bets the enclave could not price right away", async () => {
+      const before = await balance(tokenAccount(user.publicKey));
+      const betId = await placeBet(user, { pair: DOGE });
+      const bet = await program.account.bet.fetch(betPda(betId));
+      const { lockedLiquidity } = await program.account.gameState.fetch(gameStatePda());
+      await warp(bet.endTime.toNumber() + 1 - (await now()));
+
+      const request = switchboardRequest(enclaveSigner.publicKey);
+      const batch = await settlementBatch(
+        request,
+        Math.floor(bet.endTime.toNumber() / SETTLEMENT_BUCKET),
+        [betId]
+      );
+      const options = { switchboardRequest: request, settlementBatch: batch };
+      await expectError(
+        reportSettlementFailure(betId, Keypair.generate(), options),
+        "Unauthorized"
+      );
+      await reportSettlementFailure(betId, enclaveSigner, options);
+
+      // Refunded within the cancel buffer, and the payout no longer locked
+      expect(await balance(tokenAccount(user.publicKey))).to.equal(before);
+      const state = await program.account.gameState.fetch(gameStatePda());
+      expect(state.lockedLiquidity.toNumber()).to.equal(
+        lockedLiquidity.toNumber() - bet.payout.toNumber()
+      );
+      expect((await program.account.bet.fetch(betPda(betId))).active).to.be.false;
+
+      const failed = lastEvents().find((event) => event.name === "SettlementFailed");
+      expect(failed.data.betId.toNumber()).to.equal(betId);
+      expect(failed.data.user.equals(user.publicKey)).to.be.true;
+      expect(failed.data.reason).to.deep.equal({ priceUnavailable: {} });
+      expect(failed.data.refund.toNumber()).to.equal(STAKE);
+    });
+  });
+
+  describe("[user-048] batch settlement", () => {
+    const LINK = ticker("LINKUSDX");
+    const LINK_FEED = feedId("8ac0c70fff57e9aefdf5edf44b51d62c2d433653cbb2cf5cc06bb115af04d221");
+
+    let user: Keypair;
+    let switchboardState: PublicKey;
+    let attestationQueue: PublicKey;
+
+    /** Requests a batch run for `bucket` over the given bet and pair accounts. */
+    async function requestBatchSettlement(bucket: number, accounts: PublicKey[]) {
+      const switchboardRequest = Keypair.generate();
+      await program.methods
+        .requestBatchSettlement(new anchor.BN(bucket))
+        .accounts({
+          payer: payer.publicKey,
+          gameConfig: gameConfigPda(),
+          settlementBatch: settlementBatchPda(switchboardRequest.publicKey),
+          switchboard: SWITCHBOARD_ATTESTATION_PROGRAM,
+          switchboardState,
+          switchboardAttestationQueue: attestationQueue,
+          switchboardFunction,
+          switchboardRequest: switchboardRequest.publicKey,
+          switchboardRequestEscrow: Keypair.generate().publicKey,
+          switchboardMint: NATIVE_MINT,
+        })
+        .remainingAccounts(
+          accounts.map((pubkey) => ({ pubkey, isSigner: false, isWritable: false }))
+        )
+        .signers([switchboardRequest])
+        .rpc();
+    }
+
+    before(async () => {
+      await ensurePair(BTC, BTC_FEED, true);
+      await ensurePair(LINK, LINK_FEED, false);
+      user = await newUser();
+
+      // Requests are validated before the Switchboard CPI, so stand-ins for the
+      // attestation program and its accounts are enough to reach the checks.
+      context.setAccount(SWITCHBOARD_ATTESTATION_PROGRAM, {
+        lamports: LAMPORTS_PER_SOL,
+        data: Buffer.alloc(0),
+        owner: BPF_LOADER,
+        executable: true,
+      });
+      const [state, stateBump] = PublicKey.findProgramAddressSync(
+        [Buffer.from("STATE")],
+        SWITCHBOARD_ATTESTATION_PROGRAM
+      );
+      switchboardState = switchboardAccount(
+        "AttestationProgramState",
+        attestationTypes.AttestationProgramState.layout.span,
+        state
+      );
+      const stateAccount = await context.banksClient.getAccount(switchboardState);
+      const stateData = Buffer.from(stateAccount.data);
+      stateData.writeUInt8(
+        stateBump,
+        8 + attestationTypes.AttestationProgramState.layout.offsetOf("bump")
+      );
+      context.setAccount(switchboardState, { ...stateAccount, data: stateData });
+      attestationQueue = switchboardAccount(
+        "AttestationQueueAccountData",
+        attestationTypes.AttestationQueueAccountData.layout.span
+      );
+
+      if (!(await accountExists(NATIVE_MINT))) {
+        const mintData = Buffer.alloc(MINT_SIZE);
+        MintLayout.encode(
+          {
+            mintAuthorityOption: 0,
+            mintAuthority: PublicKey.default,
+            supply: BigInt(0),
+            decimals: 9,
+            isInitialized: true,
+            freezeAuthorityOption: 0,
+            freezeAuthority: PublicKey.default,
+          },
+          mintData
+        );
+        context.setAccount(NATIVE_MINT, {
+          lamports: LAMPORTS_PER_SOL,
+          data: mintData,
+          owner: TOKEN_PROGRAM_ID,
+          executable: false,
+        });
+      }
+    });
+
+    it("takes one to three bets per batch", async () => {
+      const betId = await placeBet(user, { pair: LINK });
+      const bet = [betPda(betId), pairPda(LINK)];
+      const bucket = Math.floor((await now()) / SETTLEMENT_BUCKET) - 1;
+
+      await expectError(requestBatchSettlement(bucket, []), "InvalidBatch");
+      await expectError(requestBatchSettlement(bucket, [bet[0]]), "InvalidBatch");
+      await expectError(
+        requestBatchSettlement(bucket, [...bet, ...bet, ...bet, ...bet]),
+        "InvalidBatch"
+      );
+
+      await cancelBet(betId, user);
+    });
+
+    it("only batches active bets of an ended bucket", async () => {
+      const betId = await placeBet(user, { pair: LINK });
+      const pullBetId = await placeBet(await newUser(), { pair: BTC });
+      const bet = await program.account.bet.fetch(betPda(betId));
+      const bucket = Math.floor(bet.endTime.toNumber() / SETTLEMENT_BUCKET);
+      const accounts = [betPda(betId), pairPda(LINK)];
+
+      await expectError(requestBatchSettlement(bucket, accounts), "InvalidTimestamp");
+      await warp((bucket + 1) * SETTLEMENT_BUCKET - (await now()));
+
+      await expectError(requestBatchSettlement(bucket - 1, accounts), "InvalidBatch");
+      await expectError(requestBatchSettlement(bucket, [...accounts, ...accounts]), "InvalidBatch");
+      await expectError(
+        requestBatchSettlement(bucket, [betPda(betId), pairPda(BTC)]),
+        "InvalidBatch"
+      );
+      await expectError(
+        requestBatchSettlement(bucket, [betPda(pullBetId), pairPda(BTC)]),
+        "WrongOracle"
+      );
+
+      await settleWithPyth(pullBetId, 100_000, 101_000);
+      await expectError(
+        requestBatchSettlement(bucket, [betPda(pullBetId), pairPda(BTC)]),
+        "InvalidBatch"
+      );
+
+      await cancelBet(betId, user);
+    });
+
+    it("settles the bets of a batch and closes it", async () => {
+      // Bets placed at the start of a bucket all end within it
+      await warp(SETTLEMENT_BUCKET - ((await now()) % SETTLEMENT_BUCKET));
+      const bettors = [user, await newUser(), await newUser(), await newUser()];
+      const betIds: number[] = [];
+      for (const bettor of bettors) {
+        betIds.push(await placeBet(bettor, { pair: LINK }));
+      }
+      const outsideBetId = betIds.pop();
+      const bet = await program.account.bet.fetch(betPda(betIds[0]));
+      const bucket = Math.floor(bet.endTime.toNumber() / SETTLEMENT_BUCKET);
+      await warp((bucket + 1) * SETTLEMENT_BUCKET - (await now()));
+
+      const enclaveSigner = Keypair.generate();
+      const keeper = Keypair.generate().publicKey;
+      const request = switchboardRequest(enclaveSigner.publicKey);
+      const batch = await settlementBatch(request, bucket, betIds, keeper);
+      const options = { switchboardRequest: request, settlementBatch: batch };
+      const closeBatch = () =>
+        program.methods
+          .closeSettlementBatch()
+          .accounts({ settlementBatch: batch, keeper })
+          .remainingAccounts(
+            betIds.map((betId) => ({ pubkey: betPda(betId), isSigner: false, isWritable: false }))
+          )
+          .rpc();
+
+      // The batch only vouches for the bets it was requested for
+      await expectError(
+        settleBet(outsideBetId, 100_000, 101_000, enclaveSigner, options),
+        "InvalidBatch"
+      );
+
+      const before = await balance(tokenAccount(user.publicKey));
+      await settleBet(betIds[0], 100_000, 101_000, enclaveSigner, options);
+      expect(await balance(tokenAccount(user.publicKey))).to.equal(before + PAYOUT);
+      await expectError(closeBatch(), "BatchNotSettled");
+
+      await settleBet(betIds[1], 100_000, 99_000, enclaveSigner, options);
+      await reportSettlementFailure(betIds[2], enclaveSigner, options);
+      for (const betId of betIds) {
+        expect((await program.account.bet.fetch(betPda(betId))).active).to.be.false;
+      }
+
+      // The keeper gets the batch's rent back
+      const rent = await lamports(batch);
+      await closeBatch();
+      expect(await accountExists(batch)).to.be.false;
+      expect(await lamports(keeper)).to.equal(rent);
+
+      await cancelBet(outsideBetId, bettors[3]);
+    });
+  });
+
+  describe("[user-049] settlement routine", () => {
+    const AVAX = ticker("AVAXUSDX");
+    const AVAX_FEED = feedId("93da3352f9f1d105fdfe4971cfa80e9dd777bfc5d0f683ebb6e1294b92137bb7");
+
+    let user: Keypair;
+    let enclaveSigner: Keypair;
+
+    const functionRoutine = () =>
+      switchboardAccount(
+        "FunctionRoutineAccountData",
+        attestationTypes.FunctionRoutineAccountData.layout.span
+      );
+
+    before(async () => {
+      await ensurePair(AVAX, AVAX_FEED, false);
+      user = await newUser();
+      enclaveSigner = Keypair.generate();
+    });
+
+    after(async () => {
+      await applyConfigChange({ setSwitchboardRoutine: { switchboardRoutine: PublicKey.default } });
+    });
+
+    it("places bets without a Switchboard request of their own", async () => {
+      const betId = await placeBet(user, { pair: AVAX });
+      const bet = await program.account.bet.fetch(betPda(betId));
+      expect(bet.switchboardRequest.equals(PublicKey.default)).to.be.true;
+      await cancelBet(betId, user);
+    });
+
+    it("sets the routine through a config change", async () => {
+      const routine = functionRoutine();
+      await applyConfigChange({ setSwitchboardRoutine: { switchboardRoutine: routine } });
+
+      const config = await program.account.gameConfig.fetch(gameConfigPda());
+      expect(config.switchboardRoutine.equals(routine)).to.be.true;
+    });
+
+    it("only lets the game's routine settle bets", async () => {
+      const before = await balance(tokenAccount(user.publicKey));
+      const betId = await placeBet(user, { pair: AVAX });
+
+      await expectError(
+        reportSettlementFailure(betId, enclaveSigner, { switchboardRoutine: functionRoutine() }),
+        "Unauthorized"
+      );
+
+      await applyConfigChange({ setSwitchboardRoutine: { switchboardRoutine: PublicKey.default } });
+      await expectError(
+        reportSettlementFailure(betId, enclaveSigner, { switchboardRoutine: functionRoutine() }),
+        "Unauthorized"
+      );
+
+      await cancelBet(betId, user);
+      expect(await balance(tokenAccount(user.publicKey))).to.equal(before);
+    });
+
+    it("settles bets signed by the routine's enclave", async () => {
+      const betId = await placeBet(user, { pair: AVAX });
+      const bet = await program.account.bet.fetch(betPda(betId));
+      await warp(bet.endTime.toNumber() + 1 - (await now()));
+
+      const routine = switchboardRoutine(enclaveSigner.publicKey);
+      await applyConfigChange({ setSwitchboardRoutine: { switchboardRoutine: routine } });
+      const options = { switchboardRoutine: routine };
+      await expectError(
+        settleBet(betId, 100_000, 101_000, Keypair.generate(), options),
+        "Unauthorized"
+      );
+
+      const before = await balance(tokenAccount(user.publicKey));
+      await settleBet(betId, 100_000, 101_000, enclaveSigner, options);
+      expect(await balance(tokenAccount(user.publicKey))).to.equal(before + PAYOUT);
+      expect((await program.account.bet.fetch(betPda(betId))).active).to.be.false;
+    });
+  });
+
+  describe("[user-050] settlement events", () => {
+    let user: Keypair;
+
+    const event = (name: string) => {
+      const found = lastEvents().find((e) => e.name === name);
+      expect(found, `no ${name} event`).to.not.be.undefined;
+      return found.data;
+    };
+
+    before(async () => {
+      await ensurePair(BTC, BTC_FEED, true);
+      user = await newUser();
+    });
+
+    it("emits placed bets", async () => {
+      const betId = await placeBet(user, { isLong: false });
+      const bet = await program.account.bet.fetch(betPda(betId));
+
+      const placed = event("BetPlaced");
+      expect(placed.betId.toNumber()).to.equal(betId);
+      expect(placed.user.equals(user.publicKey)).to.be.true;
+      expect(placed.amount.toNumber()).to.equal(STAKE);
+      expect(placed.pair).to.deep.equal(BTC);
+      expect(placed.interval).to.equal(INTERVAL);
+      expect(placed.isLong).to.be.false;
+      expect(placed.startTime.toNumber()).to.equal(bet.startTime.toNumber());
+
+      await settleWithPyth(betId, 101_000, 100_000);
+    });
+
+    it("emits settlements with their prices and fee", async () => {
+      const betId = await placeBet(user);
+      const bet = await program.account.bet.fetch(betPda(betId));
+      await settleWithPyth(betId, 100_000, 101_000, { expo: -4 });
+
+      const executed = event("BetExecuted");
+      expect(executed.betId.toNumber()).to.equal(betId);
+      expect(executed.user.equals(user.publicKey)).to.be.true;
+      expect(executed.pair).to.deep.equal(BTC);
+      expect(executed.isLong).to.be.true;
+      expect(executed.amount.toNumber()).to.equal(STAKE);
+      expect(executed.startTime.toNumber()).to.equal(bet.startTime.toNumber());
+      expect(executed.endTime.toNumber()).to.equal(bet.endTime.toNumber());
+      expect(executed.openPrice.toNumber()).to.equal(100_000);
+      expect(executed.closePrice.toNumber()).to.equal(101_000);
+      expect(executed.priceExpo).to.equal(-4);
+      expect(executed.won).to.be.true;
+      expect(executed.payout.toNumber()).to.equal(PAYOUT);
+      expect(executed.fee.toNumber()).to.equal(STAKE - PAYOUT / 2);
+    });
+
+    it("emits cancelled bets", async () => {
+      const betId = await placeBet(user);
+      await cancelBet(betId, user);
+
+      const cancelled = event("BetCancelled");
+      expect(cancelled.betId.toNumber()).to.equal(betId);
+      expect(cancelled.user.equals(user.publicKey)).to.be.true;
+    });
+
+    it("emits config changes when proposed and executed", async () => {
+      const change = { setMaxPublishDelay: { maxPublishDelay: 10 } };
+      const changeId = await proposeConfigChange(change);
+      const proposed = event("ConfigChangeProposed");
+      expect(proposed.configChange.equals(configChangePda(changeId))).to.be.true;
+      expect(proposed.change.setMaxPublishDelay.maxPublishDelay).to.equal(10);
+      expect(proposed.executeAfter.toNumber()).to.equal((await now()) + CONFIG_CHANGE_DELAY);
+
+      await warp(CONFIG_CHANGE_DELAY);
+      await executeConfigChange(changeId);
+      const executed = event("ConfigChangeExecuted");
+      expect(executed.configChange.equals(configChangePda(changeId))).to.be.true;
+      expect(executed.change.setMaxPublishDelay.maxPublishDelay).to.equal(10);
+    });
   });
 });